@@ -0,0 +1,254 @@
+#![doc = include_str!("../README.md")]
+#![forbid(future_incompatible)]
+#![warn(missing_docs, missing_debug_implementations, bare_trait_objects)]
+
+use std::{io, time::Duration};
+pub use db_map_trait::{
+    DBMap,
+    Error,
+    Result,
+};
+
+/// Run [`DBMap`] operations from async code.
+///
+/// Each operation runs on tokio's blocking thread pool (see [`tokio::task::spawn_blocking`])
+/// against a clone of the wrapped database, so a slow or blocked operation never stalls the
+/// async runtime. The adapter must be used from within a tokio runtime.
+///
+/// # Timeouts
+///
+/// With [`with_timeout`][AsyncAdapter::with_timeout], every operation is bounded by
+/// [`tokio::time::timeout`] and fails with [`Error::Timeout`] when the limit is exceeded. A
+/// blocking operation cannot be interrupted, so it is detached rather than cancelled: it may
+/// still complete in the background after the timeout error has been returned (for example, an
+/// `insert` that timed out may still be written).
+#[derive(Clone, Debug)]
+pub struct AsyncAdapter<M> {
+    db: M,
+    timeout: Option<Duration>,
+}
+
+impl<M> AsyncAdapter<M>
+where
+    M: DBMap + Send + Sync + 'static,
+{
+    /// Wrap a database for use from async code.
+    pub fn new(db: M) -> Self {
+        Self {
+            db,
+            timeout: None,
+        }
+    }
+
+    /// Limit every operation to `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_async::AsyncAdapter;
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::time::Duration;
+    ///
+    /// let db = AsyncAdapter::new(BTreeMapDB::open())
+    ///     .with_timeout(Duration::from_millis(250));
+    /// assert_eq!(db.timeout(), Some(Duration::from_millis(250)));
+    /// ```
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Get the time limit for each operation, if there is one.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Get the wrapped database.
+    pub fn inner(&self) -> &M {
+        &self.db
+    }
+
+    /// Get the data for a specified key.
+    ///
+    /// See [`DBMap::get`].
+    pub async fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>> {
+        let key = key.as_ref().to_vec();
+        self.run(move |db| db.get(key)).await
+    }
+
+    /// Get the data for a specified key and use a function to transform it.
+    ///
+    /// See [`DBMap::get_map`].
+    pub async fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T + Send + 'static,
+            T: Send + 'static,
+    {
+        let key = key.as_ref().to_vec();
+        self.run(move |db| db.get_map(key, mapper)).await
+    }
+
+    /// Insert data for a specified key.
+    ///
+    /// See [`DBMap::insert`].
+    pub async fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        let (key, value) = (key.as_ref().to_vec(), value.as_ref().to_vec());
+        self.run(move |db| db.insert(key, value)).await
+    }
+
+    /// Insert data for a specified key, returning the old value.
+    ///
+    /// See [`DBMap::fetch_and_replace`].
+    pub async fn fetch_and_replace<K, V>(&self, key: K, value: V) -> Result<Option<Vec<u8>>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let (key, value) = (key.as_ref().to_vec(), value.as_ref().to_vec());
+        self.run(move |db| db.fetch_and_replace(key, value)).await
+    }
+
+    /// Remove a specified key.
+    ///
+    /// See [`DBMap::remove`].
+    pub async fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        let key = key.as_ref().to_vec();
+        self.run(move |db| db.remove(key)).await
+    }
+
+    /// Atomically replace the value for a key with the result of a function.
+    ///
+    /// See [`DBMap::update`].
+    pub async fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>> + Send + 'static,
+    {
+        let key = key.as_ref().to_vec();
+        self.run(move |db| db.update(key, f)).await
+    }
+
+    /// Get the number of entries in the database.
+    ///
+    /// See [`DBMap::len`].
+    pub async fn len(&self) -> Result<usize> {
+        self.run(|db| db.len()).await
+    }
+
+    /// Return `true` if the database has no entries.
+    ///
+    /// See [`DBMap::is_empty`].
+    pub async fn is_empty(&self) -> Result<bool> {
+        self.run(|db| db.is_empty()).await
+    }
+
+    /// Run a blocking operation on a clone of the database, subject to the timeout.
+    async fn run<F, T>(&self, op: F) -> Result<T>
+        where
+            F: FnOnce(M) -> Result<T> + Send + 'static,
+            T: Send + 'static,
+    {
+        let db = self.db.clone();
+        let task = tokio::task::spawn_blocking(move || op(db));
+        let joined = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, task).await
+                .map_err(|_| Error::Timeout(timeout))?,
+            None => task.await,
+        };
+        match joined {
+            Ok(result) => result,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => Err(io::Error::other(err).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db_map_btreemap::BTreeMapDB;
+    use std::time::Instant;
+
+    /// A database whose reads take `delay` to complete.
+    #[derive(Clone, Debug)]
+    struct SlowDB {
+        db: BTreeMapDB,
+        delay: Duration,
+    }
+
+    impl DBMap for SlowDB {
+        fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+            where
+                K: AsRef<[u8]>,
+                F: FnOnce(&[u8]) -> T,
+        {
+            std::thread::sleep(self.delay);
+            self.db.get_map(key, mapper)
+        }
+
+        fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+            self.db.insert(key, value)
+        }
+
+        fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+            where
+                K: AsRef<[u8]>,
+                V: AsRef<[u8]>,
+                F: FnOnce(&[u8]) -> T,
+        {
+            self.db.fetch_and_replace_map(key, value, mapper)
+        }
+
+        fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+            self.db.remove(key)
+        }
+
+        fn update<K, F>(&self, key: K, f: F) -> Result<()>
+            where
+                K: AsRef<[u8]>,
+                F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+        {
+            self.db.update(key, f)
+        }
+
+        fn try_for_each<F>(&self, f: F) -> Result<()>
+            where
+                F: FnMut(&[u8], &[u8]) -> Result<()>,
+        {
+            self.db.try_for_each(f)
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_operation_times_out() {
+        let db = AsyncAdapter::new(SlowDB {
+            db: BTreeMapDB::open(),
+            delay: Duration::from_millis(500),
+        }).with_timeout(Duration::from_millis(50));
+
+        // Writes are fast and finish in time.
+        db.insert(b"key", b"value").await.unwrap();
+
+        let start = Instant::now();
+        match db.get(b"key").await {
+            Err(Error::Timeout(timeout)) => assert_eq!(timeout, Duration::from_millis(50)),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn operations_without_timeout_complete() {
+        let db = AsyncAdapter::new(SlowDB {
+            db: BTreeMapDB::open(),
+            delay: Duration::from_millis(50),
+        });
+        db.insert(b"key", b"value").await.unwrap();
+        assert_eq!(db.get(b"key").await.unwrap(), Some(b"value".to_vec()));
+        assert_eq!(db.fetch_and_replace(b"key", b"new").await.unwrap(), Some(b"value".to_vec()));
+        assert_eq!(db.len().await.unwrap(), 1);
+        db.remove(b"key").await.unwrap();
+        assert!(db.is_empty().await.unwrap());
+    }
+}
@@ -0,0 +1,42 @@
+//! Compare the single-lock `BTreeMapDB` with the lock-striped `ShardedMemDb` under contention.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use db_map_btreemap::{BTreeMapDB, ShardedMemDb};
+use db_map_trait::DBMap;
+use std::thread;
+
+/// The number of threads hammering the database at once.
+const THREADS: u32 = 8;
+
+/// The number of insert + get pairs each thread performs.
+const OPS_PER_THREAD: u32 = 10_000;
+
+/// Insert and read back distinct keys from every thread at once.
+fn hammer<M: DBMap + Send + Sync>(db: &M) {
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move || {
+                for i in 0..OPS_PER_THREAD {
+                    let key = (t * OPS_PER_THREAD + i).to_be_bytes();
+                    db.insert(key, key).unwrap();
+                    assert!(db.get(key).unwrap().is_some());
+                }
+            });
+        }
+    });
+}
+
+fn single_lock_vs_sharded(c: &mut Criterion) {
+    let mut group = c.benchmark_group("8_threads_10k_ops");
+    group.sample_size(10);
+    group.bench_function("btreemap", |b| {
+        b.iter(|| hammer(&BTreeMapDB::open()))
+    });
+    group.bench_function("sharded", |b| {
+        b.iter(|| hammer(&ShardedMemDb::open()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, single_lock_vs_sharded);
+criterion_main!(benches);
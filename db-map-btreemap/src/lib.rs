@@ -6,16 +6,46 @@ use parking_lot::Mutex;
 use std::{
     cell::RefCell,
     collections::BTreeMap,
+    io::{Read, Write},
+    ops::Bound,
     sync::Arc,
 };
 use db_map_trait::{
+    export::{read_binary_entry, write_binary_entry},
+    move_key,
+    Capabilities,
     DBMap,
+    Error,
+    OpenDb,
+    Page,
     Result,
 };
 
+pub mod read_only;
+pub mod sharded;
+
+pub use read_only::ReadOnlyView;
+pub use sharded::ShardedMemDb;
+
+/// The estimated memory used by each entry on top of its key and value bytes, used by
+/// [`BTreeMapDB::memory_usage`].
+///
+/// This is the size of the key's and value's `Vec` headers. The share of the `BTreeMap` node
+/// each entry lives in is not counted.
+pub const ENTRY_OVERHEAD: usize = 2 * std::mem::size_of::<Vec<u8>>();
+
+/// The magic bytes at the start of every file written by [`BTreeMapDB::save`].
+pub const FORMAT_MAGIC: [u8; 4] = *b"DBMB";
+
+/// The format version written by [`BTreeMapDB::save`].
+pub const FORMAT_VERSION: u16 = 1;
+
+/// The map shared between all of the clones of a `BTreeMapDB`.
+type SharedMap = Arc<Mutex<RefCell<BTreeMap<Vec<u8>, Vec<u8>>>>>;
+
 #[doc = include_str!("../README.md")]
 #[derive(Clone, Debug, Default)]
-pub struct BTreeMapDB(Arc<Mutex<RefCell<BTreeMap<Vec<u8>, Vec<u8>>>>>);
+pub struct BTreeMapDB(SharedMap);
 
 impl BTreeMapDB {
     /// Open a `BTreeMapDB` "database".
@@ -43,6 +73,281 @@ impl BTreeMapDB {
     pub fn open() -> Self {
         Self::default()
     }
+
+    /// Create a `BTreeMapDB` "database" populated with the key/value pairs from an iterator.
+    ///
+    /// If a key appears more than once, the last value wins.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([
+    ///     ("apple", "red"),
+    ///     ("grape", "purple"),
+    /// ]);
+    ///
+    /// assert_eq!(db.get(b"apple").unwrap(), Some(b"red".to_vec()));
+    /// assert_eq!(db.get(b"grape").unwrap(), Some(b"purple".to_vec()));
+    /// assert!(db.get(b"banana").unwrap().is_none());
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_iter<I, K, V>(iter: I) -> Self
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let map = iter.into_iter()
+            .map(|(k, v)| (k.as_ref().to_vec(), v.as_ref().to_vec()))
+            .collect();
+        Self(Arc::new(Mutex::new(RefCell::new(map))))
+    }
+
+    /// Open a `BTreeMapDB` "database" already holding the given key/value pairs.
+    ///
+    /// This is [`from_iter`][BTreeMapDB::from_iter] under a name that reads alongside
+    /// [`open`][BTreeMapDB::open], for setting up test fixtures inline. If a key appears more
+    /// than once, the last value wins.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open_with([
+    ///     (b"a".to_vec(), vec![1]),
+    ///     (b"b".to_vec(), vec![2]),
+    ///     (b"a".to_vec(), vec![3]),
+    /// ]);
+    ///
+    /// assert_eq!(db.len().unwrap(), 2);
+    /// assert_eq!(db.get(b"a").unwrap(), Some(vec![3]));
+    /// assert_eq!(db.get(b"b").unwrap(), Some(vec![2]));
+    /// ```
+    pub fn open_with<I, K, V>(data: I) -> Self
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        Self::from_iter(data)
+    }
+
+    /// Estimate how much memory the entries in the database use.
+    ///
+    /// This is the total length of every key and value plus [`ENTRY_OVERHEAD`] per entry. It is
+    /// a ballpark figure, not exact allocator accounting: spare `Vec` capacity, the `BTreeMap`
+    /// nodes, and allocator bookkeeping aren't included.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::{BTreeMapDB, ENTRY_OVERHEAD};
+    ///
+    /// let db = BTreeMapDB::open();
+    /// assert_eq!(db.memory_usage(), 0);
+    ///
+    /// db.insert(b"key", [0_u8; 100]).unwrap();
+    /// assert_eq!(db.memory_usage(), 3 + 100 + ENTRY_OVERHEAD);
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        map.iter()
+            .map(|(key, value)| key.len() + value.len() + ENTRY_OVERHEAD)
+            .sum()
+    }
+
+    /// Get a read-only view of the database, which borrows this handle.
+    ///
+    /// The view implements [`DBMapRead`][db_map_trait::DBMapRead] but not `DBMap`, so it can be
+    /// handed to code that must not write. Unlike a [`checkpoint`][BTreeMapDB::checkpoint], it
+    /// isn't a copy: every write to the database is visible through it straight away. See
+    /// [`ReadOnlyView`] for an example.
+    pub fn as_read_only(&self) -> ReadOnlyView<'_> {
+        ReadOnlyView::new(self)
+    }
+
+    /// Take a snapshot of the database that can later be restored.
+    ///
+    /// The snapshot is a copy of every entry, taken while the map is locked. Restoring it with
+    /// [`Checkpoint::restore`] undoes every change made since, through this handle or any of its
+    /// clones. Dropping the checkpoint instead keeps the changes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([("apple", "red"), ("grape", "purple")]);
+    /// let checkpoint = db.checkpoint();
+    ///
+    /// db.insert(b"banana", b"yellow").unwrap();
+    /// db.remove(b"apple").unwrap();
+    /// db.insert(b"grape", b"green").unwrap();
+    ///
+    /// checkpoint.restore();
+    /// assert_eq!(db.len().unwrap(), 2);
+    /// assert_eq!(db.get(b"apple").unwrap(), Some(b"red".to_vec()));
+    /// assert_eq!(db.get(b"grape").unwrap(), Some(b"purple".to_vec()));
+    /// assert!(db.get(b"banana").unwrap().is_none());
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow().clone();
+        Checkpoint {
+            db: self.0.clone(),
+            map,
+        }
+    }
+
+    /// Write the whole database to `writer`.
+    ///
+    /// The format is the 4 magic bytes [`FORMAT_MAGIC`], the format version [`FORMAT_VERSION`] as
+    /// a big-endian `u16`, and then each entry in key order as the key length (big-endian `u64`),
+    /// the key, the value length (big-endian `u64`), and the value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([("apple", "red"), ("grape", "purple")]);
+    ///
+    /// let mut file = Vec::new();
+    /// db.save(&mut file).unwrap();
+    ///
+    /// let loaded = BTreeMapDB::load(file.as_slice()).unwrap();
+    /// assert_eq!(loaded.get(b"apple").unwrap(), Some(b"red".to_vec()));
+    /// assert_eq!(loaded.get(b"grape").unwrap(), Some(b"purple".to_vec()));
+    /// ```
+    pub fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        writer.write_all(&FORMAT_MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_be_bytes())?;
+        for (key, value) in map.iter() {
+            write_binary_entry(&mut writer, key, value)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read a database previously written by [`save`][BTreeMapDB::save].
+    ///
+    /// Returns [`Error::BadMagic`] if the data does not start with [`FORMAT_MAGIC`], and
+    /// [`Error::UnsupportedFormat`] if it was written with a format version this library does
+    /// not understand.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::Error;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let mut file = b"DBMB".to_vec();
+    /// file.extend_from_slice(&99_u16.to_be_bytes());
+    ///
+    /// assert!(matches!(
+    ///     BTreeMapDB::load(file.as_slice()),
+    ///     Err(Error::UnsupportedFormat { version: 99 }),
+    /// ));
+    /// ```
+    pub fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0_u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != FORMAT_MAGIC {
+            return Err(Error::BadMagic { magic });
+        }
+        let mut version = [0_u8; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_be_bytes(version);
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedFormat { version });
+        }
+        let mut map = BTreeMap::new();
+        while let Some((key, value)) = read_binary_entry(&mut reader)? {
+            map.insert(key, value);
+        }
+        Ok(Self(Arc::new(Mutex::new(RefCell::new(map)))))
+    }
+}
+
+/// Check for a range that [`BTreeMap::range`] would panic on: one that starts after it ends, or
+/// that excludes the same key at both ends. Both of those are empty.
+fn range_is_empty(start: Bound<&[u8]>, end: Bound<&[u8]>) -> bool {
+    match (start, end) {
+        (Bound::Excluded(start), Bound::Excluded(end)) => start >= end,
+        (Bound::Included(start) | Bound::Excluded(start), Bound::Included(end) | Bound::Excluded(end)) => start > end,
+        _ => false,
+    }
+}
+
+/// A snapshot of a [`BTreeMapDB`] from [`BTreeMapDB::checkpoint`].
+pub struct Checkpoint {
+    db: SharedMap,
+    map: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Checkpoint {
+    /// Put the database back to the state it was in when the checkpoint was taken.
+    ///
+    /// The map is swapped in one step while it is locked, so other threads see either every
+    /// change made since the checkpoint or none of them.
+    pub fn restore(self) {
+        let map_lock = self.db.lock();
+        *map_lock.borrow_mut() = self.map;
+    }
+}
+
+impl std::fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("entries", &self.map.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Collect key/value pairs into a new `BTreeMapDB`.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMap;
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let pairs: Vec<(Vec<u8>, Vec<u8>)> = vec![
+///     (b"key1".to_vec(), b"value1".to_vec()),
+///     (b"key2".to_vec(), b"value2".to_vec()),
+///     (b"key3".to_vec(), b"value3".to_vec()),
+/// ];
+///
+/// let db: BTreeMapDB = pairs.into_iter().collect();
+///
+/// assert_eq!(db.get(b"key1").unwrap(), Some(b"value1".to_vec()));
+/// assert_eq!(db.get(b"key2").unwrap(), Some(b"value2".to_vec()));
+/// assert_eq!(db.get(b"key3").unwrap(), Some(b"value3".to_vec()));
+/// ```
+impl FromIterator<(Vec<u8>, Vec<u8>)> for BTreeMapDB {
+    fn from_iter<I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>>(iter: I) -> Self {
+        Self(Arc::new(Mutex::new(RefCell::new(iter.into_iter().collect()))))
+    }
+}
+
+/// A `BTreeMapDB` needs no configuration, so its config is `()`.
+impl OpenDb for BTreeMapDB {
+    type Config = ();
+
+    fn open(_config: ()) -> Result<Self> {
+        Ok(Self::open())
+    }
 }
 
 impl DBMap for BTreeMapDB {
@@ -63,6 +368,22 @@ impl DBMap for BTreeMapDB {
         Ok(())
     }
 
+    /// The chunks are joined into the `Vec` that is stored, so they are only copied once.
+    fn insert_chunks<K, I, V>(&self, key: K, chunks: I) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            I: IntoIterator<Item = V>,
+            V: AsRef<[u8]>,
+    {
+        let mut value = Vec::new();
+        for chunk in chunks {
+            value.extend_from_slice(chunk.as_ref());
+        }
+        let map_lock = self.0.lock();
+        map_lock.borrow_mut().insert(key.as_ref().to_vec(), value);
+        Ok(())
+    }
+
     fn fetch_and_replace<K, V>(&self, key: K, value: V) -> Result<Option<Vec<u8>>>
         where
             K: AsRef<[u8]>,
@@ -91,6 +412,200 @@ impl DBMap for BTreeMapDB {
         map.remove(key.as_ref());
         Ok(())
     }
+
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let map_lock = self.0.lock();
+        let mut map = map_lock.borrow_mut();
+        match f(map.get(key.as_ref()).map(|v| v.as_slice())) {
+            Some(value) => { map.insert(key.as_ref().to_vec(), value); }
+            None => { map.remove(key.as_ref()); }
+        }
+        Ok(())
+    }
+
+    /// Change the value's `Vec` in the map, under the lock.
+    fn map_in_place<K, F>(&self, key: K, f: F) -> Result<bool>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&mut [u8]),
+    {
+        let map_lock = self.0.lock();
+        let mut map = map_lock.borrow_mut();
+        Ok(map.get_mut(key.as_ref()).map(|value| f(value)).is_some())
+    }
+
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let map_lock = self.0.lock();
+        let mut map = map_lock.borrow_mut();
+        map.extend(items.into_iter().map(|(k, v)| (k.as_ref().to_vec(), v.as_ref().to_vec())));
+        Ok(())
+    }
+
+    /// Call a function for every entry in the database, in key order, until it returns an error.
+    ///
+    /// The entries are borrowed straight from the map, so nothing is copied or allocated for each
+    /// entry. The lock is held for the whole scan, so the function must not use this database (or
+    /// any clone of it): the lock isn't reentrant, and using it again deadlocks.
+    fn try_for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        for (key, value) in map.iter() {
+            f(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Call a function for every entry under a prefix, in key order, until it returns an error.
+    ///
+    /// This seeks straight to the prefix with [`BTreeMap::range`]. Like
+    /// [`try_for_each`][DBMap::try_for_each], the lock is held for the whole scan.
+    fn try_scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let prefix = prefix.as_ref();
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        for (key, value) in map.range::<[u8], _>((Bound::Included(prefix), Bound::Unbounded)) {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            f(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Call a function for every entry in a key range, in key order, until it returns an error.
+    ///
+    /// This scans a [`BTreeMap::range`]. Like [`try_for_each`][DBMap::try_for_each], the lock is
+    /// held for the whole scan.
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        if range_is_empty(start, end) {
+            return Ok(());
+        }
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        for (key, value) in map.range::<[u8], _>((start, end)) {
+            f(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Move the entries under the lock, so no reader sees them half moved.
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        let (from_prefix, to_prefix) = (from_prefix.as_ref(), to_prefix.as_ref());
+        let map_lock = self.0.lock();
+        let mut map = map_lock.borrow_mut();
+        let keys: Vec<Vec<u8>> = map.range::<[u8], _>((Bound::Included(from_prefix), Bound::Unbounded))
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(from_prefix))
+            .cloned()
+            .collect();
+        // Take every entry out before putting any back, so overlapping prefixes can't collide.
+        let moved: Vec<_> = keys.into_iter()
+            .filter_map(|key| {
+                let new_key = move_key(&key, from_prefix, to_prefix)?;
+                map.remove(&key).map(|value| (new_key, value))
+            })
+            .collect();
+        let count = moved.len();
+        map.extend(moved);
+        Ok(count)
+    }
+
+    /// Count the [`BTreeMap::range`] without calling anything for each entry.
+    fn count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        if range_is_empty(start, end) {
+            return Ok(0);
+        }
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        Ok(map.range::<[u8], _>((start, end)).count())
+    }
+
+    /// Get one page of the entries under a prefix, starting the [`BTreeMap::range`] just after
+    /// `start_after`.
+    fn scan_page<P: AsRef<[u8]>>(&self, prefix: P, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        let prefix = prefix.as_ref();
+        let start = match start_after {
+            Some(after) if after >= prefix => Bound::Excluded(after),
+            _ => Bound::Included(prefix),
+        };
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        let mut entries = map.range::<[u8], _>((start, Bound::Unbounded))
+            .take_while(|(key, _)| key.starts_with(prefix));
+        let page: Vec<_> = entries.by_ref()
+            .take(limit.max(1))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let next = match entries.next() {
+            Some(_) => page.last().map(|(key, _)| key.clone()),
+            None => None,
+        };
+        Ok((page, next))
+    }
+
+    fn first_value(&self) -> Result<Option<Vec<u8>>> {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        Ok(map.first_key_value().map(|(_, value)| value.clone()))
+    }
+
+    fn last_value(&self) -> Result<Option<Vec<u8>>> {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        Ok(map.last_key_value().map(|(_, value)| value.clone()))
+    }
+
+    fn len(&self) -> Result<usize> {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        Ok(map.len())
+    }
+
+    fn iter(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        Ok(map.iter().map(|(key, value)| (key.clone(), value.clone())).collect())
+    }
+
+    fn keys(&self) -> Result<Vec<Vec<u8>>> {
+        let map_lock = self.0.lock();
+        let map = map_lock.borrow();
+        Ok(map.keys().cloned().collect())
+    }
+
+    fn clear(&self) -> Result<()> {
+        let map_lock = self.0.lock();
+        map_lock.borrow_mut().clear();
+        Ok(())
+    }
+
+    /// Entries are kept in a `BTreeMap`, so scans are in key order, but they only live in memory.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            ordered_iteration: true,
+            ..Capabilities::default()
+        }
+    }
 }
 
 #[cfg(test)]
@@ -98,8 +613,350 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
     use db_map_test::impl_db_map_tests;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     impl_db_map_tests! {
         let db = BTreeMapDB::open();
     }
+
+    /// Run the suite through a boxed `DynDbMap` to check the type-erased forwarding.
+    mod boxed {
+        use super::*;
+        use db_map_trait::DynDbMap;
+
+        impl_db_map_tests! {
+            let db = Box::new(BTreeMapDB::open()) as Box<dyn DynDbMap>;
+        }
+    }
+
+    mod buffered {
+        use super::*;
+        use db_map_trait::Buffered;
+
+        impl_db_map_tests! {
+            let db = Buffered::new(BTreeMapDB::open(), 4);
+        }
+
+        #[test]
+        fn failed_compare_buffers_nothing() {
+            let db = Buffered::new(BTreeMapDB::from_iter([("key", "value")]), 4);
+            assert_eq!(db.fetch_and_replace_if(b"key", Some(b"other"), b"new").unwrap(), Err(Some(b"value".to_vec())));
+            assert_eq!(db.fetch_and_replace_if(b"absent", Some(b"other"), b"new").unwrap(), Err(None));
+            assert_eq!(db.try_insert(b"key", b"new").unwrap(), Err(b"value".to_vec()));
+            assert_eq!(db.pending(), 0);
+        }
+    }
+
+    mod expiring {
+        use super::*;
+        use db_map_trait::{expiring::ManualClock, Expiring};
+        use std::time::Duration;
+
+        impl_db_map_tests!{
+            let db = Expiring::new(BTreeMapDB::open(), Duration::from_secs(3600));
+        }
+
+        #[test]
+        fn failed_compare_keeps_the_expiry() {
+            let clock = ManualClock::new(std::time::UNIX_EPOCH);
+            let db = Expiring::with_clock(BTreeMapDB::open(), Duration::from_secs(60), clock.clock());
+            db.insert(b"key", b"value").unwrap();
+            let stored = db.inner().get(b"key").unwrap();
+
+            clock.advance(Duration::from_secs(30));
+            assert_eq!(db.fetch_and_replace_if(b"key", Some(b"other"), b"new").unwrap(), Err(Some(b"value".to_vec())));
+            assert_eq!(db.try_insert(b"key", b"new").unwrap(), Err(b"value".to_vec()));
+            assert_eq!(db.inner().get(b"key").unwrap(), stored);
+
+            // The entry still expires when it was first due to.
+            clock.advance(Duration::from_secs(30));
+            assert!(db.get(b"key").unwrap().is_none());
+        }
+
+        #[test]
+        fn short_stored_value_is_corruption() {
+            let db = Expiring::new(BTreeMapDB::from_iter([("key", "short")]), Duration::from_secs(60));
+            assert!(matches!(db.get(b"key"), Err(db_map_trait::Error::Corruption(_))));
+        }
+    }
+
+    mod namespaced {
+        use super::*;
+        use db_map_test::strategy::{namespaced_ops, Op};
+        use db_map_trait::Namespaced;
+
+        // Keys just before and after the namespace must never be seen through it.
+        impl_db_map_tests! {
+            let db = Namespaced::new(BTreeMapDB::from_iter([("ns.", "before"), ("ns0", "after")]), "ns/");
+        }
+
+        /// Apply `ops` through two namespaces over `inner`, checking after every op that each
+        /// namespace holds exactly what was written through it.
+        fn check_isolation<M: DBMap + Clone>(inner: M, ops: Vec<(usize, Op)>) -> std::result::Result<(), TestCaseError> {
+            let handles = [Namespaced::new(inner.clone(), "a/"), Namespaced::new(inner, "b/")];
+            let mut models = [BTreeMap::new(), BTreeMap::new()];
+            for (namespace, op) in ops {
+                let (db, model) = (&handles[namespace], &mut models[namespace]);
+                match op {
+                    Op::Insert(key, value) | Op::Overwrite(key, value) => {
+                        db.insert(&key, &value).unwrap();
+                        model.insert(key, value);
+                    }
+                    Op::Remove(key) => {
+                        db.remove(&key).unwrap();
+                        model.remove(&key);
+                    }
+                }
+                for (db, model) in handles.iter().zip(&models) {
+                    let mut entries = BTreeMap::new();
+                    db.for_each(|key, value| { entries.insert(key.to_vec(), value.to_vec()); }).unwrap();
+                    prop_assert_eq!(&entries, model);
+                    prop_assert_eq!(db.len().unwrap(), model.len());
+                }
+            }
+            Ok(())
+        }
+
+        proptest! {
+            #[test]
+            fn namespaces_are_isolated(ops in namespaced_ops(1, 40)) {
+                check_isolation(BTreeMapDB::open(), ops)?;
+            }
+
+            #[test]
+            fn namespaces_are_isolated_when_sharded(ops in namespaced_ops(1, 40)) {
+                check_isolation(ShardedMemDb::open(), ops)?;
+            }
+        }
+    }
+
+    mod cached {
+        use super::*;
+        use db_map_trait::cached::{Cached, Lfu};
+
+        impl_db_map_tests! {
+            let db = Cached::new(BTreeMapDB::open(), 4);
+        }
+
+        mod lfu {
+            use super::*;
+
+            impl_db_map_tests! {
+                let db = Cached::with_policy(BTreeMapDB::open(), 4, Lfu::new());
+            }
+
+            proptest! {
+                #[test]
+                fn hot_keys_raise_the_hit_rate(keys in db_map_test::strategy::zipf_keys(64, 1.2, 500)) {
+                    let inner = BTreeMapDB::from_iter((0_u32..64)
+                        .map(|rank| (rank.to_be_bytes(), rank.to_le_bytes())));
+                    let db = Cached::with_policy(inner, 4, Lfu::new());
+                    let mut hits = 0;
+                    for key in &keys {
+                        hits += usize::from(db.is_cached(key));
+                        let rank = u32::from_be_bytes(key[..].try_into().unwrap());
+                        prop_assert_eq!(db.get(key).unwrap(), Some(rank.to_le_bytes().to_vec()));
+                    }
+                    // With uniform reads, caching 4 of the 64 keys would hit one read in sixteen.
+                    // The hot keys should make it hit at least twice as often.
+                    prop_assert!(hits > keys.len() / 8, "{} hits from {} reads", hits, keys.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn capabilities_are_ordered_and_in_memory() {
+        let expected = Capabilities {
+            ordered_iteration: true,
+            ..Capabilities::default()
+        };
+        assert_eq!(BTreeMapDB::open().capabilities(), expected);
+        let boxed = Box::new(BTreeMapDB::open()) as Box<dyn db_map_trait::DynDbMap>;
+        assert_eq!(boxed.capabilities(), expected);
+    }
+
+    #[test]
+    fn wrong_length_counter_and_schema_version_are_corruption() {
+        use db_map_trait::{schema::SCHEMA_VERSION_KEY, AtomicCounter, SchemaVersioned};
+
+        let db = BTreeMapDB::from_iter([(b"count".as_slice(), b"short".as_slice())]);
+        let counter = AtomicCounter::new(db.clone(), b"count");
+        assert!(matches!(counter.get(), Err(db_map_trait::Error::Corruption(_))));
+        assert!(matches!(counter.incr(1), Err(db_map_trait::Error::Corruption(_))));
+        assert_eq!(db.get(b"count").unwrap().as_deref(), Some(b"short".as_slice()));
+
+        let db = BTreeMapDB::from_iter([(SCHEMA_VERSION_KEY, b"short".as_slice())]);
+        let opened = SchemaVersioned::open(db, 1, |_, _| Ok(()));
+        assert!(matches!(opened, Err(db_map_trait::Error::Corruption(_))));
+    }
+
+    #[test]
+    fn try_clone_shares_state() {
+        let db = BTreeMapDB::from_iter([("a", "1")]);
+        let handle = db.try_clone().unwrap();
+        assert!(Arc::ptr_eq(&db.0, &handle.0));
+        handle.insert(b"b", b"2").unwrap();
+        db.remove(b"a").unwrap();
+        assert_eq!(db.get(b"b").unwrap(), Some(b"2".to_vec()));
+        assert!(handle.get(b"a").unwrap().is_none());
+        assert_eq!(handle.len().unwrap(), 1);
+    }
+
+    /// Open a fresh database through [`OpenDb`] and check that it starts empty and works.
+    fn open_generically<M: OpenDb>(config: M::Config) -> M {
+        let db = M::open(config).unwrap();
+        assert!(db.is_empty().unwrap());
+        db.insert(b"key", b"value").unwrap();
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+        db
+    }
+
+    #[test]
+    fn open_db_opens_each_backend() {
+        let first: BTreeMapDB = open_generically(());
+        let second: BTreeMapDB = open_generically(());
+        assert!(!Arc::ptr_eq(&first.0, &second.0));
+        let sharded: ShardedMemDb = open_generically(());
+        assert_eq!(sharded.shard_count(), sharded::DEFAULT_SHARDS);
+    }
+
+    #[test]
+    fn read_only_view_reads_live_data() {
+        use db_map_trait::DBMapRead;
+
+        fn keys<M: DBMapRead>(db: &M) -> Vec<Vec<u8>> {
+            let mut keys = Vec::new();
+            db.for_each(|key, _| keys.push(key.to_vec())).unwrap();
+            keys
+        }
+
+        let db = BTreeMapDB::from_iter([("a", "1")]);
+        let clone = db.clone();
+        let view = db.as_read_only();
+        assert_eq!(keys(&view), [b"a".to_vec()]);
+
+        DBMap::insert(&clone, b"b", b"2").unwrap();
+        DBMap::insert(&clone, b"a", b"3").unwrap();
+        assert_eq!(keys(&view), [b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(view.get(b"a").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(view.get_map(b"b", |v| v.len()).unwrap(), Some(1));
+
+        DBMap::remove(&clone, b"a").unwrap();
+        assert!(view.get(b"a").unwrap().is_none());
+        let mut scanned = 0;
+        view.scan_prefix(b"b", |_, _| scanned += 1).unwrap();
+        assert_eq!((scanned, view.len().unwrap()), (1, 1));
+        assert!(!view.is_empty().unwrap());
+    }
+
+    #[test]
+    fn map_in_place_keeps_the_allocation() {
+        let db = BTreeMapDB::from_iter([("key", "value")]);
+        let before = db.0.lock().borrow()[&b"key".to_vec()].as_ptr();
+        assert!(db.map_in_place(b"key", |value| value.make_ascii_uppercase()).unwrap());
+        assert_eq!(db.0.lock().borrow()[&b"key".to_vec()].as_ptr(), before);
+        assert_eq!(db.get(b"key").unwrap(), Some(b"VALUE".to_vec()));
+        assert!(!db.map_in_place(b"absent", |_| panic!("called for an absent key")).unwrap());
+    }
+
+    #[test]
+    fn memory_usage_grows_with_entries() {
+        let db = BTreeMapDB::open();
+        let mut previous = db.memory_usage();
+        let mut data_len = 0;
+        for i in 0_usize..50 {
+            let (key, value) = (i.to_be_bytes(), vec![0xEE_u8; i * 3]);
+            data_len += key.len() + value.len();
+            db.insert(key, value).unwrap();
+            let usage = db.memory_usage();
+            assert!(usage > previous);
+            assert!(usage >= data_len);
+            assert!(usage <= data_len + (i + 1) * ENTRY_OVERHEAD);
+            previous = usage;
+        }
+
+        // Replacing a value with a shorter one shrinks the estimate.
+        db.insert(49_usize.to_be_bytes(), b"").unwrap();
+        assert_eq!(db.memory_usage(), previous - 49 * 3);
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let db = BTreeMapDB::from_iter([
+            (b"".as_slice(), b"empty key".as_slice()),
+            (b"key", b""),
+            (&[0xFF; 300], &[0x00; 70_000]),
+        ]);
+        let mut file = Vec::new();
+        db.save(&mut file).unwrap();
+        assert_eq!(&file[..6], b"DBMB\x00\x01");
+
+        let loaded = BTreeMapDB::load(file.as_slice()).unwrap();
+        assert_eq!(loaded.digest().unwrap(), db.digest().unwrap());
+        assert_eq!(loaded.len().unwrap(), 3);
+    }
+
+    #[test]
+    fn load_rejects_bad_magic() {
+        let mut file = Vec::new();
+        BTreeMapDB::open().save(&mut file).unwrap();
+        file[0] = b'X';
+        assert!(matches!(
+            BTreeMapDB::load(file.as_slice()),
+            Err(Error::BadMagic { magic }) if &magic == b"XBMB"
+        ));
+    }
+
+    #[test]
+    fn checkpoint_restore_is_atomic_for_readers() {
+        let before: Vec<_> = (0_u32..100).map(|i| (i.to_be_bytes().to_vec(), vec![0_u8])).collect();
+        let db = BTreeMapDB::from_iter(before.clone());
+        let checkpoint = db.checkpoint();
+
+        // Every value is rewritten in one batch, so a reader sees either all old or all new values.
+        let after: Vec<_> = (0_u32..100).map(|i| (i.to_be_bytes().to_vec(), vec![1_u8])).collect();
+        db.batch_insert(after.clone()).unwrap();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..4).map(|_| {
+            let (db, done) = (db.clone(), done.clone());
+            let (before, after) = (before.clone(), after.clone());
+            std::thread::spawn(move || {
+                let mut seen_before = false;
+                while !seen_before || !done.load(Ordering::SeqCst) {
+                    let mut entries = Vec::new();
+                    db.for_each(|key, value| entries.push((key.to_vec(), value.to_vec()))).unwrap();
+                    assert!(entries == before || entries == after, "saw a partly restored map");
+                    seen_before = entries == before;
+                }
+            })
+        }).collect();
+        checkpoint.restore();
+        done.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(db.get(0_u32.to_be_bytes()).unwrap(), Some(vec![0_u8]));
+        assert_eq!(db.len().unwrap(), 100);
+    }
+
+    #[test]
+    fn load_rejects_unknown_version() {
+        let mut file = Vec::new();
+        BTreeMapDB::from_iter([("a", "b")]).save(&mut file).unwrap();
+        file[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_be_bytes());
+        assert!(matches!(
+            BTreeMapDB::load(file.as_slice()),
+            Err(Error::UnsupportedFormat { version }) if version == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn load_rejects_truncated_file() {
+        let mut file = Vec::new();
+        BTreeMapDB::from_iter([("apple", "red")]).save(&mut file).unwrap();
+        file.pop();
+        assert!(matches!(BTreeMapDB::load(file.as_slice()), Err(Error::IoError(_))));
+    }
 }
@@ -0,0 +1,75 @@
+//! # ReadOnlyView: a borrowed, read-only view of a `BTreeMapDB`
+//!
+//! [`BTreeMapDB::as_read_only`][crate::BTreeMapDB::as_read_only] hands out a [`ReadOnlyView`],
+//! which implements [`DBMapRead`] but not [`DBMap`], so code given the view can read the
+//! database but has no way to write to it.
+use std::ops::Bound;
+use db_map_trait::{DBMap, DBMapRead, Result};
+use crate::BTreeMapDB;
+
+/// A read-only view of a [`BTreeMapDB`], from [`BTreeMapDB::as_read_only`].
+///
+/// The view borrows the database instead of cloning the handle, and reads go straight to the
+/// shared map, so writes made through the database (or any clone of it) are visible through the
+/// view as soon as they are made. Use [`checkpoint`][BTreeMapDB::checkpoint] for a copy that
+/// doesn't change.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMapRead;
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let db = BTreeMapDB::from_iter([("apple", "red")]);
+/// let view = db.as_read_only();
+/// assert_eq!(view.get(b"apple").unwrap(), Some(b"red".to_vec()));
+///
+/// // Writes through a clone of the database show up in the view.
+/// db_map_trait::DBMap::insert(&db.clone(), b"grape", b"purple").unwrap();
+/// assert_eq!(view.len().unwrap(), 2);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ReadOnlyView<'db>(&'db BTreeMapDB);
+
+impl<'db> ReadOnlyView<'db> {
+    /// Create a view of `db`.
+    pub(crate) fn new(db: &'db BTreeMapDB) -> Self {
+        Self(db)
+    }
+}
+
+impl DBMapRead for ReadOnlyView<'_> {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        DBMap::get_map(self.0, key, mapper)
+    }
+
+    fn try_for_each<F>(&self, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        DBMap::try_for_each(self.0, f)
+    }
+
+    fn try_scan_prefix<P, F>(&self, prefix: P, f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        DBMap::try_scan_prefix(self.0, prefix, f)
+    }
+
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        DBMap::try_scan_range(self.0, start, end, f)
+    }
+
+    fn len(&self) -> Result<usize> {
+        DBMap::len(self.0)
+    }
+}
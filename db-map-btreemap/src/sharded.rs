@@ -0,0 +1,224 @@
+//! # ShardedMemDb: a lock-striped in-memory "database"
+//!
+//! [`BTreeMapDB`][crate::BTreeMapDB] keeps everything behind a single lock, so every operation
+//! from every thread is serialized. [`ShardedMemDb`] splits the entries across several
+//! `HashMap` shards, each with its own lock, and routes each key to a shard by its hash. Reads
+//! and writes to keys in different shards don't contend with each other.
+use parking_lot::RwLock;
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+    sync::Arc,
+};
+use db_map_trait::{
+    Capabilities,
+    DBMap,
+    OpenDb,
+    Result,
+};
+
+/// The number of shards used by [`ShardedMemDb::open`].
+pub const DEFAULT_SHARDS: usize = 16;
+
+/// One shard of the database.
+type Shard = RwLock<HashMap<Vec<u8>, Vec<u8>>>;
+
+/// The shards and the hasher that picks between them.
+#[derive(Debug)]
+struct Shards {
+    shards: Box<[Shard]>,
+    hasher: RandomState,
+}
+
+/// An in-memory `DBMap` "database" split into lock-striped `HashMap` shards.
+///
+/// Operations on a single key only lock that key's shard. Operations that need the whole
+/// database, like [`for_each`][DBMap::for_each], have to fan out across every shard: entries
+/// are gathered from each shard in turn and sorted so that they are still visited in key
+/// order. The shards are not locked together, so such a scan is not a consistent snapshot if
+/// the database is changed while it runs.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMap;
+/// use db_map_btreemap::ShardedMemDb;
+/// use std::thread;
+///
+/// let db = ShardedMemDb::open();
+///
+/// thread::scope(|s| {
+///     for t in 0_u8..4 {
+///         let db = db.clone();
+///         s.spawn(move || {
+///             for i in 0_u8..100 {
+///                 db.insert([t, i], [i]).unwrap();
+///             }
+///         });
+///     }
+/// });
+///
+/// assert_eq!(db.len().unwrap(), 400);
+/// assert_eq!(db.get([3, 42]).unwrap(), Some(vec![42]));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ShardedMemDb(Arc<Shards>);
+
+impl ShardedMemDb {
+    /// Open a `ShardedMemDb` "database" with [`DEFAULT_SHARDS`] shards.
+    pub fn open() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Open a `ShardedMemDb` "database" with `shards` shards (at least one).
+    pub fn with_shards(shards: usize) -> Self {
+        Self(Arc::new(Shards {
+            shards: (0..shards.max(1)).map(|_| RwLock::default()).collect(),
+            hasher: RandomState::new(),
+        }))
+    }
+
+    /// Get the number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.0.shards.len()
+    }
+
+    /// Get the shard that holds `key`.
+    fn shard(&self, key: &[u8]) -> &Shard {
+        let index = self.0.hasher.hash_one(key) as usize % self.0.shards.len();
+        &self.0.shards[index]
+    }
+}
+
+impl Default for ShardedMemDb {
+    fn default() -> Self {
+        Self::open()
+    }
+}
+
+/// Opens a `ShardedMemDb` with [`DEFAULT_SHARDS`] shards, like [`ShardedMemDb::open`].
+impl OpenDb for ShardedMemDb {
+    type Config = ();
+
+    fn open(_config: ()) -> Result<Self> {
+        Ok(Self::open())
+    }
+}
+
+impl DBMap for ShardedMemDb {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let key = key.as_ref();
+        Ok(self.shard(key).read().get(key).map(|v| mapper(v)))
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        let key = key.as_ref();
+        self.shard(key).write().insert(key.to_vec(), value.as_ref().to_vec());
+        Ok(())
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let key = key.as_ref();
+        let old = self.shard(key).write().insert(key.to_vec(), value.as_ref().to_vec());
+        Ok(old.map(|v| mapper(&v)))
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        let key = key.as_ref();
+        self.shard(key).write().remove(key);
+        Ok(())
+    }
+
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let key = key.as_ref();
+        let mut shard = self.shard(key).write();
+        match f(shard.get(key).map(|v| v.as_slice())) {
+            Some(value) => { shard.insert(key.to_vec(), value); }
+            None => { shard.remove(key); }
+        }
+        Ok(())
+    }
+
+    /// Call a function for every entry in the database, in key order, until it returns an error.
+    ///
+    /// The entries of every shard are copied and sorted before the function is called, so no
+    /// locks are held while it runs.
+    fn try_for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let mut entries = Vec::new();
+        for shard in self.0.shards.iter() {
+            entries.extend(shard.read().iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        for (key, value) in entries {
+            f(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> Result<usize> {
+        Ok(self.0.shards.iter().map(|shard| shard.read().len()).sum())
+    }
+
+    /// Each shard is cleared in turn, so this isn't atomic either.
+    fn clear(&self) -> Result<()> {
+        self.0.shards.iter().for_each(|shard| shard.write().clear());
+        Ok(())
+    }
+
+    /// Scans are sorted into key order, but they don't lock the shards together, so they aren't
+    /// snapshots.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            ordered_iteration: true,
+            ..Capabilities::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use db_map_test::impl_db_map_tests;
+
+    impl_db_map_tests! {
+        let db = ShardedMemDb::open();
+    }
+
+    #[test]
+    fn capabilities_are_ordered_without_snapshots() {
+        assert_eq!(ShardedMemDb::open().capabilities(), Capabilities {
+            ordered_iteration: true,
+            ..Capabilities::default()
+        });
+    }
+
+    #[test]
+    fn single_shard_matches_many_shards() {
+        let single = ShardedMemDb::with_shards(1);
+        let many = ShardedMemDb::with_shards(64);
+        for i in 0_u16..1000 {
+            single.insert(i.to_be_bytes(), i.to_le_bytes()).unwrap();
+            many.insert(i.to_be_bytes(), i.to_le_bytes()).unwrap();
+        }
+        assert_eq!(single.len().unwrap(), 1000);
+        assert_eq!(many.len().unwrap(), 1000);
+        assert_eq!(single.digest().unwrap(), many.digest().unwrap());
+    }
+}
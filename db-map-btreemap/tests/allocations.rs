@@ -0,0 +1,114 @@
+//! Check that `get_map` and `read_into` avoid the allocation that `get` makes for the returned
+//! `Vec`, and that scans don't allocate for each entry.
+//!
+//! This test binary installs a global allocator that counts the allocations made by each
+//! thread, so it doesn't affect any other build.
+
+use db_map_btreemap::BTreeMapDB;
+use db_map_trait::DBMap;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// The system allocator, counting the allocations made on each thread.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Count the allocations made on this thread while running `f`.
+fn allocations<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+#[test]
+fn get_map_allocates_less_than_get() {
+    let db = BTreeMapDB::open();
+    db.insert(b"key", [0x5A_u8; 1024]).unwrap();
+
+    let (get_allocations, value) = allocations(|| db.get(b"key").unwrap());
+    assert_eq!(value.as_deref(), Some(&[0x5A_u8; 1024][..]));
+    assert!(get_allocations >= 1, "get made {get_allocations} allocations");
+
+    let (get_map_allocations, len) = allocations(|| db.get_map(b"key", |v| v.len()).unwrap());
+    assert_eq!(len, Some(1024));
+    assert_eq!(get_map_allocations, 0);
+
+    let (unit_allocations, found) = allocations(|| db.get_map(b"key", |_| ()).unwrap());
+    assert_eq!(found, Some(()));
+    assert_eq!(unit_allocations, 0);
+    assert!(get_map_allocations < get_allocations);
+}
+
+#[test]
+fn for_each_does_not_allocate_per_entry() {
+    let db = BTreeMapDB::open();
+    db.batch_insert((0_u32..1000).map(|i| (i.to_be_bytes(), [0x5A_u8; 64]))).unwrap();
+
+    let (for_each_allocations, (count, total)) = allocations(|| {
+        let (mut count, mut total) = (0, 0_usize);
+        db.for_each(|key, value| {
+            count += 1;
+            total += key.len() + value.len();
+        }).unwrap();
+        (count, total)
+    });
+    assert_eq!((count, total), (1000, 1000 * (4 + 64)));
+    assert_eq!(for_each_allocations, 0);
+
+    let (try_for_each_allocations, count) = allocations(|| {
+        let mut count = 0;
+        db.try_for_each(|_, _| {
+            count += 1;
+            Ok(())
+        }).unwrap();
+        count
+    });
+    assert_eq!(count, 1000);
+    assert_eq!(try_for_each_allocations, 0);
+}
+
+#[test]
+fn read_into_reuses_the_buffer() {
+    let db = BTreeMapDB::open();
+    for i in 0_u8..100 {
+        db.insert([i], vec![i; 1 + i as usize % 50]).unwrap();
+    }
+
+    let mut buf = Vec::with_capacity(64);
+    let (read_allocations, total) = allocations(|| {
+        let mut total = 0;
+        for i in 0_u8..100 {
+            let len = db.read_into([i], &mut buf).unwrap().unwrap();
+            assert!(buf.len() == len && buf.iter().all(|&byte| byte == i));
+            total += len;
+        }
+        total
+    });
+    assert_eq!(total, (0..100).map(|i| 1 + i % 50).sum::<usize>());
+    assert_eq!(read_allocations, 0);
+    assert_eq!(buf.capacity(), 64);
+}
@@ -0,0 +1,8 @@
+//! Check that misuse of the API is rejected at compile time.
+//!
+//! Each file in `tests/ui` must fail to compile with the error in the `.stderr` file next to it.
+
+#[test]
+fn ui() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,10 @@
+// A read-only view has no way to write, even with `DBMap` in scope.
+use db_map_btreemap::BTreeMapDB;
+use db_map_trait::{DBMap, DBMapRead};
+
+fn main() {
+    let db = BTreeMapDB::open();
+    let view = db.as_read_only();
+    assert!(view.get(b"key").unwrap().is_none());
+    view.insert(b"key", b"value").unwrap();
+}
@@ -0,0 +1,50 @@
+//! Compare inserting entries one at a time with inserting them in one batch, and with loading
+//! them with `bulk_load_sorted`, since they are already sorted.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use db_map_lmdb::{DBMap, LMDB, LMDBArgs};
+use tempfile::TempDir;
+
+/// The number of entries inserted in each iteration.
+const ENTRIES: u32 = 10_000;
+
+/// Open an empty database with a map large enough to never fill up during the benchmark.
+fn open_temp_lmdb() -> (TempDir, LMDB) {
+    let temp_dir = db_map_test::temp_dir();
+    let db = LMDB::open(temp_dir.path(), None, LMDBArgs {
+        map_size: Some(256 * 1024 * 1024),
+        ..Default::default()
+    }).unwrap();
+    (temp_dir, db)
+}
+
+/// The entries inserted by each benchmark.
+fn entries() -> impl Iterator<Item = ([u8; 4], [u8; 16])> {
+    (0..ENTRIES).map(|i| (i.to_be_bytes(), (i as u128).to_le_bytes()))
+}
+
+fn insert_vs_batch_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lmdb_10k_entries");
+    group.sample_size(10);
+    group.bench_function("insert", |b| {
+        b.iter_batched(open_temp_lmdb, |(_temp_dir, db)| {
+            for (key, value) in entries() {
+                db.insert(key, value).unwrap();
+            }
+        }, BatchSize::PerIteration)
+    });
+    group.bench_function("batch_insert", |b| {
+        b.iter_batched(open_temp_lmdb, |(_temp_dir, db)| {
+            db.batch_insert(entries()).unwrap();
+        }, BatchSize::PerIteration)
+    });
+    group.bench_function("bulk_load_sorted", |b| {
+        b.iter_batched(open_temp_lmdb, |(_temp_dir, db)| {
+            db.bulk_load_sorted(entries()).unwrap();
+        }, BatchSize::PerIteration)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, insert_vs_batch_insert);
+criterion_main!(benches);
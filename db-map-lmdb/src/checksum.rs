@@ -0,0 +1,92 @@
+//! # Value checksums for [LMDBArgs::value_checksum][crate::LMDBArgs::value_checksum].
+//!
+//! A checksummed value is stored as a format byte ([FORMAT]), the CRC-32C of the value as a
+//! big-endian `u32`, and then the value itself.
+
+/// The first byte of every checksummed value, so the format can change later.
+pub(crate) const FORMAT: u8 = 1;
+
+/// The number of bytes stored in front of each checksummed value.
+pub(crate) const HEADER_LEN: usize = 5;
+
+/// The CRC-32C (Castagnoli) lookup table, for the reflected polynomial.
+const TABLE: [u32; 256] = {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0x82F6_3B78,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the CRC-32C of `data`.
+pub(crate) fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0_u32, |crc, &byte| TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+/// Build the stored form of a value: the header followed by the value.
+pub(crate) fn seal(value: &[u8]) -> Vec<u8> {
+    let mut stored = Vec::with_capacity(HEADER_LEN + value.len());
+    stored.push(FORMAT);
+    stored.extend_from_slice(&crc32c(value).to_be_bytes());
+    stored.extend_from_slice(value);
+    stored
+}
+
+/// Rewrite the header of a stored value after its value bytes were changed in place.
+pub(crate) fn reseal(stored: &mut [u8]) {
+    let (header, value) = stored.split_at_mut(HEADER_LEN);
+    header[0] = FORMAT;
+    header[1..].copy_from_slice(&crc32c(value).to_be_bytes());
+}
+
+/// Check the header of a stored value and return the value, or `None` if the format byte or the
+/// checksum doesn't match.
+pub(crate) fn open(stored: &[u8]) -> Option<&[u8]> {
+    let (&format, rest) = stored.split_first()?;
+    let (crc, value) = rest.split_first_chunk::<4>()?;
+    (format == FORMAT && u32::from_be_bytes(*crc) == crc32c(value)).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32c_check_value() {
+        // The standard check value for CRC-32C.
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(b""), 0);
+    }
+
+    #[test]
+    fn open_rejects_damaged_values() {
+        let stored = seal(b"value");
+        assert_eq!(stored.len(), HEADER_LEN + 5);
+        assert_eq!(open(&stored), Some(&b"value"[..]));
+        assert_eq!(open(&seal(b"")), Some(&b""[..]));
+        for i in 0..stored.len() {
+            let mut damaged = stored.clone();
+            damaged[i] ^= 0x10;
+            assert!(open(&damaged).is_none(), "flipping a bit in byte {i} wasn't noticed");
+        }
+        assert!(open(&stored[..4]).is_none());
+
+        let mut changed = stored.clone();
+        changed[HEADER_LEN] = b'V';
+        assert!(open(&changed).is_none());
+        reseal(&mut changed);
+        assert_eq!(open(&changed), Some(&b"Value"[..]));
+        assert!(open(b"").is_none());
+    }
+}
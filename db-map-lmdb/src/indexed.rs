@@ -0,0 +1,257 @@
+//! # Records with secondary indexes stored in LMDB.
+//!
+//! [`Indexed`] stores records by their primary key along with index entries that map secondary
+//! keys back to primary keys, and keeps the two in step by changing both in one write
+//! transaction.
+
+use db_map_trait::{
+    DBMap,
+    Error,
+    Result,
+};
+use std::{io, path::Path};
+use super::{LMDBArgs, Writer, LMDB};
+
+/// The first byte of the key of every record: `b'r' || primary key -> value`.
+const RECORD: u8 = b'r';
+/// The first byte of the key holding a record's index keys: `b'k' || primary key -> index keys`.
+const INDEX_KEYS: u8 = b'k';
+/// The first byte of every index entry:
+/// `b'i' || index key length (big-endian u32) || index key || primary key -> ()`.
+const INDEX: u8 = b'i';
+
+/// Records stored by primary key, with secondary indexes to find them by other keys.
+///
+/// Each record can be filed under any number of index keys, and
+/// [`find_by_index`][Indexed::find_by_index] returns the primary keys of the records under one
+/// with a prefix scan. Every index entry is its own key in the database, made of the index key
+/// and the primary key, so lookups don't read the records and each index key can have any number
+/// of records.
+///
+/// [`insert`][Indexed::insert] and [`remove`][Indexed::remove] change the record and its index
+/// entries in one write transaction, so a reader never sees an index entry for a record that
+/// isn't there, or a record missing from its index. Replacing a record replaces its index keys
+/// too. The index keys of each record are stored alongside it, so removing a record doesn't have
+/// to search the index.
+///
+/// The records are stored in the database `db_name` (or the main database) of the environment,
+/// which should not be used for anything else.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_lmdb::{Indexed, LMDBArgs};
+/// use tempfile;
+///
+/// let temp_dir = tempfile::Builder::new()
+///     .prefix("lmdb_test_dir_")
+///     .rand_bytes(5)
+///     .tempdir()
+///     .unwrap();
+/// let people = Indexed::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+///
+/// people.insert(b"alice", b"Alice Smith", &[b"city:paris".to_vec(), b"team:red".to_vec()]).unwrap();
+/// people.insert(b"bob", b"Bob Jones", &[b"city:paris".to_vec()]).unwrap();
+/// people.insert(b"carol", b"Carol White", &[b"city:rome".to_vec(), b"team:red".to_vec()]).unwrap();
+///
+/// assert_eq!(people.find_by_index(b"city:paris").unwrap(), [b"alice".to_vec(), b"bob".to_vec()]);
+/// assert_eq!(people.find_by_index(b"team:red").unwrap(), [b"alice".to_vec(), b"carol".to_vec()]);
+/// assert_eq!(people.get(b"carol").unwrap(), Some(b"Carol White".to_vec()));
+///
+/// // Removing a record removes it from every index.
+/// assert!(people.remove(b"alice").unwrap());
+/// assert_eq!(people.find_by_index(b"city:paris").unwrap(), [b"bob".to_vec()]);
+/// assert_eq!(people.find_by_index(b"team:red").unwrap(), [b"carol".to_vec()]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Indexed {
+    db: LMDB,
+}
+
+impl Indexed {
+    /// Open an `Indexed` store.
+    ///
+    /// The records are stored in the database `db_name` (or the main database) of the
+    /// environment, which should not be used for anything else.
+    pub fn open(env_path: &Path, db_name: Option<&str>, lmdb_args: LMDBArgs) -> Result<Indexed> {
+        Ok(Self {
+            db: LMDB::open(env_path, db_name, lmdb_args)?,
+        })
+    }
+
+    /// Get the value of a record.
+    pub fn get<P: AsRef<[u8]>>(&self, pk: P) -> Result<Option<Vec<u8>>> {
+        self.db.get(record_key(RECORD, pk.as_ref()))
+    }
+
+    /// Insert a record filed under `index_keys`, replacing any record with the same primary key
+    /// and its index entries.
+    pub fn insert<P, V>(&self, pk: P, value: V, index_keys: &[Vec<u8>]) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let pk = pk.as_ref();
+        let mut writer = self.db.writer()?;
+        remove_index_entries(&mut writer, pk)?;
+        writer.insert(record_key(RECORD, pk), value)?;
+        let mut stored_keys = Vec::new();
+        for index_key in index_keys {
+            writer.insert(index_entry_key(index_key, pk)?, [])?;
+            stored_keys.extend_from_slice(&index_key_len(index_key)?.to_be_bytes());
+            stored_keys.extend_from_slice(index_key);
+        }
+        writer.insert(record_key(INDEX_KEYS, pk), stored_keys)?;
+        writer.commit()
+    }
+
+    /// Remove a record and its index entries, returning `true` if it was there.
+    pub fn remove<P: AsRef<[u8]>>(&self, pk: P) -> Result<bool> {
+        let pk = pk.as_ref();
+        let mut writer = self.db.writer()?;
+        remove_index_entries(&mut writer, pk)?;
+        writer.remove(record_key(INDEX_KEYS, pk))?;
+        let removed = writer.remove(record_key(RECORD, pk))?;
+        writer.commit()?;
+        Ok(removed)
+    }
+
+    /// Get the primary keys of the records filed under an index key, in primary key order.
+    pub fn find_by_index<I: AsRef<[u8]>>(&self, index_key: I) -> Result<Vec<Vec<u8>>> {
+        let prefix = index_entry_key(index_key.as_ref(), &[])?;
+        let mut pks = Vec::new();
+        self.db.scan_prefix(&prefix, |key, _| pks.push(key[prefix.len()..].to_vec()))?;
+        Ok(pks)
+    }
+
+    /// Get the index keys a record is filed under, in the order they were given.
+    pub fn index_keys<P: AsRef<[u8]>>(&self, pk: P) -> Result<Vec<Vec<u8>>> {
+        self.db.get_map(record_key(INDEX_KEYS, pk.as_ref()), parse_index_keys)?
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+}
+
+/// Remove the index entries of the record stored under `pk`, if there is one.
+fn remove_index_entries(writer: &mut Writer<'_>, pk: &[u8]) -> Result<()> {
+    let Some(stored_keys) = writer.get(record_key(INDEX_KEYS, pk))? else {
+        return Ok(());
+    };
+    for index_key in parse_index_keys(&stored_keys)? {
+        writer.remove(index_entry_key(&index_key, pk)?)?;
+    }
+    Ok(())
+}
+
+/// Build the key of a record, or of its stored index keys.
+fn record_key(kind: u8, pk: &[u8]) -> Vec<u8> {
+    [&[kind][..], pk].concat()
+}
+
+/// Build the key of an index entry, or with an empty `pk` the prefix of every entry under
+/// `index_key`.
+///
+/// The index key's length comes first, so the entries under one index key never share a prefix
+/// with the entries under a longer index key that starts the same way.
+fn index_entry_key(index_key: &[u8], pk: &[u8]) -> Result<Vec<u8>> {
+    Ok([&[INDEX][..], &index_key_len(index_key)?.to_be_bytes(), index_key, pk].concat())
+}
+
+fn index_key_len(index_key: &[u8]) -> Result<u32> {
+    u32::try_from(index_key.len())
+        .map_err(|_| Error::InvalidKey(format!("index key of {} bytes is too long", index_key.len())))
+}
+
+/// Split the stored index keys of a record back into the index keys.
+fn parse_index_keys(mut stored: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut index_keys = Vec::new();
+    while !stored.is_empty() {
+        let (len, rest) = stored.split_first_chunk::<4>().ok_or_else(corrupt_index_keys)?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if rest.len() < len {
+            return Err(corrupt_index_keys());
+        }
+        index_keys.push(rest[..len].to_vec());
+        stored = &rest[len..];
+    }
+    Ok(index_keys)
+}
+
+fn corrupt_index_keys() -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::InvalidData, "stored index keys are corrupt"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_temp_indexed() -> (tempfile::TempDir, Indexed) {
+        let temp_dir = db_map_test::temp_dir();
+        let indexed = Indexed::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+        (temp_dir, indexed)
+    }
+
+    #[test]
+    fn insert_and_find() {
+        let (_temp_dir, indexed) = open_temp_indexed();
+        indexed.insert(b"3", b"three", &[b"odd".to_vec(), b"prime".to_vec()]).unwrap();
+        indexed.insert(b"4", b"four", &[b"even".to_vec()]).unwrap();
+        indexed.insert(b"2", b"two", &[b"even".to_vec(), b"prime".to_vec()]).unwrap();
+        indexed.insert(b"1", b"one", &[b"odd".to_vec()]).unwrap();
+        // An index key that starts like another one is a separate index.
+        indexed.insert(b"5", b"five", &[b"oddly".to_vec(), b"prime".to_vec()]).unwrap();
+
+        assert_eq!(indexed.find_by_index(b"odd").unwrap(), [b"1".to_vec(), b"3".to_vec()]);
+        assert_eq!(indexed.find_by_index(b"even").unwrap(), [b"2".to_vec(), b"4".to_vec()]);
+        assert_eq!(indexed.find_by_index(b"prime").unwrap(), [b"2".to_vec(), b"3".to_vec(), b"5".to_vec()]);
+        assert_eq!(indexed.find_by_index(b"oddly").unwrap(), [b"5".to_vec()]);
+        assert!(indexed.find_by_index(b"od").unwrap().is_empty());
+        assert_eq!(indexed.get(b"4").unwrap(), Some(b"four".to_vec()));
+        assert_eq!(indexed.index_keys(b"2").unwrap(), [b"even".to_vec(), b"prime".to_vec()]);
+        assert!(indexed.get(b"6").unwrap().is_none());
+        assert!(indexed.index_keys(b"6").unwrap().is_empty());
+    }
+
+    #[test]
+    fn replacing_a_record_replaces_its_index_entries() {
+        let (_temp_dir, indexed) = open_temp_indexed();
+        indexed.insert(b"pk", b"old", &[b"a".to_vec(), b"b".to_vec()]).unwrap();
+        indexed.insert(b"pk", b"new", &[b"b".to_vec(), b"c".to_vec()]).unwrap();
+        assert!(indexed.find_by_index(b"a").unwrap().is_empty());
+        assert_eq!(indexed.find_by_index(b"b").unwrap(), [b"pk".to_vec()]);
+        assert_eq!(indexed.find_by_index(b"c").unwrap(), [b"pk".to_vec()]);
+        assert_eq!(indexed.get(b"pk").unwrap(), Some(b"new".to_vec()));
+    }
+
+    #[test]
+    fn remove_drops_index_entries() {
+        let (_temp_dir, indexed) = open_temp_indexed();
+        indexed.insert(b"x", b"1", &[b"shared".to_vec(), b"only-x".to_vec(), b"".to_vec()]).unwrap();
+        indexed.insert(b"y", b"2", &[b"shared".to_vec()]).unwrap();
+
+        assert!(indexed.remove(b"x").unwrap());
+        assert!(!indexed.remove(b"x").unwrap());
+        assert!(indexed.get(b"x").unwrap().is_none());
+        assert_eq!(indexed.find_by_index(b"shared").unwrap(), [b"y".to_vec()]);
+        assert!(indexed.find_by_index(b"only-x").unwrap().is_empty());
+        assert!(indexed.find_by_index(b"").unwrap().is_empty());
+
+        // Only the other record and its index entries are left.
+        assert!(indexed.remove(b"y").unwrap());
+        assert!(indexed.db.is_empty().unwrap());
+    }
+
+    #[test]
+    fn failed_insert_changes_nothing() {
+        let (_temp_dir, indexed) = open_temp_indexed();
+        indexed.insert(b"pk", b"value", &[b"a".to_vec()]).unwrap();
+        let digest = indexed.db.digest().unwrap();
+        let too_long = vec![0_u8; 1000];
+        assert!(matches!(
+            indexed.insert(b"pk", b"other", &[b"b".to_vec(), too_long]),
+            Err(Error::InvalidKey(_)),
+        ));
+        assert_eq!(indexed.db.digest().unwrap(), digest);
+        assert_eq!(indexed.find_by_index(b"a").unwrap(), [b"pk".to_vec()]);
+    }
+}
@@ -4,10 +4,14 @@
 
 use anyhow::Error;
 pub use db_map_trait::{
+    Capabilities,
     DBMap,
+    Page,
     Result,
 };
+use db_map_trait::{move_key, HexKey, OpenDb};
 use lmdb::{
+    Cursor,
     Environment,
     EnvironmentFlags,
     Database,
@@ -17,13 +21,82 @@ use lmdb::{
     Result as LMDBResult,
     WriteFlags,
 };
-use lmdb_sys::mdb_mode_t;
-use libc::{c_uint, size_t};
+use lmdb_sys::{mdb_mode_t, MDB_FIRST, MDB_LAST};
+use libc::{c_int, c_uint, size_t};
+use parking_lot::{RwLock, RwLockReadGuard};
 use std::{
-    path::Path,
-    sync::Arc,
+    borrow::Cow,
+    cell::Cell,
+    ffi::CString,
+    fs,
+    ops::{Bound, Deref, DerefMut},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        OnceLock,
+    },
 };
 
+mod checksum;
+pub mod indexed;
+pub mod queue;
+pub mod set;
+pub mod split;
+pub mod writer;
+
+pub use indexed::Indexed;
+pub use queue::{Queue, ReservationToken};
+pub use set::LMDBSet;
+pub use split::{DedupStats, LMDBSplit};
+pub use writer::{MultiWriter, Writer};
+
+/// Run the body of an operation, marking transient errors with [mark_transient] and warning if it
+/// is slower than [LMDBArgs::slow_threshold].
+///
+/// Without the `slow-log` feature this only marks the errors.
+macro_rules! slow_log {
+    ($self:ident, $op:literal, $key_len:expr, $body:block) => {{
+        #[cfg(feature = "slow-log")]
+        let (key_len, start) = ($key_len, $self.slow_threshold.map(|_| std::time::Instant::now()));
+        #[allow(clippy::redundant_closure_call)]
+        let result = mark_transient((move || $body)());
+        #[cfg(feature = "slow-log")]
+        $self.log_if_slow($op, Some(key_len), start);
+        result
+    }};
+    ($self:ident, $op:literal, $body:block) => {{
+        #[cfg(feature = "slow-log")]
+        let start = $self.slow_threshold.map(|_| std::time::Instant::now());
+        #[allow(clippy::redundant_closure_call)]
+        let result = mark_transient((move || $body)());
+        #[cfg(feature = "slow-log")]
+        $self.log_if_slow($op, None, start);
+        result
+    }};
+}
+
+/// Turn a database error that may go away on its own into
+/// [Error::Transient][db_map_trait::Error::Transient], so that it is
+/// [retryable][db_map_trait::Error::is_retryable].
+///
+/// A full map, reader table, or a busy or temporarily unavailable resource are transient: another
+/// handle may grow the map, free a reader slot, or release the resource.
+fn mark_transient<T>(result: Result<T>) -> Result<T> {
+    result.map_err(|err| match err {
+        db_map_trait::Error::DBError(err) if matches!(
+            err.downcast_ref::<LMDBError>(),
+            Some(LMDBError::MapFull | LMDBError::MapResized | LMDBError::ReadersFull
+                | LMDBError::Other(libc::EBUSY | libc::EAGAIN)),
+        ) => db_map_trait::Error::Transient(err),
+        err => err,
+    })
+}
+
+/// The default limit for growing the memory map in [LMDB::insert_growing] (1 GiB).
+pub const DEFAULT_MAX_MAP_SIZE: size_t = 1 << 30;
+
 /// Arguments sent to [LMDB::open] to define options when opening an LMDB database.
 ///
 /// # Examples
@@ -94,17 +167,320 @@ pub struct LMDBArgs {
     /// Any attempt to set a size smaller than the space already consumed
     /// by the environment will be silently changed to the current size of the used space.
     ///
+    /// A size that isn't a multiple of the OS page size is rounded up to the next multiple (and,
+    /// with the `tracing` feature, the rounding is logged). [LMDB::map_size] returns the size
+    /// actually in use.
+    ///
     /// Source: [lmdb::EnvironmentBuilder::set_map_size].
     pub map_size: Option<size_t>,
+    /// The largest size that [LMDB::insert_growing] will grow the memory map to.
+    ///
+    /// When an insert fails because the map is full, `insert_growing` doubles the map size and
+    /// tries again, but never beyond this limit. The default is [DEFAULT_MAX_MAP_SIZE].
+    pub max_map_size: Option<size_t>,
+    /// Log a warning for any operation that takes longer than this.
+    ///
+    /// Each `DBMap` operation is timed and a `tracing::warn!` event is emitted with the
+    /// operation name and key length when it is slower than the threshold. When this is `None`
+    /// nothing is timed.
+    #[cfg(feature = "slow-log")]
+    pub slow_threshold: Option<std::time::Duration>,
+    /// Compare keys as native-endian unsigned integers instead of as byte strings.
+    ///
+    /// When a new database is created with this set, keys are sorted in numeric order, so scans
+    /// over integer keys visit them in order. Every key must then be an unsigned integer written
+    /// with `to_ne_bytes`, and all keys must be the same size: either 4 bytes (`u32`) or
+    /// `size_of::<usize>()` bytes (`usize`, so `u64` on 64-bit platforms). The first key stored
+    /// fixes the size (and a database that already has keys uses the size of its first one). LMDB
+    /// itself would silently sort keys of any other size wrong, so reads and writes with such a key
+    /// fail with [`Error::InvalidKey`][db_map_trait::Error::InvalidKey] instead. Prefix scans match
+    /// raw key bytes, which doesn't line up with numeric order, so only scans with an empty prefix
+    /// are meaningful. Range scans compare the bounds as bytes too, so they only work with
+    /// unbounded ranges.
+    ///
+    /// This only takes effect when the database is created. An existing database keeps the
+    /// key order it was created with, and the key sizes are checked whenever the database uses
+    /// integer keys, whatever this is set to. Only [LMDB] uses this option.
+    ///
+    /// Source: [DatabaseFlags::INTEGER_KEY].
+    pub integer_keys: bool,
+    /// Compare keys from their last byte to their first instead of from the first byte.
+    ///
+    /// When a new database is created with this set, keys are sorted by their reversed bytes, so
+    /// keys that share a suffix (like domain names under the same domain) are next to each
+    /// other. Everything that depends on key order follows the reversed order: scans visit keys
+    /// in it, [first_value][db_map_trait::DBMap::first_value] and
+    /// [last_value][db_map_trait::DBMap::last_value] return the ends of it, and range scans
+    /// include the keys between the bounds in it. Keys under a prefix are no longer next to each
+    /// other, so prefix scans (and [move_prefix][db_map_trait::DBMap::move_prefix]) check every
+    /// key instead of seeking to the prefix, and are still correct but take time proportional to
+    /// the whole database. Because scans aren't in byte order,
+    /// [capabilities][db_map_trait::DBMap::capabilities] doesn't report `ordered_iteration`.
+    ///
+    /// Like `integer_keys`, this only takes effect when the database is created, and an existing
+    /// database keeps the key order it was created with. Only [LMDB] uses this option.
+    ///
+    /// Source: [DatabaseFlags::REVERSE_KEY].
+    pub reverse_key: bool,
+    /// Store a CRC-32C checksum with every value and check it whenever the value is read.
+    ///
+    /// Each value is stored behind a five byte header: a format byte followed by the checksum of
+    /// the value. Writes add the header, and reads check and strip it, so callers only ever see
+    /// the original value. A stored value with a missing or damaged header, or a checksum that
+    /// doesn't match, is reported as [Error::Corruption][db_map_trait::Error::Corruption]
+    /// instead of being returned.
+    ///
+    /// The header isn't recorded in the database flags, so this option has to be the same every
+    /// time a database is opened: values written without it fail the check when it is enabled,
+    /// and values written with it are returned with their header when it isn't. Only [LMDB] uses
+    /// this option.
+    pub value_checksum: bool,
     // This was removed because all of the Database flags will break the expectations of this crate.
     // pub db_flags: Option<DatabaseFlags>,
 }
 
+/// Convert a return code from an `lmdb_sys` function into a result.
+fn lmdb_result(err_code: c_int) -> LMDBResult<()> {
+    match err_code {
+        lmdb_sys::MDB_SUCCESS => Ok(()),
+        err_code => Err(LMDBError::from_err_code(err_code)),
+    }
+}
+
+/// Get the size of an OS memory page.
+fn os_page_size() -> size_t {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as size_t,
+        _ => 4096,
+    }
+}
+
+/// Round a memory map size up to a whole number of OS pages.
+fn page_align(size: size_t) -> size_t {
+    size.div_ceil(os_page_size()).saturating_mul(os_page_size())
+}
+
+/// Get the longest key the environment accepts (511 bytes with LMDB's default build settings).
+fn max_key_size(env: &Environment) -> usize {
+    unsafe { lmdb_sys::mdb_env_get_maxkeysize(env.env()) as usize }
+}
+
+/// Return [Error::InvalidKey][db_map_trait::Error::InvalidKey] for an empty key or one longer
+/// than `max_key_size`.
+///
+/// LMDB itself reports both as a generic `MDB_BAD_VALSIZE` error, which doesn't say whether the
+/// key or the value was at fault.
+#[inline]
+fn check_key_size(key: &[u8], max_key_size: usize) -> Result<()> {
+    match key.len() {
+        0 => Err(db_map_trait::Error::InvalidKey("keys can't be empty".to_string())),
+        len if len > max_key_size => Err(db_map_trait::Error::InvalidKey(format!(
+            "key {} is too long: keys can be at most {max_key_size} bytes, not {len}",
+            HexKey(key),
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Open an LMDB environment using the options in `lmdb_args`.
+fn open_env(env_path: &Path, lmdb_args: &LMDBArgs) -> anyhow::Result<Environment> {
+    let mut builder = Environment::new();
+    if let Some(flags) = lmdb_args.env_flags {
+        builder.set_flags(flags);
+    }
+    if let Some(max_dbs) = lmdb_args.max_dbs {
+        builder.set_max_dbs(max_dbs);
+    }
+    if let Some(max_readers) = lmdb_args.max_readers {
+        builder.set_max_readers(max_readers);
+    }
+    if let Some(requested) = lmdb_args.map_size {
+        let map_size = page_align(requested);
+        #[cfg(feature = "tracing")]
+        if map_size != requested {
+            tracing::info!(requested, map_size, "rounded the LMDB map size up to a whole number of pages");
+        }
+        builder.set_map_size(map_size);
+    }
+    Ok(match lmdb_args.file_mode {
+        None => builder.open(env_path)?,
+        Some(mode) => builder.open_with_permissions(env_path, mode)?,
+    })
+}
+
+/// Everything [LMDB::open] takes, bundled for [OpenDb::open].
+///
+/// An [LMDB] also keeps the config it was opened with, so that it can be reopened.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, OpenDb};
+/// use db_map_lmdb::{LMDB, LMDBArgs, LMDBConfig};
+///
+/// let temp_dir = tempfile::Builder::new()
+///     .prefix("lmdb_test_dir_")
+///     .rand_bytes(5)
+///     .tempdir()
+///     .unwrap();
+/// let db = <LMDB as OpenDb>::open(LMDBConfig {
+///     env_path: temp_dir.path().to_path_buf(),
+///     db_name: None,
+///     lmdb_args: LMDBArgs::default(),
+/// }).unwrap();
+/// db.insert(b"key", b"value").unwrap();
+/// assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LMDBConfig {
+    /// The path of the environment.
+    pub env_path: PathBuf,
+    /// The name of the database in the environment, or `None` for the default database.
+    pub db_name: Option<String>,
+    /// The options for the environment and the database.
+    pub lmdb_args: LMDBArgs,
+}
+
+/// Counts of the transactions an [LMDB] handle and its clones have used, from
+/// [LMDB::txn_metrics].
+///
+/// Read transactions are ended without committing, so only write transactions are counted as
+/// committed or aborted. A write transaction that is still open is in neither count.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TxnMetrics {
+    /// The number of read transactions begun.
+    pub read_txns: u64,
+    /// The number of write transactions begun.
+    pub write_txns: u64,
+    /// The number of write transactions committed.
+    pub commits: u64,
+    /// The number of write transactions aborted, either because the operation failed or because
+    /// the commit did.
+    pub aborts: u64,
+}
+
+/// The shared counters behind [TxnMetrics].
+#[derive(Debug, Default)]
+struct TxnCounters {
+    read_txns: AtomicU64,
+    write_txns: AtomicU64,
+    commits: AtomicU64,
+    aborts: AtomicU64,
+}
+
+thread_local! {
+    /// The number of [ResizeGuard]s held by this thread, on any environment.
+    static RESIZE_GUARDS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A read lock on an environment's resize lock, held by every transaction.
+///
+/// A thread's first guard waits behind a resize that is waiting for the lock, so a stream of
+/// readers can't hold a resize off forever. Any further guard on the same thread is taken
+/// recursively instead, since waiting would deadlock against the resize, which is waiting for
+/// the guard this thread already holds.
+struct ResizeGuard<'env> {
+    _guard: RwLockReadGuard<'env, ()>,
+}
+
+impl<'env> ResizeGuard<'env> {
+    fn new(lock: &'env RwLock<()>) -> Self {
+        let held = RESIZE_GUARDS.with(|held| held.replace(held.get() + 1));
+        Self {
+            _guard: match held {
+                0 => lock.read(),
+                _ => lock.read_recursive(),
+            },
+        }
+    }
+}
+
+impl Drop for ResizeGuard<'_> {
+    fn drop(&mut self) {
+        RESIZE_GUARDS.with(|held| held.set(held.get() - 1));
+    }
+}
+
+/// A read transaction, holding the environment's resize lock for as long as it is open.
+struct ReadTxn<'env> {
+    // Declared before the guard, so the transaction ends before the lock is released.
+    txn: lmdb::RoTransaction<'env>,
+    _resize: ResizeGuard<'env>,
+}
+
+impl<'env> Deref for ReadTxn<'env> {
+    type Target = lmdb::RoTransaction<'env>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+/// A write transaction that counts itself as committed or aborted when it ends, holding the
+/// environment's resize lock for as long as it is open.
+struct WriteTxn<'env> {
+    txn: Option<lmdb::RwTransaction<'env>>,
+    counters: &'env TxnCounters,
+    _resize: ResizeGuard<'env>,
+}
+
+impl WriteTxn<'_> {
+    /// Commit the transaction.
+    fn commit(mut self) -> LMDBResult<()> {
+        let result = self.txn.take().expect("the transaction is only taken here").commit();
+        match result {
+            Ok(()) => &self.counters.commits,
+            Err(_) => &self.counters.aborts,
+        }.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+}
+
+impl<'env> Deref for WriteTxn<'env> {
+    type Target = lmdb::RwTransaction<'env>;
+
+    fn deref(&self) -> &Self::Target {
+        self.txn.as_ref().expect("the transaction is only taken when it is committed")
+    }
+}
+
+impl DerefMut for WriteTxn<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.txn.as_mut().expect("the transaction is only taken when it is committed")
+    }
+}
+
+impl Drop for WriteTxn<'_> {
+    fn drop(&mut self) {
+        // Dropping an uncommitted transaction aborts it.
+        if self.txn.is_some() {
+            self.counters.aborts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
 #[doc = include_str!("../README.md")]
 #[derive(Clone, Debug)]
 pub struct LMDB {
     env: Arc<Environment>,
+    /// Held for reading by every transaction, and for writing while the map is resized, which
+    /// LMDB only allows when the process has no transactions open. Shared by every handle on the
+    /// environment.
+    resize_lock: Arc<RwLock<()>>,
     db: Arc<Database>,
+    opened: Arc<LMDBConfig>,
+    txn_counters: Arc<TxnCounters>,
+    read_only: bool,
+    integer_keys: bool,
+    /// The size of every key in an integer key database, fixed by the first key stored in it.
+    /// Shared by the clones of a handle.
+    integer_key_size: Arc<OnceLock<usize>>,
+    reverse_key: bool,
+    value_checksum: bool,
+    max_key_size: usize,
+    max_map_size: size_t,
+    #[cfg(feature = "slow-log")]
+    slow_threshold: Option<std::time::Duration>,
 }
 
 impl LMDB {
@@ -119,108 +495,2243 @@ impl LMDB {
 
     /// The implementation for the `open` function.
     fn open_inner(env_path: &Path, db_name: Option<&str>, lmdb_args: LMDBArgs) -> anyhow::Result<LMDB> {
-        let mut builder = Environment::new();
-        if let Some(flags) = lmdb_args.env_flags {
-            builder.set_flags(flags);
-        }
-        if let Some(max_dbs) = lmdb_args.max_dbs {
-            builder.set_max_dbs(max_dbs);
-        }
-        if let Some(max_readers) = lmdb_args.max_readers {
-            builder.set_max_readers(max_readers);
-        }
-        if let Some(map_size) = lmdb_args.map_size {
-            builder.set_map_size(map_size);
-        }
-        let env = Arc::new(match lmdb_args.file_mode {
-            None => builder.open(env_path)?,
-            Some(mode) => builder.open_with_permissions(env_path, mode)?,
+        let env = Arc::new(open_env(env_path, &lmdb_args)?);
+        Self::open_in_env(env, Arc::default(), env_path, db_name, lmdb_args)
+    }
+
+    /// Open another database in the environment of this handle.
+    ///
+    /// LMDB only allows an environment to be opened once in a process, so this is how to use
+    /// several named sub-databases side by side, and the only way to write to them together with
+    /// a [MultiWriter]. The new handle shares this handle's environment and uses the same
+    /// [LMDBArgs] (so the same key order and checksums for a database it creates), but has its own
+    /// transaction counters. `max_dbs` must leave room for every named database.
+    ///
+    /// Like [LMDB::open], this may need a write transaction to create the database, so don't call
+    /// it while a [Writer] or [MultiWriter] is open on the same thread.
+    ///
+    /// See [MultiWriter] for an example.
+    pub fn open_db(&self, db_name: Option<&str>) -> Result<LMDB> {
+        let opened = &self.opened;
+        let (env, resize_lock) = (Arc::clone(&self.env), Arc::clone(&self.resize_lock));
+        Ok(Self::open_in_env(env, resize_lock, &opened.env_path, db_name, opened.lmdb_args.clone())?)
+    }
+
+    /// Open a database in an environment that is already open.
+    fn open_in_env(
+        env: Arc<Environment>,
+        resize_lock: Arc<RwLock<()>>,
+        env_path: &Path,
+        db_name: Option<&str>,
+        lmdb_args: LMDBArgs,
+    ) -> anyhow::Result<LMDB> {
+        let read_only = lmdb_args.env_flags
+            .is_some_and(|flags| flags.contains(EnvironmentFlags::READ_ONLY));
+        // Creating a database needs a write transaction, so a read-only handle can only open one
+        // that already exists.
+        let db = Arc::new(match read_only {
+            true => env.open_db(db_name)?,
+            false => {
+                let mut flags = DatabaseFlags::empty();
+                flags.set(DatabaseFlags::INTEGER_KEY, lmdb_args.integer_keys);
+                flags.set(DatabaseFlags::REVERSE_KEY, lmdb_args.reverse_key);
+                env.create_db(db_name, flags)?
+            }
         });
-        let db = Arc::new(env.create_db(db_name, DatabaseFlags::empty())?);
+        // Check the flags the database actually has, which may predate these arguments, and the
+        // size of the integer keys already stored.
+        let integer_key_size = Arc::new(OnceLock::new());
+        let db_flags = {
+            let _resize = ResizeGuard::new(&resize_lock);
+            let txn = env.begin_ro_txn()?;
+            let db_flags = txn.db_flags(*db)?;
+            if db_flags.contains(DatabaseFlags::INTEGER_KEY) {
+                match txn.open_ro_cursor(*db)?.get(None, None, MDB_FIRST) {
+                    Ok((key, _)) => { integer_key_size.get_or_init(|| key.unwrap_or_default().len()); }
+                    Err(LMDBError::NotFound) => {}
+                    Err(err) => { return Err(err.into()) }
+                }
+            }
+            db_flags
+        };
+        let integer_keys = db_flags.contains(DatabaseFlags::INTEGER_KEY);
+        let reverse_key = db_flags.contains(DatabaseFlags::REVERSE_KEY);
+        let max_key_size = max_key_size(&env);
         Ok(Self {
             env,
+            resize_lock,
             db,
+            txn_counters: Arc::default(),
+            read_only,
+            integer_keys,
+            integer_key_size,
+            reverse_key,
+            value_checksum: lmdb_args.value_checksum,
+            max_key_size,
+            max_map_size: lmdb_args.max_map_size.unwrap_or(DEFAULT_MAX_MAP_SIZE),
+            #[cfg(feature = "slow-log")]
+            slow_threshold: lmdb_args.slow_threshold,
+            opened: Arc::new(LMDBConfig {
+                env_path: env_path.to_path_buf(),
+                db_name: db_name.map(str::to_string),
+                lmdb_args,
+            }),
         })
     }
 
-    /// Open a read-only transaction.
+    /// Write a compacted copy of the environment into `dest_dir`.
+    ///
+    /// Free pages are left out of the copy and the b-trees are rewritten in order, so the copy
+    /// is usually much smaller than the original after lots of deletions. `dest_dir` must be an
+    /// existing, empty directory (or, if the environment was opened with
+    /// [EnvironmentFlags::NO_SUB_DIR], the path of the new data file).
+    ///
+    /// The copy is taken from a read transaction, so it can be made while the database is in
+    /// use.
+    ///
+    /// Source: `mdb_env_copy2` with `MDB_CP_COMPACT`.
+    pub fn compact_to(&self, dest_dir: &Path) -> Result<()> {
+        let dest = CString::new(dest_dir.as_os_str().as_bytes()).map_err(Error::from)?;
+        // The copy runs in a read transaction of its own.
+        let _resize = ResizeGuard::new(&self.resize_lock);
+        lmdb_result(unsafe {
+            lmdb_sys::mdb_env_copy2(self.env.env(), dest.as_ptr(), lmdb_sys::MDB_CP_COMPACT)
+        }).map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Compact the environment on disk and reopen it.
+    ///
+    /// The environment is compacted (see [compact_to][LMDB::compact_to]) into a temporary
+    /// directory next to the data file, the environment is closed, the compacted data file is
+    /// renamed over the original in one atomic step, and the database is opened again with the
+    /// original arguments.
+    ///
+    /// This requires exclusive access to the environment: this must be the only handle to it
+    /// (no clones), and no other process may have it open. If any clones of this handle still
+    /// exist an error is returned and nothing is changed. The old handle is consumed because it
+    /// would otherwise keep using the replaced file.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+    /// for i in 0_u32..1000 {
+    ///     db.insert(i.to_be_bytes(), [0xAA_u8; 512]).unwrap();
+    /// }
+    /// for i in 1_u32..1000 {
+    ///     db.remove(i.to_be_bytes()).unwrap();
+    /// }
+    ///
+    /// let db = db.compact_in_place().unwrap();
+    /// assert_eq!(db.len().unwrap(), 1);
+    /// assert_eq!(db.get(0_u32.to_be_bytes()).unwrap(), Some(vec![0xAA_u8; 512]));
+    /// ```
+    pub fn compact_in_place(self) -> Result<LMDB> {
+        self.check_sole_handle("compact in place")?;
+        let opened = Arc::clone(&self.opened);
+        let no_sub_dir = self.env_flags()?.contains(EnvironmentFlags::NO_SUB_DIR);
+        let data_file = match no_sub_dir {
+            true => opened.env_path.clone(),
+            false => opened.env_path.join("data.mdb"),
+        };
+        let parent = match data_file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let temp_dir = tempfile::Builder::new()
+            .prefix(".lmdb_compact_")
+            .rand_bytes(5)
+            .tempdir_in(parent)?;
+        let compacted = temp_dir.path().join("data.mdb");
+        self.compact_to(match no_sub_dir {
+            true => &compacted,
+            false => temp_dir.path(),
+        })?;
+        drop(self);
+        fs::rename(&compacted, &data_file)?;
+        LMDB::open(&opened.env_path, opened.db_name.as_deref(), opened.lmdb_args.clone())
+    }
+
+    /// Remove every entry from the database and then shrink the data file to match.
+    ///
+    /// [clear][db_map_trait::DBMap::clear] frees the pages the entries used, but LMDB keeps them for reuse, so
+    /// the data file stays at the size it grew to. This clears the database and then compacts the
+    /// environment with [compact_in_place][LMDB::compact_in_place], so the file only holds what
+    /// is left: the empty database and any other databases in the environment.
+    ///
+    /// Swapping the file needs the same exclusive access as `compact_in_place`, so this consumes
+    /// the handle and returns the reopened one. If any clones of this handle still exist an error
+    /// is returned before anything is cleared. If the compaction fails, the database has still
+    /// been cleared.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let data_file = temp_dir.path().join("data.mdb");
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+    /// db.batch_insert((0_u32..500).map(|i| (i.to_be_bytes(), [0xAA_u8; 1024]))).unwrap();
+    /// let grown = std::fs::metadata(&data_file).unwrap().len();
+    ///
+    /// let db = db.clear_and_compact().unwrap();
+    /// assert!(db.is_empty().unwrap());
+    /// assert!(std::fs::metadata(&data_file).unwrap().len() < grown / 10);
+    /// ```
+    pub fn clear_and_compact(self) -> Result<LMDB> {
+        self.check_sole_handle("clear and compact")?;
+        self.clear()?;
+        self.compact_in_place()
+    }
+
+    /// Close this handle and open the same database again with different arguments.
+    ///
+    /// This is the way to change the options of an open database, most often to give it a larger
+    /// [map_size][LMDBArgs::map_size]. Like [close][LMDB::close], it needs exclusive access: if
+    /// any clones of this handle still exist, an error is returned and this handle is dropped.
+    /// Taking the handle by value also means no transaction can still be using the old
+    /// environment. If opening again fails the error is returned and the database is left closed.
+    ///
+    /// Not every option can change once the database has data in it:
+    ///
+    /// - `map_size` can grow freely. A size smaller than the space already in use is silently
+    ///   raised to that size.
+    /// - `max_readers`, `max_map_size`, and the runtime `env_flags` (like
+    ///   [EnvironmentFlags::NO_SYNC] or [EnvironmentFlags::READ_ONLY]) take effect on reopening.
+    ///   [EnvironmentFlags::NO_SUB_DIR] must match how the environment was created.
+    /// - `max_dbs` must still allow the named database to be opened.
+    /// - `file_mode` only applies to files that are created, and `integer_keys` and `reverse_key`
+    ///   only apply to a database that is created, so none of them change an existing database.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs {
+    ///     map_size: Some(1024 * 1024),
+    ///     ..Default::default()
+    /// }).unwrap();
+    /// db.insert(b"key", b"value").unwrap();
+    ///
+    /// let db = db.reopen_with(LMDBArgs {
+    ///     map_size: Some(16 * 1024 * 1024),
+    ///     ..Default::default()
+    /// }).unwrap();
+    /// assert_eq!(db.map_size().unwrap(), 16 * 1024 * 1024);
+    /// assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+    /// ```
+    pub fn reopen_with(self, lmdb_args: LMDBArgs) -> Result<LMDB> {
+        self.check_sole_handle("reopen")?;
+        let opened = Arc::clone(&self.opened);
+        drop(self);
+        LMDB::open(&opened.env_path, opened.db_name.as_deref(), lmdb_args)
+    }
+
+    /// Sync the environment to disk and close it.
+    ///
+    /// Dropping an `LMDB` only closes the environment once the last clone is dropped, which makes
+    /// it hard to tell when the files are released. `close` makes that explicit: if this is the
+    /// only handle to the environment, the data is flushed to disk (unless the handle is
+    /// read-only) and the environment is closed before `close` returns, so the directory can
+    /// safely be removed afterwards.
+    ///
+    /// If any clones of this handle still exist, an error is returned. This handle is dropped
+    /// either way, and the environment stays open for the remaining clones.
+    ///
+    /// Source: [lmdb::Environment::sync].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+    /// db.insert(b"key", b"value").unwrap();
+    ///
+    /// // A clone keeps the environment open.
+    /// let clone = db.clone();
+    /// assert!(db.close().is_err());
+    /// clone.close().unwrap();
+    /// temp_dir.close().unwrap();
+    /// ```
+    pub fn close(self) -> Result<()> {
+        self.check_sole_handle("close")?;
+        if !self.read_only {
+            self.env.sync(true).map_err(Error::from)?;
+        }
+        Ok(())
+    }
+
+    /// Get the number of handles sharing this environment: this one and all of its clones.
+    ///
+    /// [close][LMDB::close], [compact_in_place][LMDB::compact_in_place], and
+    /// [reopen_with][LMDB::reopen_with] need this to be 1, so it helps find the clone that is
+    /// keeping them from working. Wrappers that own a handle, like [LMDBSet] or a
+    /// [Cached][db_map_trait::Cached] map, count as one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+    /// assert_eq!(db.handle_count(), 1);
+    ///
+    /// let clone = db.clone();
+    /// assert_eq!(db.handle_count(), 2);
+    /// drop(clone);
+    /// db.close().unwrap();
+    /// ```
+    pub fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.env)
+    }
+
+    /// Return an error if any other handle shares this environment.
+    fn check_sole_handle(&self, action: &str) -> anyhow::Result<()> {
+        match self.handle_count() {
+            1 => Ok(()),
+            count => Err(anyhow::anyhow!(
+                "cannot {action}: the environment is still referenced by {} other handles", count - 1,
+            )),
+        }
+    }
+
+    /// Get the environment flags that are actually in effect.
+    ///
+    /// This reads the live flags from LMDB (`mdb_env_get_flags`), so it can be used to confirm
+    /// that options like [EnvironmentFlags::NO_SYNC] were applied as intended.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use lmdb::EnvironmentFlags;
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs{
+    ///     env_flags: Some(EnvironmentFlags::NO_SYNC),
+    ///     max_readers: Some(42),
+    ///     ..Default::default()
+    /// }).unwrap();
+    ///
+    /// assert!(db.env_flags().unwrap().contains(EnvironmentFlags::NO_SYNC));
+    /// assert!(!db.env_flags().unwrap().contains(EnvironmentFlags::READ_ONLY));
+    /// assert_eq!(db.max_readers().unwrap(), 42);
+    /// ```
+    pub fn env_flags(&self) -> Result<EnvironmentFlags> {
+        let mut flags: c_uint = 0;
+        lmdb_result(unsafe { lmdb_sys::mdb_env_get_flags(self.env.env(), &mut flags) })
+            .map_err(Error::from)?;
+        Ok(EnvironmentFlags::from_bits_truncate(flags))
+    }
+
+    /// Return `true` if this handle was opened with [EnvironmentFlags::READ_ONLY].
+    ///
+    /// Every write through a read-only handle fails with
+    /// [Error::ReadOnly][db_map_trait::Error::ReadOnly] before a write transaction is attempted.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Return [Error::ReadOnly][db_map_trait::Error::ReadOnly] if this is a read-only handle.
+    fn check_writable(&self) -> Result<()> {
+        match self.read_only {
+            true => Err(db_map_trait::Error::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    /// Return [Error::InvalidKey][db_map_trait::Error::InvalidKey] for a key that LMDB can't
+    /// store (see [check_key_size]) or, if the database uses integer keys, a key that isn't the
+    /// size of the keys already stored (or, before any are, the size of a `u32` or a `usize`).
     #[inline]
-    fn begin_ro_txn<'env>(&'env self) -> LMDBResult<lmdb::RoTransaction<'env>> {
-        self.env.begin_ro_txn()
+    fn check_key(&self, key: &[u8]) -> Result<()> {
+        check_key_size(key, self.max_key_size)?;
+        if !self.integer_keys {
+            return Ok(());
+        }
+        match self.integer_key_size.get() {
+            Some(&size) if key.len() != size => Err(db_map_trait::Error::InvalidKey(format!(
+                "integer key {} is the wrong size: this database's integer keys are {} bytes, not {}",
+                HexKey(key), size, key.len(),
+            ))),
+            None if key.len() != 4 && key.len() != std::mem::size_of::<usize>() => {
+                Err(db_map_trait::Error::InvalidKey(format!(
+                    "integer key {} is the wrong size: integer keys must be 4 or {} bytes, not {}",
+                    HexKey(key), std::mem::size_of::<usize>(), key.len(),
+                )))
+            }
+            _ => Ok(()),
+        }
     }
 
-    /// Begin a read-write transaction.
+    /// Check a key that is about to be stored, like [check_key][LMDB::check_key].
+    ///
+    /// The first integer key stored through a handle on an empty database fixes the size of
+    /// every integer key after it, so a database never mixes `u32` and `usize` keys.
     #[inline]
-    fn begin_rw_txn<'env>(&'env self) -> LMDBResult<lmdb::RwTransaction<'env>> {
-        self.env.begin_rw_txn()
+    fn check_new_key(&self, key: &[u8]) -> Result<()> {
+        self.check_key(key)?;
+        match self.integer_keys && *self.integer_key_size.get_or_init(|| key.len()) != key.len() {
+            // Another thread fixed a different size first.
+            true => self.check_key(key),
+            false => Ok(()),
+        }
     }
-}
 
-impl DBMap for LMDB {
-    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
-        where
-            K: AsRef<[u8]>,
-            F: FnOnce(&[u8]) -> T
-    {
-        let db = &self.db;
-        let txn = self.begin_ro_txn().map_err(|e| Error::from(e))?;
-        match txn.get(**db, &key) {
-            Ok(result) => Ok(Some(mapper(result))),
-            Err(LMDBError::NotFound) => Ok(None),
-            Err(err) => Err(Error::from(err).into()),
+    /// Compare two keys in the order the database sorts them.
+    ///
+    /// Integer keys only support unbounded ranges, so they are compared as bytes here.
+    fn compare_keys(&self, a: &[u8], b: &[u8]) -> std::cmp::Ordering {
+        match self.reverse_key {
+            true => a.iter().rev().cmp(b.iter().rev()),
+            false => a.cmp(b),
         }
     }
 
-    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
-        let db = &self.db;
-        let mut txn = self.begin_rw_txn().map_err(|e| Error::from(e))?;
-        txn.put(**db, &key, &value, WriteFlags::empty()).map_err(|e| Error::from(e))?;
-        txn.commit().map_err(|e| Error::from(e))?;
-        Ok(())
+    /// Get the key to seek a cursor to before scanning for a prefix.
+    ///
+    /// The keys under a prefix are only together when keys are compared from the front, so with
+    /// [reverse_key][LMDBArgs::reverse_key] the scan starts at the beginning (an empty key).
+    fn prefix_seek<'p>(&self, prefix: &'p [u8]) -> &'p [u8] {
+        match self.reverse_key {
+            true => &[],
+            false => prefix,
+        }
     }
 
-    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
-        where
-            K: AsRef<[u8]>,
-            V: AsRef<[u8]>,
-            F: FnOnce(&[u8]) -> T
-    {
-        let db = &self.db;
-        let mut txn = self.begin_rw_txn().map_err(|e| Error::from(e))?;
-        let result = match txn.get(**db, &key) {
-            Ok(result) => Some(mapper(result)),
-            Err(LMDBError::NotFound) => None,
-            Err(err) => { return Err(Error::from(err).into()) }
-        };
-        txn.put(**db, &key, &value, WriteFlags::empty()).map_err(|e| Error::from(e))?;
-        txn.commit().map_err(|e| Error::from(e))?;
-        Ok(result)
+    /// Get the form of a value to store, with a checksum header if
+    /// [value_checksum][LMDBArgs::value_checksum] is enabled.
+    fn seal_value<'v>(&self, value: &'v [u8]) -> Cow<'v, [u8]> {
+        match self.value_checksum {
+            true => Cow::Owned(checksum::seal(value)),
+            false => Cow::Borrowed(value),
+        }
     }
 
-    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
-        let db = &self.db;
-        let mut txn = self.begin_rw_txn().map_err(|e| Error::from(e))?;
-        txn.del(**db, &key, None).map_err(|e| Error::from(e))?;
-        txn.commit().map_err(|e| Error::from(e))?;
-        Ok(())
+    /// Get the value from its stored form, checking and stripping its checksum header if
+    /// [value_checksum][LMDBArgs::value_checksum] is enabled.
+    fn open_value<'v>(&self, key: &[u8], stored: &'v [u8]) -> Result<&'v [u8]> {
+        match self.value_checksum {
+            true => checksum::open(stored).ok_or_else(|| db_map_trait::Error::Corruption(format!(
+                "the value for key {} doesn't match its checksum", HexKey(key),
+            ))),
+            false => Ok(stored),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use proptest::prelude::*;
-    use db_map_test::impl_db_map_tests;
+    /// Get the maximum number of reader slots for the environment.
+    pub fn max_readers(&self) -> Result<c_uint> {
+        let mut readers: c_uint = 0;
+        lmdb_result(unsafe { lmdb_sys::mdb_env_get_maxreaders(self.env.env(), &mut readers) })
+            .map_err(Error::from)?;
+        Ok(readers)
+    }
 
-    fn open_temp_lmdb(db_name: Option<&str>) -> Result<LMDB> {
-        let temp_dir = tempfile::Builder::new()
-            .prefix("lmdb_test_dir_")
-            .rand_bytes(5)
-            .tempdir()?;
-        LMDB::open(temp_dir.path(), db_name, LMDBArgs::default())
+    /// Get the size of the memory map currently in effect.
+    ///
+    /// This is the requested [LMDBArgs::map_size] rounded up to a whole number of OS pages, or
+    /// larger if the map has grown (see [LMDB::insert_growing]) or the existing data needed more
+    /// room.
+    ///
+    /// Source: `mdb_env_info`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs{
+    ///     map_size: Some(1_000_000),
+    ///     ..Default::default()
+    /// }).unwrap();
+    ///
+    /// let map_size = db.map_size().unwrap();
+    /// assert!(map_size >= 1_000_000);
+    /// assert!(db.used_bytes().unwrap() <= map_size as u64);
+    /// ```
+    pub fn map_size(&self) -> Result<size_t> {
+        Ok(self.env.info().map_err(Error::from)?.map_size())
     }
 
-    impl_db_map_tests! {
-        let db = open_temp_lmdb(None).unwrap();
+    /// Get the number of bytes of the memory map that the environment actually uses.
+    ///
+    /// This is `(last_pgno + 1) * page_size`: every page up to the highest one ever used,
+    /// including pages that have since been freed and will be reused. It is the size the data file
+    /// has grown to, which is usually much less than the reserved [LMDBArgs::map_size].
+    ///
+    /// Source: `mdb_env_info` and `mdb_env_stat`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+    ///
+    /// let empty = db.used_bytes().unwrap();
+    /// db.insert(b"key", vec![0_u8; 64 * 1024]).unwrap();
+    /// assert!(db.used_bytes().unwrap() >= empty + 64 * 1024);
+    /// ```
+    pub fn used_bytes(&self) -> Result<u64> {
+        let info = self.env.info().map_err(Error::from)?;
+        let stat = self.env.stat().map_err(Error::from)?;
+        Ok((info.last_pgno() as u64 + 1) * stat.page_size() as u64)
+    }
+
+    /// Get the number of pages on the free list: pages that were used and then freed, and will be
+    /// reused for new data instead of growing the file.
+    ///
+    /// Freed pages still count towards [used_bytes][LMDB::used_bytes], so the more of the used
+    /// pages are free, the more [compact_to][LMDB::compact_to] would save. Pages freed while an
+    /// older read transaction is still open are included, even though they can't be reused until
+    /// that transaction ends.
+    ///
+    /// Source: the free-list database (`FREE_DBI`), read with `mdb_cursor_get`. Each of its
+    /// records lists the pages freed by one write transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+    ///
+    /// db.insert(b"big", vec![0_u8; 64 * 1024]).unwrap();
+    /// let used = db.used_bytes().unwrap();
+    /// db.remove(b"big").unwrap();
+    ///
+    /// // Removing the value frees its pages, but the file doesn't shrink.
+    /// assert!(db.free_pages().unwrap() >= 16);
+    /// assert!(db.used_bytes().unwrap() >= used);
+    /// ```
+    pub fn free_pages(&self) -> Result<u64> {
+        let txn = self.begin_ro_txn().map_err(Error::from)?;
+        let mut cursor = std::ptr::null_mut();
+        // The free list is always database 0, which the lmdb crate doesn't give a handle for.
+        lmdb_result(unsafe { lmdb_sys::mdb_cursor_open(txn.txn(), 0, &mut cursor) }).map_err(Error::from)?;
+        let mut key = lmdb_sys::MDB_val { mv_size: 0, mv_data: std::ptr::null_mut() };
+        let mut data = lmdb_sys::MDB_val { mv_size: 0, mv_data: std::ptr::null_mut() };
+        let mut pages = 0;
+        let result = loop {
+            match unsafe { lmdb_sys::mdb_cursor_get(cursor, &mut key, &mut data, lmdb_sys::MDB_NEXT) } {
+                lmdb_sys::MDB_SUCCESS => {
+                    // Each record is an array of page numbers that starts with its length.
+                    if data.mv_size >= std::mem::size_of::<size_t>() {
+                        pages += unsafe { (data.mv_data as *const size_t).read_unaligned() } as u64;
+                    }
+                }
+                lmdb_sys::MDB_NOTFOUND => break Ok(pages),
+                err_code => break Err(LMDBError::from_err_code(err_code)),
+            }
+        };
+        unsafe { lmdb_sys::mdb_cursor_close(cursor) };
+        Ok(result.map_err(Error::from)?)
+    }
+
+    /// Get the number of transactions this handle and its clones have begun, committed, and
+    /// aborted.
+    ///
+    /// The counts start at zero when the database is opened and are shared by every clone of the
+    /// handle. Each `DBMap` operation uses one transaction (and [insert_growing][LMDB::insert_growing]
+    /// one per attempt), so this can show, for example, a hot loop making many more reads than
+    /// expected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{LMDB, LMDBArgs, TxnMetrics};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+    ///
+    /// db.insert(b"key", b"value").unwrap();
+    /// db.clone().get(b"key").unwrap();
+    /// // Removing a missing key fails, which aborts its transaction.
+    /// assert!(db.remove(b"missing").is_err());
+    ///
+    /// assert_eq!(db.txn_metrics(), TxnMetrics { read_txns: 1, write_txns: 2, commits: 1, aborts: 1 });
+    /// ```
+    pub fn txn_metrics(&self) -> TxnMetrics {
+        let counters = &self.txn_counters;
+        TxnMetrics {
+            read_txns: counters.read_txns.load(Ordering::Relaxed),
+            write_txns: counters.write_txns.load(Ordering::Relaxed),
+            commits: counters.commits.load(Ordering::Relaxed),
+            aborts: counters.aborts.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Begin a write transaction for streaming many writes, committed together with
+    /// [Writer::commit].
+    ///
+    /// This is the recommended way to make a high volume of writes: they share one transaction
+    /// instead of each `DBMap` write committing its own, and the caller decides when to commit.
+    /// Dropping the [Writer] without committing discards the writes. See [Writer] for an example.
+    pub fn writer(&self) -> Result<Writer<'_>> {
+        Writer::new(self)
+    }
+
+    /// Begin a write transaction for writes to several databases in this handle's environment,
+    /// committed together with [MultiWriter::commit].
+    ///
+    /// The other databases are opened with [open_db][LMDB::open_db]. Dropping the [MultiWriter]
+    /// without committing discards the writes to all of them. See [MultiWriter] for an example.
+    pub fn multi_writer(&self) -> Result<MultiWriter<'_>> {
+        MultiWriter::new(self)
+    }
+
+    /// Emit a warning if an operation that started at `start` took longer than the threshold.
+    #[cfg(feature = "slow-log")]
+    fn log_if_slow(&self, op: &'static str, key_len: Option<usize>, start: Option<std::time::Instant>) {
+        if let (Some(threshold), Some(start)) = (self.slow_threshold, start) {
+            let elapsed = start.elapsed();
+            if elapsed > threshold {
+                tracing::warn!(op, key_len, ?elapsed, ?threshold, "slow LMDB operation");
+            }
+        }
+    }
+
+    /// Insert data for a specified key, growing the memory map if it is full.
+    ///
+    /// If the insert fails because the memory map is full, the map size is doubled (but never
+    /// beyond [LMDBArgs::max_map_size]) and the insert is tried again. If the map can't grow any
+    /// further, the `MapFull` error is returned.
+    ///
+    /// Resizing the map requires that no transactions are active in this process. Every handle on
+    /// the environment (clones and [open_db][LMDB::open_db] handles) shares a lock that the
+    /// resize takes, so it waits for the transactions other threads have open to end, and new
+    /// ones wait for the resize. This is safe to call while other threads use the environment,
+    /// but not while this thread has a transaction open, such as a [Writer], which would never
+    /// end. Handles opened separately with [LMDB::open], and other processes, aren't coordinated.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{LMDB, LMDBArgs};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// // Start with a map that is too small for the value.
+    /// let db = LMDB::open(temp_dir.path(), None, LMDBArgs{
+    ///     map_size: Some(64 * 1024),
+    ///     ..Default::default()
+    /// }).unwrap();
+    ///
+    /// let value = vec![0x55_u8; 256 * 1024];
+    /// assert!(db.insert(b"key", &value).is_err());
+    /// db.insert_growing(b"key", &value).unwrap();
+    /// assert_eq!(db.get(b"key").unwrap(), Some(value));
+    /// ```
+    pub fn insert_growing<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        self.check_writable()?;
+        self.check_new_key(key.as_ref())?;
+        loop {
+            match self.insert_inner(key.as_ref(), value.as_ref()) {
+                Err(LMDBError::MapFull) => self.grow_map()?,
+                result => { return Ok(result.map_err(Error::from)?) }
+            }
+        }
+    }
+
+    /// Double the size of the memory map, up to the configured maximum.
+    ///
+    /// The resize lock is taken for writing, so this waits for every transaction on the
+    /// environment in this process to end, and holds off new ones until the map is resized. The
+    /// caller must not have a transaction open.
+    fn grow_map(&self) -> anyhow::Result<()> {
+        let _resize = self.resize_lock.write();
+        let map_size = self.env.info()?.map_size();
+        if map_size >= self.max_map_size {
+            return Err(LMDBError::MapFull.into());
+        }
+        self.env.set_map_size(page_align(map_size.saturating_mul(2).min(self.max_map_size)))?;
+        Ok(())
+    }
+
+    /// Insert data for a key in its own write transaction.
+    fn insert_inner(&self, key: &[u8], value: &[u8]) -> LMDBResult<()> {
+        let db = &self.db;
+        let mut txn = self.begin_rw_txn()?;
+        txn.put(**db, &key, &self.seal_value(value), WriteFlags::empty())?;
+        txn.commit()
+    }
+
+    /// Get the value at the first or last key with a single cursor operation, so the key and its
+    /// value come from the same transaction.
+    fn boundary_value(&self, op: c_uint) -> Result<Option<Vec<u8>>> {
+        let txn = self.begin_ro_txn().map_err(Error::from)?;
+        let cursor = txn.open_ro_cursor(*self.db).map_err(Error::from)?;
+        match cursor.get(None, None, op) {
+            Ok((key, value)) => Ok(Some(self.open_value(key.unwrap_or_default(), value)?.to_vec())),
+            Err(LMDBError::NotFound) => Ok(None),
+            Err(err) => Err(Error::from(err).into()),
+        }
+    }
+
+    /// Open a read-only transaction.
+    #[inline]
+    fn begin_ro_txn<'env>(&'env self) -> LMDBResult<ReadTxn<'env>> {
+        self.txn_counters.read_txns.fetch_add(1, Ordering::Relaxed);
+        let resize = ResizeGuard::new(&self.resize_lock);
+        Ok(ReadTxn { txn: self.env.begin_ro_txn()?, _resize: resize })
+    }
+
+    /// Begin a read-write transaction.
+    #[inline]
+    fn begin_rw_txn<'env>(&'env self) -> LMDBResult<WriteTxn<'env>> {
+        self.txn_counters.write_txns.fetch_add(1, Ordering::Relaxed);
+        let resize = ResizeGuard::new(&self.resize_lock);
+        let txn = self.env.begin_rw_txn()?;
+        Ok(WriteTxn { txn: Some(txn), counters: &self.txn_counters, _resize: resize })
+    }
+}
+
+impl OpenDb for LMDB {
+    type Config = LMDBConfig;
+
+    fn open(config: LMDBConfig) -> Result<LMDB> {
+        LMDB::open(&config.env_path, config.db_name.as_deref(), config.lmdb_args)
+    }
+}
+
+impl DBMap for LMDB {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T
+    {
+        self.check_key(key.as_ref())?;
+        slow_log!(self, "get_map", key.as_ref().len(), {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            match txn.get(**db, &key) {
+                Ok(result) => Ok(Some(mapper(self.open_value(key.as_ref(), result)?))),
+                Err(LMDBError::NotFound) => Ok(None),
+                Err(err) => Err(Error::from(err).into()),
+            }
+        })
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        self.check_writable()?;
+        self.check_new_key(key.as_ref())?;
+        slow_log!(self, "insert", key.as_ref().len(), {
+            self.insert_inner(key.as_ref(), value.as_ref()).map_err(Error::from)?;
+            Ok(())
+        })
+    }
+
+    /// Reserve space for the whole value in the database and copy each chunk straight into it,
+    /// without joining them first.
+    fn insert_chunks<K, I, V>(&self, key: K, chunks: I) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            I: IntoIterator<Item = V>,
+            V: AsRef<[u8]>,
+    {
+        self.check_writable()?;
+        self.check_new_key(key.as_ref())?;
+        // The chunks are gathered first, since the length has to be known before reserving.
+        let chunks: Vec<V> = chunks.into_iter().collect();
+        let header_len = match self.value_checksum {
+            true => checksum::HEADER_LEN,
+            false => 0,
+        };
+        let len = header_len + chunks.iter().map(|chunk| chunk.as_ref().len()).sum::<usize>();
+        slow_log!(self, "insert_chunks", key.as_ref().len(), {
+            let db = &self.db;
+            let mut txn = self.begin_rw_txn().map_err(Error::from)?;
+            let slot = txn.reserve(**db, &key, len, WriteFlags::empty()).map_err(Error::from)?;
+            let mut offset = header_len;
+            for chunk in &chunks {
+                let chunk = chunk.as_ref();
+                slot[offset..offset + chunk.len()].copy_from_slice(chunk);
+                offset += chunk.len();
+            }
+            if self.value_checksum {
+                checksum::reseal(slot);
+            }
+            txn.commit().map_err(Error::from)?;
+            Ok(())
+        })
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T
+    {
+        self.check_writable()?;
+        self.check_new_key(key.as_ref())?;
+        slow_log!(self, "fetch_and_replace_map", key.as_ref().len(), {
+            let db = &self.db;
+            let mut txn = self.begin_rw_txn().map_err(Error::from)?;
+            let result = match txn.get(**db, &key) {
+                Ok(result) => Some(mapper(self.open_value(key.as_ref(), result)?)),
+                Err(LMDBError::NotFound) => None,
+                Err(err) => { return Err(Error::from(err).into()) }
+            };
+            txn.put(**db, &key, &self.seal_value(value.as_ref()), WriteFlags::empty()).map_err(Error::from)?;
+            txn.commit().map_err(Error::from)?;
+            Ok(result)
+        })
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        self.check_writable()?;
+        self.check_key(key.as_ref())?;
+        slow_log!(self, "remove", key.as_ref().len(), {
+            let db = &self.db;
+            let mut txn = self.begin_rw_txn().map_err(Error::from)?;
+            txn.del(**db, &key, None).map_err(Error::from)?;
+            txn.commit().map_err(Error::from)?;
+            Ok(())
+        })
+    }
+
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        self.check_writable()?;
+        self.check_new_key(key.as_ref())?;
+        slow_log!(self, "update", key.as_ref().len(), {
+            let db = &self.db;
+            let mut txn = self.begin_rw_txn().map_err(Error::from)?;
+            let new_value = match txn.get(**db, &key) {
+                Ok(existing) => f(Some(self.open_value(key.as_ref(), existing)?)),
+                Err(LMDBError::NotFound) => f(None),
+                Err(err) => { return Err(Error::from(err).into()) }
+            };
+            match new_value {
+                Some(value) => txn.put(**db, &key, &self.seal_value(&value), WriteFlags::empty()),
+                None => match txn.del(**db, &key, None) {
+                    Err(LMDBError::NotFound) => Ok(()),
+                    result => result,
+                },
+            }.map_err(Error::from)?;
+            txn.commit().map_err(Error::from)?;
+            Ok(())
+        })
+    }
+
+    /// Insert all of the pairs in a single write transaction.
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        self.check_writable()?;
+        slow_log!(self, "batch_insert", {
+            let db = &self.db;
+            let mut txn = self.begin_rw_txn().map_err(Error::from)?;
+            for (key, value) in items {
+                self.check_new_key(key.as_ref())?;
+                txn.put(**db, &key, &self.seal_value(value.as_ref()), WriteFlags::empty()).map_err(Error::from)?;
+            }
+            txn.commit().map_err(Error::from)?;
+            Ok(())
+        })
+    }
+
+    /// Insert all of the pairs in a single write transaction with [WriteFlags::APPEND], which adds
+    /// each key at the end of the database without searching for its place.
+    ///
+    /// LMDB checks the order itself, against the last key in the database, so a key that doesn't
+    /// sort after every key already written (including the keys already in the database) fails
+    /// with [Error::NotSorted][db_map_trait::Error::NotSorted] and nothing is written. The order
+    /// is the database's own, so it follows [reverse_key][LMDBArgs::reverse_key] and
+    /// [integer_keys][LMDBArgs::integer_keys].
+    fn bulk_load_sorted<I, K, V>(&self, sorted_items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        self.check_writable()?;
+        slow_log!(self, "bulk_load_sorted", {
+            let db = &self.db;
+            let mut txn = self.begin_rw_txn().map_err(Error::from)?;
+            for (key, value) in sorted_items {
+                let key = key.as_ref();
+                self.check_new_key(key)?;
+                match txn.put(**db, &key, &self.seal_value(value.as_ref()), WriteFlags::APPEND) {
+                    Err(LMDBError::KeyExist) => {
+                        return Err(db_map_trait::Error::NotSorted(format!(
+                            "key {} is not after the last key in the database", HexKey(key),
+                        )))
+                    }
+                    result => result.map_err(Error::from)?,
+                }
+            }
+            txn.commit().map_err(Error::from)?;
+            Ok(())
+        })
+    }
+
+    /// Move the entries in a single write transaction.
+    ///
+    /// Every new key is checked before anything is written, so a key that would be too long
+    /// under `to_prefix` fails the move with [Error::InvalidKey][db_map_trait::Error::InvalidKey]
+    /// and leaves the database unchanged.
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        self.check_writable()?;
+        let (from_prefix, to_prefix) = (from_prefix.as_ref(), to_prefix.as_ref());
+        slow_log!(self, "move_prefix", from_prefix.len(), {
+            let db = &self.db;
+            let mut txn = self.begin_rw_txn().map_err(Error::from)?;
+            let mut entries = Vec::new();
+            {
+                let mut cursor = txn.open_ro_cursor(**db).map_err(Error::from)?;
+                let iter = match self.prefix_seek(from_prefix).is_empty() {
+                    true => cursor.iter_start(),
+                    false => cursor.iter_from(from_prefix),
+                };
+                for item in iter {
+                    let (key, value) = item.map_err(Error::from)?;
+                    let Some(new_key) = move_key(key, from_prefix, to_prefix) else {
+                        match self.reverse_key {
+                            true => continue,
+                            false => break,
+                        }
+                    };
+                    entries.push((key.to_vec(), new_key, value.to_vec()));
+                }
+            }
+            for (_, new_key, _) in &entries {
+                self.check_new_key(new_key)?;
+            }
+            // Delete every entry before writing any, so overlapping prefixes can't collide.
+            for (key, _, _) in &entries {
+                txn.del(**db, key, None).map_err(Error::from)?;
+            }
+            for (_, new_key, value) in &entries {
+                txn.put(**db, new_key, value, WriteFlags::empty()).map_err(Error::from)?;
+            }
+            txn.commit().map_err(Error::from)?;
+            Ok(entries.len())
+        })
+    }
+
+    /// Scan the database with a cursor in a read transaction, which is aborted on error.
+    fn try_for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        slow_log!(self, "try_for_each", {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            let mut cursor = txn.open_ro_cursor(**db).map_err(Error::from)?;
+            for item in cursor.iter_start() {
+                let (key, value) = item.map_err(Error::from)?;
+                f(key, self.open_value(key, value)?)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Seek a cursor to the prefix and scan until the keys no longer match.
+    ///
+    /// With [reverse_key][LMDBArgs::reverse_key] keys under a prefix aren't next to each other,
+    /// so every key is checked.
+    fn try_scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let prefix = prefix.as_ref();
+        slow_log!(self, "try_scan_prefix", prefix.len(), {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            let mut cursor = txn.open_ro_cursor(**db).map_err(Error::from)?;
+            // LMDB rejects an empty key, so an empty prefix scans from the start.
+            let iter = match self.prefix_seek(prefix).is_empty() {
+                true => cursor.iter_start(),
+                false => cursor.iter_from(prefix),
+            };
+            for item in iter {
+                let (key, value) = item.map_err(Error::from)?;
+                if !key.starts_with(prefix) {
+                    match self.reverse_key {
+                        true => continue,
+                        false => break,
+                    }
+                }
+                f(key, self.open_value(key, value)?)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Seek a cursor to the start of the range and scan until the keys pass the end.
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        slow_log!(self, "try_scan_range", {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            let mut cursor = txn.open_ro_cursor(**db).map_err(Error::from)?;
+            // LMDB rejects an empty key, so an empty start key scans from the start.
+            let iter = match start {
+                Bound::Included(start) | Bound::Excluded(start) if !start.is_empty() => cursor.iter_from(start),
+                _ => cursor.iter_start(),
+            };
+            for item in iter {
+                let (key, value) = item.map_err(Error::from)?;
+                if start == Bound::Excluded(key) {
+                    continue;
+                }
+                let past_end = match end {
+                    Bound::Included(end) => self.compare_keys(key, end).is_gt(),
+                    Bound::Excluded(end) => self.compare_keys(key, end).is_ge(),
+                    Bound::Unbounded => false,
+                };
+                if past_end {
+                    break;
+                }
+                f(key, self.open_value(key, value)?)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Get one page of the entries under a prefix, seeking the cursor just past `start_after`.
+    fn scan_page<P: AsRef<[u8]>>(&self, prefix: P, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        let prefix = prefix.as_ref();
+        slow_log!(self, "scan_page", prefix.len(), {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            let mut cursor = txn.open_ro_cursor(**db).map_err(Error::from)?;
+            let start = match start_after {
+                Some(after) if self.reverse_key || after >= prefix => after,
+                _ => self.prefix_seek(prefix),
+            };
+            // LMDB rejects an empty key, so an empty start key scans from the start.
+            let iter = match start.is_empty() {
+                true => cursor.iter_start(),
+                false => cursor.iter_from(start),
+            };
+            let mut page = Vec::new();
+            for item in iter {
+                let (key, value) = item.map_err(Error::from)?;
+                if Some(key) == start_after {
+                    continue;
+                }
+                if !key.starts_with(prefix) {
+                    match self.reverse_key {
+                        true => continue,
+                        false => break,
+                    }
+                }
+                if page.len() == limit.max(1) {
+                    let next = page.last().map(|(key, _): &(Vec<u8>, Vec<u8>)| key.clone());
+                    return Ok((page, next));
+                }
+                page.push((key.to_vec(), self.open_value(key, value)?.to_vec()));
+            }
+            Ok((page, None))
+        })
+    }
+
+    /// Position a cursor on the first key in a read transaction.
+    fn first_value(&self) -> Result<Option<Vec<u8>>> {
+        slow_log!(self, "first_value", { self.boundary_value(MDB_FIRST) })
+    }
+
+    /// Position a cursor on the last key in a read transaction.
+    fn last_value(&self) -> Result<Option<Vec<u8>>> {
+        slow_log!(self, "last_value", { self.boundary_value(MDB_LAST) })
+    }
+
+    /// Read the entry count from the database statistics.
+    ///
+    /// LMDB keeps the count in the database's root, so this takes constant time however many
+    /// entries there are, instead of walking them with a cursor. The statistics are read in a new
+    /// read transaction on every call, so they always reflect the latest committed write,
+    /// including a [`clear`][DBMap::clear].
+    fn len(&self) -> Result<usize> {
+        slow_log!(self, "len", {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            let stat = txn.stat(**db).map_err(Error::from)?;
+            Ok(stat.entries())
+        })
+    }
+
+    /// Copy the entries with a cursor in one read transaction. With
+    /// [reverse_key][LMDBArgs::reverse_key] or [integer_keys][LMDBArgs::integer_keys] the cursor
+    /// order isn't byte order, so the entries are sorted afterwards.
+    fn iter(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        slow_log!(self, "iter", {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            let mut cursor = txn.open_ro_cursor(**db).map_err(Error::from)?;
+            let mut entries = Vec::new();
+            for item in cursor.iter_start() {
+                let (key, value) = item.map_err(Error::from)?;
+                entries.push((key.to_vec(), self.open_value(key, value)?.to_vec()));
+            }
+            if self.reverse_key || self.integer_keys {
+                entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            }
+            Ok(entries)
+        })
+    }
+
+    /// Copy the keys with a cursor in one read transaction. The values are neither copied nor
+    /// checked against their [checksums][LMDBArgs::value_checksum], so the pages of large values
+    /// aren't read. With [reverse_key][LMDBArgs::reverse_key] or
+    /// [integer_keys][LMDBArgs::integer_keys] the keys are sorted afterwards.
+    fn keys(&self) -> Result<Vec<Vec<u8>>> {
+        slow_log!(self, "keys", {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            let mut cursor = txn.open_ro_cursor(**db).map_err(Error::from)?;
+            let mut keys = Vec::new();
+            for item in cursor.iter_start() {
+                let (key, _) = item.map_err(Error::from)?;
+                keys.push(key.to_vec());
+            }
+            if self.reverse_key || self.integer_keys {
+                keys.sort_unstable();
+            }
+            Ok(keys)
+        })
+    }
+
+    /// Remove every entry in one write transaction. Other databases in the same environment are
+    /// left alone.
+    ///
+    /// Source: [lmdb::RwTransaction::clear_db].
+    fn clear(&self) -> Result<()> {
+        self.check_writable()?;
+        slow_log!(self, "clear", {
+            let mut txn = self.begin_rw_txn().map_err(Error::from)?;
+            txn.clear_db(*self.db).map_err(Error::from)?;
+            txn.commit().map_err(Error::from)?;
+            Ok(())
+        })
+    }
+
+    /// Walk the range with a cursor, reading one byte from every OS page of each value, so that
+    /// the pages holding the range are faulted into the memory map. LMDB has no way to reserve
+    /// space for keys ahead of time, so this only warms the page cache.
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        slow_log!(self, "prepare_range", {
+            let db = &self.db;
+            let txn = self.begin_ro_txn().map_err(Error::from)?;
+            let mut cursor = txn.open_ro_cursor(**db).map_err(Error::from)?;
+            // LMDB rejects an empty key, so an empty start key walks from the start.
+            let iter = match start.is_empty() {
+                true => cursor.iter_start(),
+                false => cursor.iter_from(start),
+            };
+            let page_size = os_page_size();
+            let mut touched = 0_u8;
+            for item in iter {
+                let (key, value) = item.map_err(Error::from)?;
+                if self.compare_keys(key, end).is_ge() {
+                    break;
+                }
+                touched = value.iter().step_by(page_size).fold(touched, |acc, &byte| acc ^ byte);
+            }
+            std::hint::black_box(touched);
+            Ok(())
+        })
+    }
+
+    /// Batches are written in one transaction, and scans run in a read transaction, which sees a
+    /// snapshot of the database. Scans are in byte order unless the database was created with
+    /// [reverse_key][LMDBArgs::reverse_key] or [integer_keys][LMDBArgs::integer_keys].
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            ordered_iteration: !(self.reverse_key || self.integer_keys),
+            atomic_batch: true,
+            persistent: true,
+            snapshots: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use db_map_test::impl_db_map_tests;
+    use db_map_trait::export::ExportFormat;
+    use tempfile::TempDir;
+
+    /// Open a database with `lmdb_args` in a new temporary directory, which is returned so that
+    /// the test can keep it alive.
+    fn open_temp_lmdb_with(db_name: Option<&str>, lmdb_args: LMDBArgs) -> (TempDir, LMDB) {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDB::open(temp_dir.path(), db_name, lmdb_args).unwrap();
+        (temp_dir, db)
+    }
+
+    fn open_temp_lmdb(db_name: Option<&str>) -> (TempDir, LMDB) {
+        open_temp_lmdb_with(db_name, LMDBArgs::default())
+    }
+
+    impl_db_map_tests! {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+    }
+
+    fn open_checksum_lmdb() -> (TempDir, LMDB) {
+        open_temp_lmdb_with(None, LMDBArgs {
+            value_checksum: true,
+            ..Default::default()
+        })
+    }
+
+    /// The shared suite again, with checksums enabled, to check that they are invisible.
+    mod checksummed {
+        use super::*;
+
+        impl_db_map_tests! {
+            let (_temp_dir, db) = open_checksum_lmdb();
+        }
+    }
+
+    fn open_small_temp_lmdb(map_size: size_t, max_map_size: Option<size_t>) -> (TempDir, LMDB) {
+        open_temp_lmdb_with(None, LMDBArgs {
+            map_size: Some(map_size),
+            max_map_size,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn capabilities_report_everything() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        assert_eq!(db.capabilities(), Capabilities {
+            ordered_iteration: true,
+            atomic_batch: true,
+            persistent: true,
+            snapshots: true,
+        });
+        let buffered = db_map_trait::Buffered::new(db, 4);
+        assert!(!buffered.capabilities().atomic_batch);
+        assert!(buffered.capabilities().persistent);
+    }
+
+    #[test]
+    fn boxed_batch_insert_stays_atomic() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        let too_long = vec![b'k'; db.max_key_size + 1];
+        let boxed = Box::new(db) as Box<dyn db_map_trait::DynDbMap>;
+        assert!(boxed.capabilities().atomic_batch);
+        let batch = [(&b"first"[..], &b"1"[..]), (&too_long, b"2"), (b"last", b"3")];
+        assert!(boxed.batch_insert(batch).is_err());
+        assert!(boxed.is_empty().unwrap());
+        boxed.batch_insert([("first", "1"), ("last", "3")]).unwrap();
+        assert_eq!(boxed.len().unwrap(), 2);
+    }
+
+    #[test]
+    fn scan_prefix_seeks_to_prefix() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        for key in ["a", "ab/1", "ab/2", "abc", "b"] {
+            db.insert(key, key).unwrap();
+        }
+        let scan = |prefix: &str| {
+            let mut keys = Vec::new();
+            db.scan_prefix(prefix, |key, _| keys.push(String::from_utf8(key.to_vec()).unwrap()))
+                .unwrap();
+            keys
+        };
+        assert_eq!(scan("ab/"), ["ab/1", "ab/2"]);
+        assert_eq!(scan("ab"), ["ab/1", "ab/2", "abc"]);
+        assert_eq!(scan(""), ["a", "ab/1", "ab/2", "abc", "b"]);
+        assert!(scan("c").is_empty());
+    }
+
+    fn open_integer_key_lmdb() -> (TempDir, LMDB) {
+        open_temp_lmdb_with(None, LMDBArgs {
+            integer_keys: true,
+            ..Default::default()
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn integer_keys_iterate_in_numeric_order(keys in proptest::collection::vec(any::<u64>(), 1..200)) {
+            let (_temp_dir, db) = open_integer_key_lmdb();
+            for key in &keys {
+                db.insert(key.to_ne_bytes(), key.to_be_bytes()).unwrap();
+            }
+
+            let mut expected = keys.clone();
+            expected.sort_unstable();
+            expected.dedup();
+            let mut found = Vec::new();
+            db.for_each(|key, value| {
+                let key = u64::from_ne_bytes(key.try_into().unwrap());
+                assert_eq!(value, key.to_be_bytes());
+                found.push(key);
+            }).unwrap();
+            prop_assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn reverse_key_digest_and_diff_match_other_backends() {
+        let (_temp_dir, reversed) = open_temp_lmdb_with(None, LMDBArgs {
+            reverse_key: true,
+            ..Default::default()
+        });
+        let entries = [("ab", "1"), ("b", "2"), ("ba", "3")];
+        reversed.batch_insert(entries).unwrap();
+        let memory = db_map_btreemap::BTreeMapDB::from_iter(entries);
+
+        assert_eq!(reversed.digest().unwrap(), memory.digest().unwrap());
+        assert_eq!(reversed.digest_prefix(b"b").unwrap(), memory.digest_prefix(b"b").unwrap());
+        assert!(db_map_trait::diff::diff(&reversed, &memory).unwrap().is_empty());
+        assert!(db_map_trait::diff::diff(&memory, &reversed).unwrap().is_empty());
+        assert!(db_map_trait::diff::contents_eq(&reversed, &memory).unwrap());
+        let (mut reversed_dump, mut memory_dump) = (Vec::new(), Vec::new());
+        db_map_trait::export::export(&reversed, &mut reversed_dump, ExportFormat::JsonLines).unwrap();
+        db_map_trait::export::export(&memory, &mut memory_dump, ExportFormat::JsonLines).unwrap();
+        assert_eq!(String::from_utf8(reversed_dump).unwrap(), String::from_utf8(memory_dump).unwrap());
+
+        memory.insert(b"ba", b"changed").unwrap();
+        memory.remove(b"ab").unwrap();
+        memory.insert(b"c", b"4").unwrap();
+        let diff = db_map_trait::diff::diff(&reversed, &memory).unwrap();
+        assert_eq!(diff.only_in_a, [b"ab"]);
+        assert_eq!(diff.only_in_b, [b"c"]);
+        assert_eq!(diff.differing, [b"ba"]);
+    }
+
+    #[test]
+    fn reverse_key_orders_by_reversed_bytes() {
+        // Each database gets its own environment, since one can't be opened twice in a process.
+        let (_reversed_dir, reversed) = open_temp_lmdb_with(None, LMDBArgs {
+            reverse_key: true,
+            ..Default::default()
+        });
+        let (_forward_dir, forward) = open_temp_lmdb(None);
+        let names = ["com.example", "org.example", "example.com", "mail.example.com", "example.org", "b", "ab"];
+        for db in [&reversed, &forward] {
+            db.batch_insert(names.iter().map(|name| (name, name))).unwrap();
+        }
+        let scan = |db: &LMDB| {
+            let mut keys = Vec::new();
+            db.for_each(|key, _| keys.push(String::from_utf8(key.to_vec()).unwrap())).unwrap();
+            keys
+        };
+
+        let mut expected = names.map(String::from).to_vec();
+        expected.sort_by(|a, b| a.bytes().rev().cmp(b.bytes().rev()));
+        assert_eq!(scan(&reversed), expected);
+        assert_eq!(scan(&reversed), ["b", "ab", "org.example", "com.example", "example.org", "example.com", "mail.example.com"]);
+        assert_ne!(scan(&reversed), scan(&forward));
+        assert_eq!(reversed.first_value().unwrap(), Some(b"b".to_vec()));
+        assert_eq!(reversed.last_value().unwrap(), Some(b"mail.example.com".to_vec()));
+        assert!(!reversed.capabilities().ordered_iteration);
+        // `iter` is in byte order whatever the scan order.
+        assert_eq!(reversed.iter().unwrap(), forward.iter().unwrap());
+        assert_eq!(reversed.keys().unwrap(), forward.keys().unwrap());
+
+        // Range bounds follow the reversed order too.
+        let mut range = Vec::new();
+        reversed.scan_range(Bound::Included(b"org.example"), Bound::Excluded(b"example.com"), |key, _| {
+            range.push(key.to_vec());
+        }).unwrap();
+        assert_eq!(range, [&b"org.example"[..], b"com.example", b"example.org"]);
+
+        // Prefix scans still find every key under the prefix, wherever it sorts.
+        for db in [&reversed, &forward] {
+            let mut found = Vec::new();
+            db.scan_prefix(b"example", |key, _| found.push(key.to_vec())).unwrap();
+            found.sort();
+            assert_eq!(found, [&b"example.com"[..], b"example.org"]);
+            let (page, next) = db.scan_page(b"example", None, 1).unwrap();
+            let (rest, _) = db.scan_page(b"example", next.as_deref(), 10).unwrap();
+            assert_eq!(page.len() + rest.len(), 2);
+        }
+        assert_eq!(reversed.move_prefix(b"example", b"sample").unwrap(), 2);
+        assert!(reversed.get(b"sample.org").unwrap().is_some());
+    }
+
+    #[test]
+    fn integer_keys_are_not_lexicographic() {
+        let (_temp_dir, db) = open_integer_key_lmdb();
+        // Little-endian 256 starts with a 0 byte, so it sorts first as a byte string.
+        for key in [256_u64, 255, 1, u64::MAX, 1 << 32] {
+            db.insert(key.to_ne_bytes(), b"").unwrap();
+        }
+        let mut keys = Vec::new();
+        db.for_each(|key, _| keys.push(u64::from_ne_bytes(key.try_into().unwrap()))).unwrap();
+        assert_eq!(keys, [1, 255, 256, 1 << 32, u64::MAX]);
+        assert_eq!(db.scan_page(b"", Some(&255_u64.to_ne_bytes()), 2).unwrap().0.len(), 2);
+
+        // `iter` is in byte order, not numeric order.
+        let mut expected: Vec<_> = keys.iter().map(|key| (key.to_ne_bytes().to_vec(), Vec::new())).collect();
+        expected.sort();
+        assert_eq!(db.iter().unwrap(), expected);
+    }
+
+    #[test]
+    fn integer_keys_list_keys_in_byte_order() {
+        let (_temp_dir, db) = open_integer_key_lmdb();
+        assert!(!db.capabilities().ordered_iteration);
+        let keys = [0x0100_u64, 0x01, 0x0200, u64::MAX, 0];
+        for key in keys {
+            db.insert(key.to_ne_bytes(), key.to_be_bytes()).unwrap();
+        }
+        let mut expected: Vec<_> = keys.iter().map(|key| key.to_ne_bytes().to_vec()).collect();
+        expected.sort();
+        assert_eq!(db.keys().unwrap(), expected);
+
+        // Generic code that relies on the capability sorts for itself and gets the same order.
+        let memory = db_map_btreemap::BTreeMapDB::from_iter(keys.map(|key| (key.to_ne_bytes(), key.to_be_bytes())));
+        assert_eq!(db.digest().unwrap(), memory.digest().unwrap());
+    }
+
+    #[test]
+    fn integer_keys_reject_mis_sized_keys() {
+        let (_temp_dir, db) = open_integer_key_lmdb();
+        db.insert(7_usize.to_ne_bytes(), b"seven").unwrap();
+        db.insert(8_usize.to_ne_bytes(), b"eight").unwrap();
+        for key in [&b"abc"[..], &[0; 5], &[0; 16]] {
+            assert!(matches!(db.insert(key, b"bad"), Err(db_map_trait::Error::InvalidKey(_))));
+            assert!(matches!(db.get(key), Err(db_map_trait::Error::InvalidKey(_))));
+        }
+        assert!(matches!(
+            db.batch_insert([(&9_usize.to_ne_bytes()[..], &b"ok"[..]), (&[1, 2][..], &b"bad"[..])]),
+            Err(db_map_trait::Error::InvalidKey(msg)) if msg.starts_with("integer key 0102 ") && msg.ends_with("not 2"),
+        ));
+        assert_eq!(db.len().unwrap(), 2);
+        assert_eq!(db.get(7_usize.to_ne_bytes()).unwrap(), Some(b"seven".to_vec()));
+
+        // The check follows the database's flags, not the arguments it was reopened with.
+        let db = db.reopen_with(LMDBArgs::default()).unwrap();
+        assert!(matches!(db.insert(b"abc", b"bad"), Err(db_map_trait::Error::InvalidKey(_))));
+    }
+
+    #[test]
+    fn integer_keys_keep_the_first_size() {
+        let (_temp_dir, db) = open_integer_key_lmdb();
+        let clone = db.clone();
+        db.insert(7_u32.to_ne_bytes(), b"seven").unwrap();
+        assert!(matches!(
+            clone.insert(8_u64.to_ne_bytes(), b"eight"),
+            Err(db_map_trait::Error::InvalidKey(msg)) if msg.ends_with("integer keys are 4 bytes, not 8"),
+        ));
+        assert!(matches!(db.get(8_u64.to_ne_bytes()), Err(db_map_trait::Error::InvalidKey(_))));
+        let mut writer = db.writer().unwrap();
+        assert!(matches!(writer.insert(8_u64.to_ne_bytes(), b"eight"), Err(db_map_trait::Error::InvalidKey(_))));
+        writer.insert(8_u32.to_ne_bytes(), b"eight").unwrap();
+        writer.commit().unwrap();
+
+        // A reopened handle takes the size from the keys already stored.
+        drop(clone);
+        let db = db.reopen_with(LMDBArgs::default()).unwrap();
+        assert!(matches!(db.insert(9_u64.to_ne_bytes(), b"nine"), Err(db_map_trait::Error::InvalidKey(_))));
+        db.insert(9_u32.to_ne_bytes(), b"nine").unwrap();
+        assert_eq!(db.len().unwrap(), 3);
+    }
+
+    /// Count the entries by scanning them, rather than with the statistics used by `len`.
+    fn count_entries(db: &LMDB) -> usize {
+        let mut count = 0;
+        db.for_each(|_, _| count += 1).unwrap();
+        count
+    }
+
+    #[test]
+    fn clear_and_len_agree() {
+        let (_temp_dir, db, other) = open_two_sub_databases();
+        other.insert(b"key", b"value").unwrap();
+
+        for round in 1_u32..=3 {
+            let n = round * 100;
+            db.batch_insert((0..n).map(|i| (i.to_be_bytes(), round.to_be_bytes()))).unwrap();
+            assert_eq!(db.len().unwrap(), n as usize);
+            assert_eq!(count_entries(&db), n as usize);
+
+            // A clone shares the environment, so it must see the clear too.
+            let clone = db.clone();
+            assert_eq!(clone.len().unwrap(), n as usize);
+            db.clear().unwrap();
+            assert_eq!(db.len().unwrap(), 0);
+            assert_eq!(count_entries(&db), 0);
+            assert_eq!(clone.len().unwrap(), 0);
+            assert!(db.is_empty().unwrap());
+        }
+
+        // Only the cleared database is emptied.
+        assert_eq!(other.len().unwrap(), 1);
+        assert_eq!(count_entries(&other), 1);
+    }
+
+    #[test]
+    fn close_sole_handle() {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+        db.insert(b"key", b"value").unwrap();
+        db.close().unwrap();
+
+        // The data was synced and the environment can be opened again.
+        let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+        db.close().unwrap();
+    }
+
+    #[test]
+    fn close_while_cloned_fails() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        let clone = db.clone();
+        let err = db.close().unwrap_err();
+        assert!(err.to_string().contains("still referenced"), "{err}");
+
+        // The clone still works and can then be closed.
+        clone.insert(b"key", b"value").unwrap();
+        assert_eq!(clone.get(b"key").unwrap(), Some(b"value".to_vec()));
+        clone.close().unwrap();
+    }
+
+    #[test]
+    fn reopen_with_larger_map_size() {
+        let map_size = 256 * 1024;
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDB::open(temp_dir.path(), None, LMDBArgs {
+            map_size: Some(map_size),
+            ..Default::default()
+        }).unwrap();
+        let mut filled = 0_u32;
+        while db.insert(filled.to_be_bytes(), [0x5A_u8; 1024]).is_ok() {
+            filled += 1;
+        }
+        assert!(filled > 0);
+
+        let clone = db.clone();
+        assert!(clone.reopen_with(LMDBArgs::default()).is_err());
+        let db = db.reopen_with(LMDBArgs {
+            map_size: Some(4 * map_size),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(db.opened.env_path, temp_dir.path());
+        assert_eq!(db.map_size().unwrap(), 4 * map_size);
+        assert_eq!(db.len().unwrap(), filled as usize);
+        for i in 0..filled {
+            assert_eq!(db.get(i.to_be_bytes()).unwrap(), Some(vec![0x5A_u8; 1024]));
+        }
+        db.insert(filled.to_be_bytes(), [0x5A_u8; 1024]).unwrap();
+    }
+
+    #[test]
+    fn txn_metrics_count_operations() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        assert_eq!(db.txn_metrics(), TxnMetrics::default());
+        for i in 0_u32..10 {
+            db.insert(i.to_be_bytes(), i.to_le_bytes()).unwrap();
+        }
+        for i in 0_u32..25 {
+            db.get((i % 10).to_be_bytes()).unwrap();
+        }
+        db.batch_insert([(b"a", b"1"), (b"b", b"2")]).unwrap();
+        db.update(b"a", |_| None).unwrap();
+        assert!(db.remove(b"a").is_err());
+        assert_eq!(db.len().unwrap(), 11);
+        assert_eq!(db.txn_metrics(), TxnMetrics {
+            read_txns: 26,
+            write_txns: 13,
+            commits: 12,
+            aborts: 1,
+        });
+    }
+
+    #[test]
+    fn writer_commits_streamed_writes() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        db.insert(0_u32.to_be_bytes(), b"old").unwrap();
+        let mut writer = db.writer().unwrap();
+        for i in 0_u32..10_000 {
+            writer.insert(i.to_be_bytes(), i.to_le_bytes()).unwrap();
+        }
+        assert_eq!(writer.fetch_and_replace(1_u32.to_be_bytes(), b"one").unwrap(),
+            Some(1_u32.to_le_bytes().to_vec()));
+        assert_eq!(writer.get(1_u32.to_be_bytes()).unwrap(), Some(b"one".to_vec()));
+        assert!(writer.remove(2_u32.to_be_bytes()).unwrap());
+        writer.commit().unwrap();
+
+        assert_eq!(db.len().unwrap(), 9_999);
+        assert_eq!(db.get(0_u32.to_be_bytes()).unwrap(), Some(0_u32.to_le_bytes().to_vec()));
+        assert_eq!(db.get(1_u32.to_be_bytes()).unwrap(), Some(b"one".to_vec()));
+        assert!(db.get(2_u32.to_be_bytes()).unwrap().is_none());
+        assert_eq!(db.get(9_999_u32.to_be_bytes()).unwrap(), Some(9_999_u32.to_le_bytes().to_vec()));
+        let metrics = db.txn_metrics();
+        assert_eq!((metrics.write_txns, metrics.commits, metrics.aborts), (2, 2, 0));
+    }
+
+    #[test]
+    fn writer_dropped_without_commit_discards_writes() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        db.insert(b"kept", b"value").unwrap();
+        let mut writer = db.writer().unwrap();
+        for i in 0_u32..1000 {
+            writer.insert(i.to_be_bytes(), b"discarded").unwrap();
+        }
+        assert!(writer.remove(b"kept").unwrap());
+        drop(writer);
+
+        assert_eq!(db.len().unwrap(), 1);
+        assert_eq!(db.get(b"kept").unwrap(), Some(b"value".to_vec()));
+        let metrics = db.txn_metrics();
+        assert_eq!((metrics.write_txns, metrics.commits, metrics.aborts), (2, 1, 1));
+
+        // The aborted transaction doesn't block the next one.
+        db.insert(b"after", b"abort").unwrap();
+        assert_eq!(db.len().unwrap(), 2);
+    }
+
+    fn open_two_sub_databases() -> (tempfile::TempDir, LMDB, LMDB) {
+        let temp_dir = db_map_test::temp_dir();
+        let records = LMDB::open(temp_dir.path(), Some("records"), LMDBArgs {
+            max_dbs: Some(2),
+            ..Default::default()
+        }).unwrap();
+        let index = records.open_db(Some("index")).unwrap();
+        (temp_dir, records, index)
+    }
+
+    #[test]
+    fn multi_writer_commits_both_databases() {
+        let (_temp_dir, records, index) = open_two_sub_databases();
+        assert_eq!(records.handle_count(), 2);
+        records.insert(b"old", b"record").unwrap();
+        index.insert(b"old-index", b"old").unwrap();
+
+        let mut writer = index.multi_writer().unwrap();
+        writer.insert(&records, b"new", b"record").unwrap();
+        writer.insert(&index, b"new-index", b"new").unwrap();
+        assert!(writer.remove(&records, b"old").unwrap());
+        assert!(writer.remove(&index, b"old-index").unwrap());
+        assert_eq!(writer.get(&records, b"new").unwrap(), Some(b"record".to_vec()));
+        assert!(writer.get(&index, b"new").unwrap().is_none());
+        writer.commit().unwrap();
+
+        assert_eq!(records.get(b"new").unwrap(), Some(b"record".to_vec()));
+        assert_eq!(index.get(b"new-index").unwrap(), Some(b"new".to_vec()));
+        assert_eq!((records.len().unwrap(), index.len().unwrap()), (1, 1));
+
+        // Both databases survive reopening the environment.
+        drop(index);
+        let records = records.reopen_with(LMDBArgs { max_dbs: Some(2), ..Default::default() }).unwrap();
+        let index = records.open_db(Some("index")).unwrap();
+        assert_eq!(records.get(b"new").unwrap(), Some(b"record".to_vec()));
+        assert_eq!(index.get(b"new-index").unwrap(), Some(b"new".to_vec()));
+    }
+
+    #[test]
+    fn multi_writer_dropped_without_commit_discards_both() {
+        let (_temp_dir, records, index) = open_two_sub_databases();
+        let mut writer = records.multi_writer().unwrap();
+        writer.insert(&records, b"record", b"value").unwrap();
+        writer.insert(&index, b"index", b"record").unwrap();
+        drop(writer);
+
+        assert!(records.is_empty().unwrap());
+        assert!(index.is_empty().unwrap());
+        assert_eq!(records.txn_metrics().aborts, 1);
+    }
+
+    #[test]
+    fn multi_writer_rejects_other_environments() {
+        let (_temp_dir, records, _index) = open_two_sub_databases();
+        let (_other_dir, other) = open_temp_lmdb(None);
+        let mut writer = records.multi_writer().unwrap();
+        assert!(matches!(writer.insert(&other, b"key", b"value"), Err(db_map_trait::Error::DBError(_))));
+        assert!(writer.get(&other, b"key").is_err());
+        writer.insert(&records, b"key", b"value").unwrap();
+        writer.commit().unwrap();
+        assert!(other.is_empty().unwrap());
+        assert_eq!(records.len().unwrap(), 1);
+    }
+
+    #[test]
+    fn value_checksum_round_trips() {
+        let (_temp_dir, db) = open_checksum_lmdb();
+        db.insert(b"a", b"apple").unwrap();
+        db.insert(b"empty", b"").unwrap();
+        assert_eq!(db.fetch_and_replace(b"a", b"avocado").unwrap(), Some(b"apple".to_vec()));
+        db.update(b"b", |_| Some(b"banana".to_vec())).unwrap();
+        let mut writer = db.writer().unwrap();
+        writer.insert(b"c", b"cherry").unwrap();
+        assert_eq!(writer.get(b"c").unwrap(), Some(b"cherry".to_vec()));
+        writer.commit().unwrap();
+
+        assert_eq!(db.get(b"a").unwrap(), Some(b"avocado".to_vec()));
+        assert_eq!(db.get(b"empty").unwrap(), Some(Vec::new()));
+        assert_eq!(db.first_value().unwrap(), Some(b"avocado".to_vec()));
+        assert_eq!(db.last_value().unwrap(), Some(b"".to_vec()));
+        let mut entries = Vec::new();
+        db.for_each(|key, value| entries.push((key.to_vec(), value.to_vec()))).unwrap();
+        assert_eq!(entries, [
+            (b"a".to_vec(), b"avocado".to_vec()),
+            (b"b".to_vec(), b"banana".to_vec()),
+            (b"c".to_vec(), b"cherry".to_vec()),
+            (b"empty".to_vec(), Vec::new()),
+        ]);
+
+        // The stored values carry the header.
+        let txn = db.begin_ro_txn().unwrap();
+        let stored = txn.get(*db.db, b"a").unwrap();
+        assert_eq!(stored.len(), checksum::HEADER_LEN + b"avocado".len());
+        assert_eq!(&stored[checksum::HEADER_LEN..], b"avocado");
+    }
+
+    #[test]
+    fn value_checksum_detects_tampering() {
+        let (_temp_dir, db) = open_checksum_lmdb();
+        db.batch_insert([(&b"a"[..], &b"apple"[..]), (b"b", b"banana"), (b"c", b"cherry")]).unwrap();
+
+        // Change a stored value behind the checksum's back.
+        let mut txn = db.begin_rw_txn().unwrap();
+        let mut stored = txn.get(*db.db, b"b").unwrap().to_vec();
+        *stored.last_mut().unwrap() ^= 1;
+        txn.put(*db.db, b"b", &stored, WriteFlags::empty()).unwrap();
+        // A value written without a header is caught too.
+        txn.put(*db.db, b"d", b"raw", WriteFlags::empty()).unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(db.get(b"a").unwrap(), Some(b"apple".to_vec()));
+        for key in [&b"b"[..], b"d"] {
+            assert!(matches!(db.get(key), Err(db_map_trait::Error::Corruption(_))));
+            assert!(matches!(db.update(key, |_| None), Err(db_map_trait::Error::Corruption(_))));
+        }
+        let err = db.for_each(|_, _| ()).unwrap_err();
+        assert_eq!(err.to_string(), "corrupt data: the value for key 62 doesn't match its checksum");
+        assert!(matches!(db.scan_page(b"", None, 10), Err(db_map_trait::Error::Corruption(_))));
+        assert!(matches!(db.last_value(), Err(db_map_trait::Error::Corruption(_))));
+
+        // Overwriting the damaged value repairs it.
+        db.insert(b"b", b"blueberry").unwrap();
+        assert_eq!(db.get(b"b").unwrap(), Some(b"blueberry".to_vec()));
+    }
+
+    #[test]
+    fn len_does_not_walk_the_entries() {
+        let (_temp_dir, db) = open_small_temp_lmdb(64 * 1024 * 1024, None);
+        db.batch_insert((0_u32..100_000).map(|i| (i.to_be_bytes(), i.to_le_bytes()))).unwrap();
+
+        assert_eq!(count_entries(&db), 100_000);
+
+        // Each count is one read transaction that only looks at the statistics.
+        let before = db.txn_metrics();
+        for _ in 0..100 {
+            assert_eq!(db.len().unwrap(), 100_000);
+        }
+        assert_eq!(db.txn_metrics().read_txns - before.read_txns, 100);
+    }
+
+    #[test]
+    fn errors_downcast_to_lmdb_errors() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        let err = db.remove(b"missing").unwrap_err();
+        assert!(matches!(err, db_map_trait::Error::DBError(_)));
+        assert_eq!(err.source_lmdb(), Some(&LMDBError::NotFound));
+        assert_eq!(err.downcast_ref::<LMDBError>(), Some(&LMDBError::NotFound));
+
+        let (_temp_dir, db) = open_small_temp_lmdb(64 * 1024, None);
+        let err = (0_u32..1000).try_for_each(|i| db.insert(i.to_be_bytes(), [0_u8; 1024]))
+            .unwrap_err();
+        assert!(err.is_retryable());
+        assert_eq!(err.source_lmdb(), Some(&LMDBError::MapFull));
+
+        assert!(db_map_trait::Error::ReadOnly.source_lmdb().is_none());
+    }
+
+    #[test]
+    fn used_bytes_grows_within_map_size() {
+        let map_size = 4 * 1024 * 1024;
+        let (_temp_dir, db) = open_small_temp_lmdb(map_size, None);
+        let empty = db.used_bytes().unwrap();
+        assert!(empty > 0);
+        let mut previous = empty;
+        for i in 0_u32..50 {
+            db.insert(i.to_be_bytes(), vec![0xC3_u8; 16 * 1024]).unwrap();
+            let used = db.used_bytes().unwrap();
+            assert!(used >= previous, "insert {i}: {used} < {previous}");
+            assert!(used <= map_size as u64);
+            previous = used;
+        }
+        assert!(previous >= empty + 50 * 16 * 1024);
+    }
+
+    #[test]
+    fn free_pages_grow_after_deletes() {
+        let (_temp_dir, db) = open_small_temp_lmdb(16 * 1024 * 1024, None);
+        db.batch_insert((0_u32..1000).map(|i| (i.to_be_bytes(), vec![0x3C_u8; 1024]))).unwrap();
+        let before = db.free_pages().unwrap();
+        for i in 0_u32..1000 {
+            db.remove(i.to_be_bytes()).unwrap();
+        }
+        let after = db.free_pages().unwrap();
+        assert!(after > 0);
+        assert!(after > before, "{after} <= {before}");
+        assert!(db.is_empty().unwrap());
+    }
+
+    #[test]
+    fn read_only_handle_rejects_writes() {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+        db.insert(b"key", b"value").unwrap();
+        assert!(!db.is_read_only());
+        drop(db);
+
+        let db = LMDB::open(temp_dir.path(), None, LMDBArgs {
+            env_flags: Some(EnvironmentFlags::READ_ONLY),
+            ..Default::default()
+        }).unwrap();
+        assert!(db.is_read_only());
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+        assert!(matches!(db.insert(b"key", b"new"), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.remove(b"key"), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.fetch_and_replace(b"key", b"new"), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.update(b"key", |_| None), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.batch_insert([(b"a", b"b")]), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.insert_growing(b"key", b"new"), Err(db_map_trait::Error::ReadOnly)));
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+    }
+
+    /// Open a database in `temp_dir` with room for [insert_and_remove_most].
+    fn open_compaction_lmdb(temp_dir: &tempfile::TempDir) -> LMDB {
+        LMDB::open(temp_dir.path(), None, LMDBArgs {
+            map_size: Some(16 * 1024 * 1024),
+            ..Default::default()
+        }).unwrap()
+    }
+
+    /// Fill a database with entries and then remove most of them, leaving lots of free pages.
+    fn insert_and_remove_most(db: &LMDB) {
+        db.batch_insert((0_u32..4000).map(|i| (i.to_be_bytes(), [i as u8; 1024]))).unwrap();
+        for i in (0_u32..4000).filter(|i| i % 100 != 0) {
+            db.remove(i.to_be_bytes()).unwrap();
+        }
+    }
+
+    #[test]
+    fn compact_to_writes_smaller_copy() {
+        let temp_dir = db_map_test::temp_dir();
+        let db = open_compaction_lmdb(&temp_dir);
+        insert_and_remove_most(&db);
+        let dest_dir = db_map_test::temp_dir();
+        db.compact_to(dest_dir.path()).unwrap();
+        let original_len = fs::metadata(temp_dir.path().join("data.mdb")).unwrap().len();
+        let compacted_len = fs::metadata(dest_dir.path().join("data.mdb")).unwrap().len();
+        assert!(compacted_len < original_len, "{compacted_len} >= {original_len}");
+
+        let copy = LMDB::open(dest_dir.path(), None, LMDBArgs::default()).unwrap();
+        assert_eq!(copy.digest().unwrap(), db.digest().unwrap());
+    }
+
+    #[test]
+    fn compact_in_place_shrinks_file() {
+        let temp_dir = db_map_test::temp_dir();
+        let data_file = temp_dir.path().join("data.mdb");
+        let db = open_compaction_lmdb(&temp_dir);
+        insert_and_remove_most(&db);
+        let digest = db.digest().unwrap();
+        let original_len = fs::metadata(&data_file).unwrap().len();
+
+        let db = db.compact_in_place().unwrap();
+        let compacted_len = fs::metadata(&data_file).unwrap().len();
+        assert!(compacted_len < original_len, "{compacted_len} >= {original_len}");
+        assert_eq!(db.digest().unwrap(), digest);
+        assert_eq!(db.len().unwrap(), 40);
+        db.insert(b"after", b"compaction").unwrap();
+        assert_eq!(db.get(b"after").unwrap(), Some(b"compaction".to_vec()));
+    }
+
+    #[test]
+    fn clear_and_compact_leaves_a_small_file() {
+        let temp_dir = db_map_test::temp_dir();
+        let data_file = temp_dir.path().join("data.mdb");
+        let db = open_compaction_lmdb(&temp_dir);
+        db.batch_insert((0_u32..4000).map(|i| (i.to_be_bytes(), [i as u8; 1024]))).unwrap();
+        let grown_len = fs::metadata(&data_file).unwrap().len();
+        assert!(grown_len >= 4000 * 1024);
+
+        let db = db.clear_and_compact().unwrap();
+        let compacted_len = fs::metadata(&data_file).unwrap().len();
+        // An empty environment is only its two meta pages and a few tree pages.
+        assert!(compacted_len <= 8 * os_page_size() as u64, "{compacted_len} bytes after compacting");
+        assert!(db.is_empty().unwrap());
+        db.insert(b"after", b"compaction").unwrap();
+        assert_eq!(db.get(b"after").unwrap(), Some(b"compaction".to_vec()));
+
+        let clone = db.clone();
+        assert!(db.clear_and_compact().is_err());
+        assert_eq!(clone.get(b"after").unwrap(), Some(b"compaction".to_vec()));
+    }
+
+    #[test]
+    fn open_db_opens_and_reopens() {
+        fn open_generically<M: OpenDb>(config: M::Config) -> M {
+            M::open(config).unwrap()
+        }
+
+        let temp_dir = db_map_test::temp_dir();
+        let config = LMDBConfig {
+            env_path: temp_dir.path().to_path_buf(),
+            db_name: Some("named".to_string()),
+            lmdb_args: LMDBArgs {
+                max_dbs: Some(2),
+                ..Default::default()
+            },
+        };
+        let db: LMDB = open_generically(config.clone());
+        assert!(db.is_empty().unwrap());
+        db.insert(b"key", b"value").unwrap();
+        db.close().unwrap();
+
+        let db: LMDB = open_generically(config);
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(db.opened.db_name.as_deref(), Some("named"));
+    }
+
+    #[test]
+    fn handle_count_follows_clones() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        assert_eq!(db.handle_count(), 1);
+        let clones: Vec<LMDB> = (0..3).map(|_| db.clone()).collect();
+        assert_eq!(db.handle_count(), 4);
+        assert!(clones.iter().all(|clone| clone.handle_count() == 4));
+        let err = db.clone().close().unwrap_err();
+        assert!(err.to_string().ends_with("referenced by 4 other handles"), "{err}");
+
+        let mut clones = clones.into_iter();
+        drop(clones.next());
+        assert_eq!(db.handle_count(), 3);
+        drop(clones);
+        assert_eq!(db.handle_count(), 1);
+        db.close().unwrap();
+    }
+
+    #[test]
+    fn compact_in_place_requires_sole_handle() {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+        db.insert(b"key", b"value").unwrap();
+        let clone = db.clone();
+        assert!(db.compact_in_place().is_err());
+        assert_eq!(clone.get(b"key").unwrap(), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn map_size_is_rounded_up_to_whole_pages() {
+        let page_size = os_page_size();
+        let (_temp_dir, db) = open_small_temp_lmdb(10 * page_size + 1, None);
+        assert_eq!(db.map_size().unwrap(), 11 * page_size);
+
+        let (_temp_dir, db) = open_small_temp_lmdb(16 * page_size, None);
+        assert_eq!(db.map_size().unwrap(), 16 * page_size);
+    }
+
+    #[test]
+    fn insert_growing_grows_full_map() {
+        let (_temp_dir, db) = open_small_temp_lmdb(64 * 1024, None);
+        let value = vec![0xA5_u8; 512 * 1024];
+        let initial_size = db.env.info().unwrap().map_size();
+        db.insert_growing(b"key", &value).unwrap();
+        assert!(db.env.info().unwrap().map_size() > initial_size);
+        assert_eq!(db.get(b"key").unwrap(), Some(value));
+    }
+
+    #[test]
+    fn insert_growing_waits_for_readers() {
+        const VALUE_LEN: usize = 64 * 1024;
+        let (_temp_dir, db) = open_small_temp_lmdb(64 * 1024, Some(64 * 1024 * 1024));
+        let initial_size = db.map_size().unwrap();
+        db.insert(b"seed", [0x5A_u8; 100]).unwrap();
+        let done = std::sync::atomic::AtomicBool::new(false);
+
+        let reads: usize = std::thread::scope(|scope| {
+            let readers: Vec<_> = (0..4).map(|_| {
+                let (db, done) = (db.clone(), &done);
+                scope.spawn(move || {
+                    let mut reads = 0;
+                    while reads == 0 || !done.load(Ordering::Relaxed) {
+                        assert_eq!(db.get(b"seed").unwrap(), Some(vec![0x5A_u8; 100]));
+                        // A scan keeps its read transaction open across many entries.
+                        db.for_each(|key, value| match key {
+                            b"seed" => assert_eq!(value, [0x5A_u8; 100]),
+                            _ => assert!(value.len() == VALUE_LEN && value.iter().all(|&b| b == key[0])),
+                        }).unwrap();
+                        reads += 1;
+                    }
+                    reads
+                })
+            }).collect();
+            for i in 0_u8..64 {
+                db.insert_growing([i], vec![i; VALUE_LEN]).unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+            readers.into_iter().map(|reader| reader.join().unwrap()).sum()
+        });
+
+        assert!(reads >= 4);
+        assert!(db.map_size().unwrap() >= 64 * initial_size);
+        assert_eq!(db.len().unwrap(), 65);
+        for i in 0_u8..64 {
+            assert_eq!(db.get([i]).unwrap(), Some(vec![i; VALUE_LEN]));
+        }
+    }
+
+    /// Guard against `batch_insert` losing its single-transaction advantage.
+    ///
+    /// The threshold is deliberately loose: committing a transaction per entry is normally
+    /// orders of magnitude slower than one commit for all of them.
+    #[test]
+    fn batch_insert_is_faster_than_insert() {
+        const ENTRIES: u32 = 10_000;
+        let open = || {
+            let temp_dir = db_map_test::temp_dir();
+            let db = LMDB::open(temp_dir.path(), None, LMDBArgs {
+                map_size: Some(256 * 1024 * 1024),
+                ..Default::default()
+            }).unwrap();
+            (temp_dir, db)
+        };
+        let entries = || (0..ENTRIES).map(|i| (i.to_be_bytes(), (i as u128).to_le_bytes()));
+
+        let (_single_dir, single_db) = open();
+        let start = std::time::Instant::now();
+        for (key, value) in entries() {
+            single_db.insert(key, value).unwrap();
+        }
+        let single_time = start.elapsed();
+
+        let (_batch_dir, batch_db) = open();
+        let start = std::time::Instant::now();
+        batch_db.batch_insert(entries()).unwrap();
+        let batch_time = start.elapsed();
+
+        assert_eq!(single_db.len().unwrap(), ENTRIES as usize);
+        assert_eq!(batch_db.digest().unwrap(), single_db.digest().unwrap());
+        assert!(batch_time * 3 <= single_time,
+            "batch_insert took {batch_time:?}, individual inserts took {single_time:?}");
+    }
+
+    #[test]
+    fn map_in_place_changes_bytes() {
+        for (_temp_dir, db) in [open_temp_lmdb(None), open_checksum_lmdb()] {
+            // A value in its leaf page, and one big enough for overflow pages.
+            let large = vec![0x11_u8; 3 * os_page_size()];
+            db.insert(b"small", [0_u8, 1, 2, 3]).unwrap();
+            db.insert(b"large", &large).unwrap();
+
+            assert!(db.map_in_place(b"small", |value| value[2] = 0xFF).unwrap());
+            assert_eq!(db.get(b"small").unwrap(), Some(vec![0, 1, 0xFF, 3]));
+            assert!(db.map_in_place(b"large", |value| *value.last_mut().unwrap() = 0x22).unwrap());
+            let value = db.get(b"large").unwrap().unwrap();
+            assert_eq!(&value[..large.len() - 1], &large[..large.len() - 1]);
+            assert_eq!(value.last(), Some(&0x22));
+
+            assert!(!db.map_in_place(b"absent", |_| panic!("called for an absent key")).unwrap());
+            assert!(db.get(b"absent").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn insert_chunks_stores_the_concatenation() {
+        for (_temp_dir, db) in [open_temp_lmdb(None), open_checksum_lmdb()] {
+            let large = vec![0x44_u8; 3 * os_page_size()];
+            db.insert_chunks(b"key", [&b"first,"[..], b"", b"second,", &large, b"last"]).unwrap();
+            let mut expected = b"first,second,".to_vec();
+            expected.extend_from_slice(&large);
+            expected.extend_from_slice(b"last");
+            assert_eq!(db.get(b"key").unwrap(), Some(expected));
+
+            // Replacing with fewer bytes, and with no chunks at all.
+            db.insert_chunks(b"key", [b"a", b"b"]).unwrap();
+            assert_eq!(db.get(b"key").unwrap(), Some(b"ab".to_vec()));
+            db.insert_chunks(b"key", Vec::<Vec<u8>>::new()).unwrap();
+            assert_eq!(db.get(b"key").unwrap(), Some(Vec::new()));
+            assert_eq!(db.len().unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn prepare_range_leaves_contents_alone() {
+        for (_temp_dir, db) in [open_temp_lmdb(None), open_checksum_lmdb()] {
+            db.batch_insert((0_u32..200).map(|i| (i.to_be_bytes(), i.to_le_bytes()))).unwrap();
+            db.insert(50_u32.to_be_bytes(), vec![0x33_u8; 3 * os_page_size()]).unwrap();
+            let digest = db.digest().unwrap();
+
+            db.prepare_range(&10_u32.to_be_bytes(), &100_u32.to_be_bytes()).unwrap();
+            db.prepare_range(b"", &[0xFF; 8]).unwrap();
+            // An empty or backwards range is fine too.
+            db.prepare_range(&7_u32.to_be_bytes(), &7_u32.to_be_bytes()).unwrap();
+            db.prepare_range(&100_u32.to_be_bytes(), &10_u32.to_be_bytes()).unwrap();
+            assert_eq!(db.digest().unwrap(), digest);
+            assert_eq!(db.len().unwrap(), 200);
+        }
+    }
+
+    #[test]
+    fn bulk_load_sorted_appends_in_order() {
+        let (_temp_dir, db) = open_small_temp_lmdb(64 * 1024 * 1024, None);
+        db.bulk_load_sorted((0_u32..10_000).map(|i| (i.to_be_bytes(), i.to_le_bytes()))).unwrap();
+        assert_eq!(db.len().unwrap(), 10_000);
+        assert_eq!(db.get(1234_u32.to_be_bytes()).unwrap(), Some(1234_u32.to_le_bytes().to_vec()));
+        assert_eq!(db.last_value().unwrap(), Some(9_999_u32.to_le_bytes().to_vec()));
+
+        // More sorted keys can be appended after the existing ones.
+        db.bulk_load_sorted([(10_000_u32.to_be_bytes(), [0_u8])]).unwrap();
+        assert_eq!(db.len().unwrap(), 10_001);
+    }
+
+    #[test]
+    fn bulk_load_sorted_rejects_out_of_order_keys() {
+        let (_temp_dir, db) = open_temp_lmdb(None);
+        let result = db.bulk_load_sorted([(&b"a"[..], b"1"), (b"c", b"3"), (b"b", b"2")]);
+        match result {
+            Err(db_map_trait::Error::NotSorted(message)) => assert!(message.contains("62"), "{message}"),
+            other => panic!("expected NotSorted, got {other:?}"),
+        }
+        // The transaction was aborted, so even the keys in order weren't written.
+        assert!(db.is_empty().unwrap());
+
+        // A repeated key and a key before the existing ones are out of order too.
+        assert!(matches!(db.bulk_load_sorted([(b"a", b"1"), (b"a", b"2")]),
+            Err(db_map_trait::Error::NotSorted(_))));
+        db.insert(b"m", b"middle").unwrap();
+        assert!(matches!(db.bulk_load_sorted([(b"b", b"2")]), Err(db_map_trait::Error::NotSorted(_))));
+        assert_eq!(db.len().unwrap(), 1);
+    }
+
+    #[cfg(feature = "slow-log")]
+    #[test]
+    #[tracing_test::traced_test]
+    fn slow_operation_is_logged() {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDB::open(temp_dir.path(), None, LMDBArgs {
+            slow_threshold: Some(std::time::Duration::from_millis(1)),
+            ..Default::default()
+        }).unwrap();
+        db.insert(b"key", b"value").unwrap();
+        let value = db.get_map(b"key", |v| {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            v.to_vec()
+        }).unwrap();
+        assert_eq!(value, Some(b"value".to_vec()));
+        assert!(logs_contain("slow LMDB operation"));
+        assert!(logs_contain("op=\"get_map\""));
+    }
+
+    #[test]
+    fn map_full_is_retryable() {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDB::open(temp_dir.path(), None, LMDBArgs {
+            map_size: Some(64 * 1024),
+            ..Default::default()
+        }).unwrap();
+        let err = db.insert(b"key", vec![0xA5_u8; 512 * 1024]).unwrap_err();
+        assert!(matches!(err, db_map_trait::Error::Transient(_)), "unexpected error: {err:?}");
+        assert!(err.is_retryable());
+
+        // Other errors are not.
+        let err = db.remove(b"missing").unwrap_err();
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn insert_growing_respects_cap() {
+        let (_temp_dir, db) = open_small_temp_lmdb(64 * 1024, Some(128 * 1024));
+        let value = vec![0xA5_u8; 512 * 1024];
+        let err = db.insert_growing(b"key", &value).unwrap_err();
+        match err {
+            db_map_trait::Error::DBError(err) => {
+                assert!(matches!(err.downcast_ref::<LMDBError>(), Some(LMDBError::MapFull)));
+            }
+            err => panic!("unexpected error: {err:?}"),
+        }
+        assert!(db.env.info().unwrap().map_size() <= 128 * 1024);
+        assert!(db.get(b"key").unwrap().is_none());
     }
 }
@@ -0,0 +1,351 @@
+//! # A durable FIFO queue stored in LMDB.
+//!
+//! Items are popped in two steps so that a crash between taking an item and finishing with it
+//! doesn't lose the item: [`Queue::reserve`] moves the head of the queue into an in-flight
+//! sub-database, and [`Queue::ack`] deletes it once it has been handled (or [`Queue::nack`] puts
+//! it back at the head of the queue).
+
+use anyhow::{anyhow, Error};
+use db_map_trait::Result;
+use lmdb::{
+    Cursor,
+    Database,
+    DatabaseFlags,
+    Environment,
+    Error as LMDBError,
+    RwTransaction,
+    Transaction,
+    WriteFlags,
+};
+use lmdb_sys::{MDB_FIRST, MDB_LAST};
+use std::{
+    path::Path,
+    sync::Arc,
+};
+use super::{open_env, LMDBArgs};
+
+/// Identifies an item that has been reserved with [`Queue::reserve`].
+///
+/// Pass it to [`Queue::ack`] when the item has been handled, or to [`Queue::nack`] to return the
+/// item to the queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ReservationToken(u64);
+
+impl ReservationToken {
+    /// The sequence number the item was given when it was pushed.
+    pub fn sequence(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A durable FIFO queue with reserve/acknowledge semantics.
+///
+/// Three named sub-databases are created in the environment: `<name>.pending` holds the items
+/// waiting in the queue, `<name>.in_flight` holds the reserved items, and `<name>.meta` holds the
+/// next sequence number. When no name is given, `pending`, `in_flight`, and `meta` are used.
+/// Items are keyed by a big-endian `u64` sequence number, so the queue is ordered by the order
+/// items were pushed. Sequence numbers are never reused, even after the queue is drained, so a
+/// stale [`ReservationToken`] can't acknowledge a newer item.
+///
+/// Every operation runs in a single LMDB write transaction, so items are never lost or
+/// duplicated. Items that are still in flight when the process exits stay in the in-flight
+/// sub-database; call [`requeue_in_flight`][Queue::requeue_in_flight] on startup to deliver them
+/// again.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_lmdb::{LMDBArgs, Queue};
+/// use tempfile;
+///
+/// let temp_dir = tempfile::Builder::new()
+///     .prefix("lmdb_test_dir_")
+///     .rand_bytes(5)
+///     .tempdir()
+///     .unwrap();
+/// let queue = Queue::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+///
+/// queue.push(b"first").unwrap();
+/// queue.push(b"second").unwrap();
+///
+/// let (token, item) = queue.reserve().unwrap().unwrap();
+/// assert_eq!(item, b"first");
+/// queue.ack(token).unwrap();
+///
+/// let (token, item) = queue.reserve().unwrap().unwrap();
+/// assert_eq!(item, b"second");
+/// queue.nack(token).unwrap();
+///
+/// // The item that wasn't acknowledged is delivered again.
+/// let (token, item) = queue.reserve().unwrap().unwrap();
+/// assert_eq!(item, b"second");
+/// queue.ack(token).unwrap();
+/// assert!(queue.reserve().unwrap().is_none());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Queue {
+    env: Arc<Environment>,
+    pending: Arc<Database>,
+    in_flight: Arc<Database>,
+    meta: Arc<Database>,
+}
+
+/// The key in the metadata sub-database that holds the next sequence number.
+const NEXT_SEQUENCE_KEY: &[u8] = b"next_sequence";
+
+impl Queue {
+    /// Open a `Queue` in an LMDB environment.
+    pub fn open(env_path: &Path, queue_name: Option<&str>, lmdb_args: LMDBArgs) -> Result<Queue> {
+        Ok(Self::open_inner(env_path, queue_name, lmdb_args)?)
+    }
+
+    /// The implementation for the `open` function.
+    fn open_inner(env_path: &Path, queue_name: Option<&str>, lmdb_args: LMDBArgs) -> anyhow::Result<Queue> {
+        let lmdb_args = LMDBArgs {
+            max_dbs: Some(lmdb_args.max_dbs.unwrap_or(0).max(3)),
+            ..lmdb_args
+        };
+        let env = Arc::new(open_env(env_path, &lmdb_args)?);
+        let (pending_name, in_flight_name, meta_name) = match queue_name {
+            None => ("pending".to_string(), "in_flight".to_string(), "meta".to_string()),
+            Some(name) => (format!("{name}.pending"), format!("{name}.in_flight"), format!("{name}.meta")),
+        };
+        let pending = Arc::new(env.create_db(Some(&pending_name), DatabaseFlags::empty())?);
+        let in_flight = Arc::new(env.create_db(Some(&in_flight_name), DatabaseFlags::empty())?);
+        let meta = Arc::new(env.create_db(Some(&meta_name), DatabaseFlags::empty())?);
+        Ok(Self {
+            env,
+            pending,
+            in_flight,
+            meta,
+        })
+    }
+
+    /// Add an item to the end of the queue and return its sequence number.
+    ///
+    /// The sequence number comes from a counter that is advanced in the same transaction, so
+    /// every push gets a larger number than any before it.
+    pub fn push<V: AsRef<[u8]>>(&self, value: V) -> Result<u64> {
+        let mut txn = self.env.begin_rw_txn().map_err(Error::from)?;
+        let sequence = self.next_sequence(&txn)?;
+        let next = sequence.checked_add(1).ok_or_else(|| anyhow!("queue sequence overflow"))?;
+        txn.put(*self.pending, &sequence.to_be_bytes(), &value, WriteFlags::empty())
+            .map_err(Error::from)?;
+        txn.put(*self.meta, &NEXT_SEQUENCE_KEY, &next.to_be_bytes(), WriteFlags::empty())
+            .map_err(Error::from)?;
+        txn.commit().map_err(Error::from)?;
+        Ok(sequence)
+    }
+
+    /// Read the sequence number for the next push.
+    ///
+    /// A queue written before the counter was kept has none, so its counter starts after the
+    /// largest sequence number still in the queue.
+    fn next_sequence<T: Transaction>(&self, txn: &T) -> anyhow::Result<u64> {
+        match txn.get(*self.meta, &NEXT_SEQUENCE_KEY) {
+            Ok(next) => return decode_sequence(next),
+            Err(LMDBError::NotFound) => {}
+            Err(err) => return Err(err.into()),
+        }
+        let last_pending = last_sequence(txn, *self.pending)?;
+        let last_in_flight = last_sequence(txn, *self.in_flight)?;
+        match last_pending.max(last_in_flight) {
+            Some(last) => last.checked_add(1).ok_or_else(|| anyhow!("queue sequence overflow")),
+            None => Ok(0),
+        }
+    }
+
+    /// Take the item at the head of the queue, moving it into the in-flight sub-database.
+    ///
+    /// Returns `None` if the queue is empty.
+    pub fn reserve(&self) -> Result<Option<(ReservationToken, Vec<u8>)>> {
+        let mut txn = self.env.begin_rw_txn().map_err(Error::from)?;
+        let head = {
+            let cursor = txn.open_ro_cursor(*self.pending).map_err(Error::from)?;
+            match cursor.get(None, None, MDB_FIRST) {
+                Ok((Some(key), value)) => Some((key.to_vec(), value.to_vec())),
+                Ok((None, _)) | Err(LMDBError::NotFound) => None,
+                Err(err) => { return Err(Error::from(err).into()) }
+            }
+        };
+        let Some((key, value)) = head else {
+            return Ok(None);
+        };
+        txn.del(*self.pending, &key, None).map_err(Error::from)?;
+        txn.put(*self.in_flight, &key, &value, WriteFlags::empty()).map_err(Error::from)?;
+        txn.commit().map_err(Error::from)?;
+        Ok(Some((ReservationToken(decode_sequence(&key)?), value)))
+    }
+
+    /// Acknowledge a reserved item, deleting it permanently.
+    pub fn ack(&self, token: ReservationToken) -> Result<()> {
+        let mut txn = self.env.begin_rw_txn().map_err(Error::from)?;
+        match txn.del(*self.in_flight, &token.0.to_be_bytes(), None) {
+            Ok(()) => {}
+            Err(LMDBError::NotFound) => { return Err(unknown_reservation(token).into()) }
+            Err(err) => { return Err(Error::from(err).into()) }
+        }
+        txn.commit().map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Return a reserved item to the queue so that it is delivered again.
+    ///
+    /// The item keeps its original sequence number, so it goes back to its original place in
+    /// the queue instead of the end.
+    pub fn nack(&self, token: ReservationToken) -> Result<()> {
+        let mut txn = self.env.begin_rw_txn().map_err(Error::from)?;
+        self.requeue(&mut txn, &token.0.to_be_bytes())
+            .map_err(|err| match err {
+                LMDBError::NotFound => unknown_reservation(token),
+                err => Error::from(err),
+            })?;
+        txn.commit().map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Return every in-flight item to the queue and return how many were returned.
+    ///
+    /// This is meant to be called at startup, to deliver items that were reserved but never
+    /// acknowledged before the process stopped.
+    pub fn requeue_in_flight(&self) -> Result<usize> {
+        let mut txn = self.env.begin_rw_txn().map_err(Error::from)?;
+        let keys = {
+            let mut cursor = txn.open_ro_cursor(*self.in_flight).map_err(Error::from)?;
+            cursor.iter_start()
+                .map(|item| item.map(|(key, _)| key.to_vec()))
+                .collect::<lmdb::Result<Vec<_>>>()
+                .map_err(Error::from)?
+        };
+        for key in &keys {
+            self.requeue(&mut txn, key).map_err(Error::from)?;
+        }
+        txn.commit().map_err(Error::from)?;
+        Ok(keys.len())
+    }
+
+    /// Get the number of items waiting in the queue (not counting in-flight items).
+    pub fn len(&self) -> Result<usize> {
+        let txn = self.env.begin_ro_txn().map_err(Error::from)?;
+        Ok(txn.stat(*self.pending).map_err(Error::from)?.entries())
+    }
+
+    /// Returns `true` if there are no items waiting in the queue.
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Get the number of items that have been reserved but not acknowledged.
+    pub fn in_flight_len(&self) -> Result<usize> {
+        let txn = self.env.begin_ro_txn().map_err(Error::from)?;
+        Ok(txn.stat(*self.in_flight).map_err(Error::from)?.entries())
+    }
+
+    /// Move one in-flight item back to the pending sub-database.
+    fn requeue(&self, txn: &mut RwTransaction, key: &[u8]) -> lmdb::Result<()> {
+        let value = txn.get(*self.in_flight, &key)?.to_vec();
+        txn.put(*self.pending, &key, &value, WriteFlags::empty())?;
+        txn.del(*self.in_flight, &key, None)
+    }
+}
+
+/// Find the largest sequence number in a sub-database.
+fn last_sequence<T: Transaction>(txn: &T, db: Database) -> anyhow::Result<Option<u64>> {
+    let cursor = txn.open_ro_cursor(db)?;
+    match cursor.get(None, None, MDB_LAST) {
+        Ok((Some(key), _)) => Ok(Some(decode_sequence(key)?)),
+        Ok((None, _)) | Err(LMDBError::NotFound) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Decode a big-endian sequence number key.
+fn decode_sequence(key: &[u8]) -> anyhow::Result<u64> {
+    let buf: [u8; 8] = key.try_into().map_err(|_| anyhow!("invalid queue key length {}", key.len()))?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+/// The error returned when a reservation token isn't in flight.
+fn unknown_reservation(token: ReservationToken) -> Error {
+    anyhow!("no in-flight item for reservation {}", token.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_temp_queue() -> (tempfile::TempDir, Queue) {
+        let temp_dir = db_map_test::temp_dir();
+        let queue = Queue::open(temp_dir.path(), Some("queue"), LMDBArgs::default()).unwrap();
+        (temp_dir, queue)
+    }
+
+    #[test]
+    fn push_reserve_ack_in_order() {
+        let (_temp_dir, queue) = open_temp_queue();
+        for i in 0..10_u8 {
+            assert_eq!(queue.push([i]).unwrap(), i as u64);
+        }
+        assert_eq!(queue.len().unwrap(), 10);
+        for i in 0..10_u8 {
+            let (token, item) = queue.reserve().unwrap().unwrap();
+            assert_eq!(token.sequence(), i as u64);
+            assert_eq!(item, vec![i]);
+            assert_eq!(queue.in_flight_len().unwrap(), 1);
+            queue.ack(token).unwrap();
+            assert_eq!(queue.in_flight_len().unwrap(), 0);
+        }
+        assert!(queue.is_empty().unwrap());
+        assert!(queue.reserve().unwrap().is_none());
+    }
+
+    #[test]
+    fn nack_redelivers_item() {
+        let (_temp_dir, queue) = open_temp_queue();
+        queue.push(b"a").unwrap();
+        queue.push(b"b").unwrap();
+        let (token_a, item) = queue.reserve().unwrap().unwrap();
+        assert_eq!(item, b"a");
+        let (token_b, item) = queue.reserve().unwrap().unwrap();
+        assert_eq!(item, b"b");
+        queue.nack(token_a).unwrap();
+        assert_eq!(queue.len().unwrap(), 1);
+        let (token, item) = queue.reserve().unwrap().unwrap();
+        assert_eq!(token, token_a);
+        assert_eq!(item, b"a");
+        queue.ack(token).unwrap();
+        queue.ack(token_b).unwrap();
+        assert!(queue.ack(token_b).is_err());
+        assert!(queue.nack(token_b).is_err());
+    }
+
+    #[test]
+    fn push_never_reuses_in_flight_sequence() {
+        let (_temp_dir, queue) = open_temp_queue();
+        queue.push(b"a").unwrap();
+        let (token, _) = queue.reserve().unwrap().unwrap();
+        assert_eq!(queue.push(b"b").unwrap(), token.sequence() + 1);
+        assert_eq!(queue.requeue_in_flight().unwrap(), 1);
+        assert_eq!(queue.reserve().unwrap().unwrap().1, b"a");
+    }
+
+    #[test]
+    fn push_never_reuses_sequence_after_drain() {
+        let (temp_dir, queue) = open_temp_queue();
+        queue.push(b"a").unwrap();
+        let (stale, _) = queue.reserve().unwrap().unwrap();
+        queue.ack(stale).unwrap();
+        assert!(queue.is_empty().unwrap());
+
+        // The drained queue still moves on, so the old token can't touch the new item.
+        assert_eq!(queue.push(b"b").unwrap(), stale.sequence() + 1);
+        let (token, _) = queue.reserve().unwrap().unwrap();
+        assert!(queue.ack(stale).is_err());
+        queue.ack(token).unwrap();
+
+        // The counter is stored, so it survives reopening the queue.
+        drop(queue);
+        let queue = Queue::open(temp_dir.path(), Some("queue"), LMDBArgs::default()).unwrap();
+        assert_eq!(queue.push(b"c").unwrap(), token.sequence() + 1);
+    }
+}
@@ -0,0 +1,233 @@
+//! # Sets of fixed-size members stored in LMDB.
+//!
+//! [`LMDBSet`] stores a set of members under each group key. A group's value is its members
+//! concatenated in sorted order, so membership is a binary search over the stored bytes and the
+//! members come back sorted without any extra work.
+
+use db_map_trait::{
+    DBMap,
+    Error,
+    Result,
+};
+use std::{
+    cmp::Ordering,
+    path::Path,
+};
+use super::{LMDBArgs, LMDB};
+
+/// Sets of fixed-size members, one set per group key, stored in an LMDB database.
+///
+/// Every member of every set is exactly `member_size` bytes. The members of a group are stored as
+/// one value: their bytes concatenated in sorted order. Each change to a group is made with
+/// [`update`][DBMap::update] in a single write transaction, so concurrent changes to the same
+/// group never lose each other's members. A group with no members has no entry in the database.
+///
+/// Since every change rewrites the whole group, this suits sets of up to a few thousand members.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_lmdb::{LMDBArgs, LMDBSet};
+/// use tempfile;
+///
+/// let temp_dir = tempfile::Builder::new()
+///     .prefix("lmdb_test_dir_")
+///     .rand_bytes(5)
+///     .tempdir()
+///     .unwrap();
+/// let set = LMDBSet::open(temp_dir.path(), None, LMDBArgs::default(), 4).unwrap();
+///
+/// assert!(set.add(b"admins", 42_u32.to_be_bytes()).unwrap());
+/// assert!(set.add(b"admins", 7_u32.to_be_bytes()).unwrap());
+/// // Adding a member again changes nothing.
+/// assert!(!set.add(b"admins", 42_u32.to_be_bytes()).unwrap());
+///
+/// assert!(set.contains(b"admins", 7_u32.to_be_bytes()).unwrap());
+/// assert!(!set.contains(b"users", 7_u32.to_be_bytes()).unwrap());
+/// assert_eq!(set.members(b"admins").unwrap(), vec![
+///     7_u32.to_be_bytes().to_vec(),
+///     42_u32.to_be_bytes().to_vec(),
+/// ]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LMDBSet {
+    db: LMDB,
+    member_size: usize,
+}
+
+impl LMDBSet {
+    /// Open an `LMDBSet` whose members are all `member_size` bytes.
+    ///
+    /// The sets are stored in the database `db_name` (or the main database) of the environment,
+    /// which should not be used for anything else.
+    pub fn open(env_path: &Path, db_name: Option<&str>, lmdb_args: LMDBArgs, member_size: usize) -> Result<LMDBSet> {
+        if member_size == 0 {
+            return Err(Error::Validation("set members can't be empty".to_string()));
+        }
+        Ok(Self {
+            db: LMDB::open(env_path, db_name, lmdb_args)?,
+            member_size,
+        })
+    }
+
+    /// Get the size of every member.
+    pub fn member_size(&self) -> usize {
+        self.member_size
+    }
+
+    /// Add a member to a group, returning `true` if it wasn't already there.
+    pub fn add<G: AsRef<[u8]>, V: AsRef<[u8]>>(&self, group: G, member: V) -> Result<bool> {
+        let member = self.check_member(member.as_ref())?;
+        let mut added = false;
+        self.db.update(group, |members| {
+            let members = members.unwrap_or_default();
+            match self.search(members, member) {
+                Ok(_) => Some(members.to_vec()),
+                Err(index) => {
+                    added = true;
+                    let at = index * self.member_size;
+                    Some([&members[..at], member, &members[at..]].concat())
+                }
+            }
+        })?;
+        Ok(added)
+    }
+
+    /// Remove a member from a group, returning `true` if it was there.
+    ///
+    /// Removing the last member removes the group.
+    pub fn remove<G: AsRef<[u8]>, V: AsRef<[u8]>>(&self, group: G, member: V) -> Result<bool> {
+        let member = self.check_member(member.as_ref())?;
+        let mut removed = false;
+        self.db.update(group, |members| {
+            let members = members?;
+            match self.search(members, member) {
+                Ok(index) => {
+                    removed = true;
+                    let at = index * self.member_size;
+                    let rest = [&members[..at], &members[at + self.member_size..]].concat();
+                    (!rest.is_empty()).then_some(rest)
+                }
+                Err(_) => Some(members.to_vec()),
+            }
+        })?;
+        Ok(removed)
+    }
+
+    /// Check whether a group has a member.
+    pub fn contains<G: AsRef<[u8]>, V: AsRef<[u8]>>(&self, group: G, member: V) -> Result<bool> {
+        let member = self.check_member(member.as_ref())?;
+        Ok(self.db.get_map(group, |members| self.search(members, member).is_ok())?.unwrap_or(false))
+    }
+
+    /// Get every member of a group, in sorted order.
+    pub fn members<G: AsRef<[u8]>>(&self, group: G) -> Result<Vec<Vec<u8>>> {
+        Ok(self.db.get_map(group, |members| {
+            members.chunks_exact(self.member_size).map(<[u8]>::to_vec).collect()
+        })?.unwrap_or_default())
+    }
+
+    /// Get the number of members in a group.
+    pub fn len<G: AsRef<[u8]>>(&self, group: G) -> Result<usize> {
+        Ok(self.db.get_map(group, |members| members.len() / self.member_size)?.unwrap_or(0))
+    }
+
+    /// Return `true` if a group has no members.
+    pub fn is_empty<G: AsRef<[u8]>>(&self, group: G) -> Result<bool> {
+        Ok(self.len(group)? == 0)
+    }
+
+    /// Return a [`Validation`][Error::Validation] error if a member is the wrong size.
+    fn check_member<'a>(&self, member: &'a [u8]) -> Result<&'a [u8]> {
+        match member.len() == self.member_size {
+            true => Ok(member),
+            false => Err(Error::Validation(format!(
+                "set members must be {} bytes, not {}", self.member_size, member.len(),
+            ))),
+        }
+    }
+
+    /// Binary search the sorted members of a group, like [`slice::binary_search`].
+    fn search(&self, members: &[u8], member: &[u8]) -> std::result::Result<usize, usize> {
+        let (mut low, mut high) = (0, members.len() / self.member_size);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let at = mid * self.member_size;
+            match members[at..at + self.member_size].cmp(member) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_temp_set(member_size: usize) -> (tempfile::TempDir, LMDBSet) {
+        let temp_dir = db_map_test::temp_dir();
+        let set = LMDBSet::open(temp_dir.path(), None, LMDBArgs::default(), member_size).unwrap();
+        (temp_dir, set)
+    }
+
+    #[test]
+    fn add_and_remove_are_idempotent() {
+        let (_temp_dir, set) = open_temp_set(2);
+        assert!(set.add(b"group", b"ab").unwrap());
+        assert!(!set.add(b"group", b"ab").unwrap());
+        assert_eq!(set.len(b"group").unwrap(), 1);
+
+        assert!(set.remove(b"group", b"ab").unwrap());
+        assert!(!set.remove(b"group", b"ab").unwrap());
+        assert!(set.is_empty(b"group").unwrap());
+        // Removing the last member removes the group, and removing from a missing group is fine.
+        assert!(set.db.get(b"group").unwrap().is_none());
+        assert!(!set.remove(b"missing", b"ab").unwrap());
+    }
+
+    #[test]
+    fn membership_is_per_group() {
+        let (_temp_dir, set) = open_temp_set(1);
+        for member in [b"a", b"c", b"e"] {
+            set.add(b"odd", member).unwrap();
+        }
+        set.add(b"even", b"b").unwrap();
+        for member in [b"a", b"c", b"e"] {
+            assert!(set.contains(b"odd", member).unwrap());
+            assert!(!set.contains(b"even", member).unwrap());
+        }
+        for member in [b"b", b"d", b"0", b"z"] {
+            assert!(!set.contains(b"odd", member).unwrap());
+        }
+        assert!(set.contains(b"even", b"b").unwrap());
+        assert!(!set.contains(b"none", b"b").unwrap());
+    }
+
+    #[test]
+    fn members_are_sorted() {
+        let (_temp_dir, set) = open_temp_set(4);
+        let values = [500_u32, 3, 70_000, 42, 3, 1 << 31, 0, 42];
+        for value in values {
+            set.add(b"group", value.to_be_bytes()).unwrap();
+        }
+        let mut expected: Vec<_> = values.iter().map(|v| v.to_be_bytes().to_vec()).collect();
+        expected.sort();
+        expected.dedup();
+        assert_eq!(set.members(b"group").unwrap(), expected);
+
+        set.remove(b"group", 42_u32.to_be_bytes()).unwrap();
+        expected.retain(|member| member != &42_u32.to_be_bytes());
+        assert_eq!(set.members(b"group").unwrap(), expected);
+    }
+
+    #[test]
+    fn wrong_sized_members_are_rejected() {
+        let (_temp_dir, set) = open_temp_set(4);
+        assert!(matches!(set.add(b"group", b"abc"), Err(Error::Validation(_))));
+        assert!(matches!(set.contains(b"group", b"abcde"), Err(Error::Validation(_))));
+        assert!(set.members(b"group").unwrap().is_empty());
+    }
+}
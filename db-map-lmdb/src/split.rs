@@ -0,0 +1,581 @@
+//! # Content-addressed LMDB storage with keys and values in separate sub-databases.
+//!
+//! [`LMDBSplit`] keeps the key b-tree small by storing only a 32-byte SHA-256 hash of each value
+//! next to the key. The values themselves live in a second sub-database keyed by that hash, so
+//! identical values are only stored once no matter how many keys point at them.
+
+use anyhow::{anyhow, Error};
+use db_map_trait::{
+    move_key,
+    Capabilities,
+    DBMap,
+    HexKey,
+    Result,
+};
+use lmdb::{
+    Cursor,
+    Error as LMDBError,
+    RwTransaction,
+    Transaction,
+    WriteFlags,
+};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use super::{mark_transient, LMDBArgs, LMDB};
+
+/// Deduplication statistics for an [`LMDBSplit`] database, from [`LMDBSplit::dedup_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// The number of keys in the database.
+    pub logical_keys: usize,
+    /// The number of distinct values actually stored.
+    pub unique_values: usize,
+    /// The number of value bytes that didn't have to be stored because keys share values.
+    ///
+    /// Every key beyond the first that points at a value saves that value's length. The 32-byte
+    /// hash stored with each key and the reference counts are not subtracted.
+    pub bytes_saved: u64,
+}
+
+/// An LMDB database that stores keys and values in separate sub-databases, deduplicating values.
+///
+/// Three named sub-databases are created in the environment: `<name>.keys`, which maps each key
+/// to the SHA-256 hash of its value, `<name>.values`, which maps each hash to the value itself,
+/// and `<name>.refcounts`, which maps each hash to the number of keys that point at it. When no
+/// name is given, `keys`, `values` and `refcounts` are used. At least three named databases must
+/// be allowed in the environment, so `max_dbs` is raised to 3 if it is lower.
+///
+/// Each sub-database is an [`LMDB`] handle on the same environment, so read-only handles,
+/// [`value_checksum`][LMDBArgs::value_checksum], and growing the map work as they do for `LMDB`.
+/// Keys are always compared as bytes: [`integer_keys`][LMDBArgs::integer_keys] and
+/// [`reverse_key`][LMDBArgs::reverse_key] are ignored.
+///
+/// # Garbage collection
+///
+/// Every stored value has a reference count of the keys that point at it, kept under its hash in
+/// the reference count sub-database. Inserting, replacing, and removing keys adjust the count in
+/// the same transaction as the key change, without rewriting the value, and a value is deleted as
+/// soon as its count drops to zero. No orphaned values are left behind.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMap;
+/// use db_map_lmdb::{LMDBArgs, LMDBSplit};
+/// use tempfile;
+///
+/// let temp_dir = tempfile::Builder::new()
+///     .prefix("lmdb_test_dir_")
+///     .rand_bytes(5)
+///     .tempdir()
+///     .unwrap();
+/// let db = LMDBSplit::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+///
+/// // Both keys share the same stored value.
+/// let big_value = vec![0xAB_u8; 64 * 1024];
+/// db.insert(b"key1", &big_value).unwrap();
+/// db.insert(b"key2", &big_value).unwrap();
+///
+/// assert_eq!(db.get(b"key1").unwrap(), Some(big_value.clone()));
+/// assert_eq!(db.get(b"key2").unwrap(), Some(big_value));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LMDBSplit {
+    keys: LMDB,
+    values: LMDB,
+    refcounts: LMDB,
+}
+
+impl LMDBSplit {
+    /// Open an `LMDBSplit` database for use with the [`DBMap`] trait.
+    pub fn open(env_path: &Path, db_name: Option<&str>, lmdb_args: LMDBArgs) -> Result<LMDBSplit> {
+        let lmdb_args = LMDBArgs {
+            max_dbs: Some(lmdb_args.max_dbs.unwrap_or(0).max(3)),
+            integer_keys: false,
+            reverse_key: false,
+            ..lmdb_args
+        };
+        let (keys_name, values_name, refcounts_name) = match db_name {
+            None => ("keys".to_string(), "values".to_string(), "refcounts".to_string()),
+            Some(name) => (format!("{name}.keys"), format!("{name}.values"), format!("{name}.refcounts")),
+        };
+        let keys = LMDB::open(env_path, Some(&keys_name), lmdb_args)?;
+        Ok(Self {
+            values: keys.open_db(Some(&values_name))?,
+            refcounts: keys.open_db(Some(&refcounts_name))?,
+            keys,
+        })
+    }
+
+    /// Measure how much storage the content addressing saves.
+    ///
+    /// The key count comes from the statistics of the keys sub-database, and the reference
+    /// counts are scanned to add up the savings, all in one read transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_lmdb::{DedupStats, LMDBArgs, LMDBSplit};
+    /// use tempfile;
+    ///
+    /// let temp_dir = tempfile::Builder::new()
+    ///     .prefix("lmdb_test_dir_")
+    ///     .rand_bytes(5)
+    ///     .tempdir()
+    ///     .unwrap();
+    /// let db = LMDBSplit::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+    ///
+    /// db.insert(b"key1", [0_u8; 100]).unwrap();
+    /// db.insert(b"key2", [0_u8; 100]).unwrap();
+    /// db.insert(b"key3", [0_u8; 100]).unwrap();
+    /// db.insert(b"key4", b"unique").unwrap();
+    ///
+    /// assert_eq!(db.dedup_stats().unwrap(), DedupStats {
+    ///     logical_keys: 4,
+    ///     unique_values: 2,
+    ///     bytes_saved: 200,
+    /// });
+    /// ```
+    pub fn dedup_stats(&self) -> Result<DedupStats> {
+        let txn = self.keys.begin_ro_txn().map_err(Error::from)?;
+        let mut stats = DedupStats {
+            logical_keys: txn.stat(*self.keys.db).map_err(Error::from)?.entries(),
+            ..Default::default()
+        };
+        let mut cursor = txn.open_ro_cursor(*self.refcounts.db).map_err(Error::from)?;
+        for item in cursor.iter_start() {
+            let (hash, stored) = item.map_err(Error::from)?;
+            let count = self.read_refcount(hash, stored)?;
+            let value_len = self.stored_value(&*txn, hash)?.len() as u64;
+            stats.unique_values += 1;
+            stats.bytes_saved += count.saturating_sub(1) * value_len;
+        }
+        Ok(stats)
+    }
+
+    /// Calculate the content hash used to store a value.
+    fn hash(value: &[u8]) -> [u8; 32] {
+        Sha256::digest(value).into()
+    }
+
+    /// Look up the stored value for a hash.
+    fn stored_value<'txn, T: Transaction>(&self, txn: &'txn T, hash: &[u8]) -> Result<&'txn [u8]> {
+        get(&self.values, txn, hash)?.ok_or_else(|| db_map_trait::Error::Corruption(format!(
+            "missing value for content hash {}", HexKey(hash),
+        )))
+    }
+
+    /// Decode a stored reference count.
+    fn read_refcount(&self, hash: &[u8], stored: &[u8]) -> Result<u64> {
+        let stored = self.refcounts.open_value(hash, stored)?;
+        let count = stored.try_into().map_err(|_| db_map_trait::Error::Corruption(format!(
+            "the reference count for content hash {} is {} bytes, not 8", HexKey(hash), stored.len(),
+        )))?;
+        Ok(u64::from_be_bytes(count))
+    }
+
+    /// Look up the reference count for a hash, which is zero if the value isn't stored.
+    fn refcount(&self, txn: &RwTransaction, hash: &[u8]) -> Result<u64> {
+        match txn.get(*self.refcounts.db, &hash) {
+            Ok(stored) => self.read_refcount(hash, stored),
+            Err(LMDBError::NotFound) => Ok(0),
+            Err(err) => Err(Error::from(err).into()),
+        }
+    }
+
+    /// Add a reference to a value, storing it if this is the first reference.
+    ///
+    /// Only the reference count is written when the value is already stored.
+    fn add_ref(&self, txn: &mut RwTransaction, hash: &[u8], value: &[u8]) -> Result<()> {
+        let count = self.refcount(txn, hash)?;
+        if count == 0 {
+            put(&self.values, txn, hash, value)?;
+        }
+        let count = count.checked_add(1)
+            .ok_or_else(|| anyhow!("the reference count for content hash {} overflowed", HexKey(hash)))?;
+        put(&self.refcounts, txn, hash, &count.to_be_bytes())
+    }
+
+    /// Drop a reference to a value, deleting it when no references remain.
+    fn release_ref(&self, txn: &mut RwTransaction, hash: &[u8]) -> Result<()> {
+        match self.refcount(txn, hash)? {
+            0 => {}
+            1 => {
+                del(&self.refcounts, txn, hash)?;
+                del(&self.values, txn, hash)?;
+            }
+            count => put(&self.refcounts, txn, hash, &(count - 1).to_be_bytes())?,
+        }
+        Ok(())
+    }
+
+    /// Get the hash a key points at inside a transaction.
+    fn key_hash(&self, txn: &RwTransaction, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(get(&self.keys, txn, key)?.map(<[u8]>::to_vec))
+    }
+
+    /// Remove a key inside a write transaction, releasing its value.
+    fn remove_inner(&self, txn: &mut RwTransaction, key: &[u8]) -> Result<()> {
+        if let Some(hash) = self.key_hash(txn, key)? {
+            self.release_ref(txn, &hash)?;
+            del(&self.keys, txn, key)?;
+        }
+        Ok(())
+    }
+
+    /// Point a key at a new value inside a write transaction and return the old value's hash.
+    fn replace_inner(&self, txn: &mut RwTransaction, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let hash = Self::hash(value);
+        let old_hash = self.key_hash(txn, key)?;
+        if old_hash.as_deref() != Some(&hash[..]) {
+            self.add_ref(txn, &hash, value)?;
+            if let Some(old_hash) = &old_hash {
+                self.release_ref(txn, old_hash)?;
+            }
+            put(&self.keys, txn, key, &hash)?;
+        }
+        Ok(old_hash)
+    }
+}
+
+/// Get a value from one of the sub-databases, checking its checksum if the handle has them.
+fn get<'txn, T: Transaction>(db: &LMDB, txn: &'txn T, key: &[u8]) -> Result<Option<&'txn [u8]>> {
+    match txn.get(*db.db, &key) {
+        Ok(stored) => Ok(Some(db.open_value(key, stored)?)),
+        Err(LMDBError::NotFound) => Ok(None),
+        Err(err) => Err(Error::from(err).into()),
+    }
+}
+
+/// Store a value in one of the sub-databases, adding a checksum if the handle has them.
+fn put(db: &LMDB, txn: &mut RwTransaction, key: &[u8], value: &[u8]) -> Result<()> {
+    mark_transient(txn.put(*db.db, &key, &db.seal_value(value), WriteFlags::empty())
+        .map_err(|err| Error::from(err).into()))
+}
+
+/// Delete a key from one of the sub-databases.
+fn del(db: &LMDB, txn: &mut RwTransaction, key: &[u8]) -> Result<()> {
+    txn.del(*db.db, &key, None).map_err(|err| Error::from(err).into())
+}
+
+impl DBMap for LMDBSplit {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T
+    {
+        self.keys.check_key(key.as_ref())?;
+        let txn = self.keys.begin_ro_txn().map_err(Error::from)?;
+        match get(&self.keys, &*txn, key.as_ref())? {
+            Some(hash) => Ok(Some(mapper(self.stored_value(&*txn, hash)?))),
+            None => Ok(None),
+        }
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        self.keys.check_writable()?;
+        self.keys.check_key(key.as_ref())?;
+        let mut txn = self.keys.begin_rw_txn().map_err(Error::from)?;
+        self.replace_inner(&mut txn, key.as_ref(), value.as_ref())?;
+        mark_transient(txn.commit().map_err(|err| Error::from(err).into()))
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T
+    {
+        self.keys.check_writable()?;
+        self.keys.check_key(key.as_ref())?;
+        let mut txn = self.keys.begin_rw_txn().map_err(Error::from)?;
+        let result = match get(&self.keys, &*txn, key.as_ref())? {
+            Some(hash) => Some(mapper(self.stored_value(&*txn, hash)?)),
+            None => None,
+        };
+        self.replace_inner(&mut txn, key.as_ref(), value.as_ref())?;
+        mark_transient(txn.commit().map_err(|err| Error::from(err).into()))?;
+        Ok(result)
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        self.keys.check_writable()?;
+        self.keys.check_key(key.as_ref())?;
+        let mut txn = self.keys.begin_rw_txn().map_err(Error::from)?;
+        self.remove_inner(&mut txn, key.as_ref())?;
+        mark_transient(txn.commit().map_err(|err| Error::from(err).into()))
+    }
+
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        self.keys.check_writable()?;
+        self.keys.check_key(key.as_ref())?;
+        let mut txn = self.keys.begin_rw_txn().map_err(Error::from)?;
+        let new_value = match get(&self.keys, &*txn, key.as_ref())? {
+            Some(hash) => f(Some(self.stored_value(&*txn, hash)?)),
+            None => f(None),
+        };
+        match new_value {
+            Some(value) => { self.replace_inner(&mut txn, key.as_ref(), &value)?; }
+            None => self.remove_inner(&mut txn, key.as_ref())?,
+        }
+        mark_transient(txn.commit().map_err(|err| Error::from(err).into()))
+    }
+
+    /// Insert all of the pairs in a single write transaction.
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        self.keys.check_writable()?;
+        let mut txn = self.keys.begin_rw_txn().map_err(Error::from)?;
+        for (key, value) in items {
+            self.keys.check_key(key.as_ref())?;
+            self.replace_inner(&mut txn, key.as_ref(), value.as_ref())?;
+        }
+        mark_transient(txn.commit().map_err(|err| Error::from(err).into()))
+    }
+
+    /// Move the entries in a single write transaction.
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        self.keys.check_writable()?;
+        let (from_prefix, to_prefix) = (from_prefix.as_ref(), to_prefix.as_ref());
+        let mut txn = self.keys.begin_rw_txn().map_err(Error::from)?;
+        let mut entries = Vec::new();
+        {
+            let mut cursor = txn.open_ro_cursor(*self.keys.db).map_err(Error::from)?;
+            let iter = match from_prefix.is_empty() {
+                true => cursor.iter_start(),
+                false => cursor.iter_from(from_prefix),
+            };
+            for item in iter {
+                let (key, stored) = item.map_err(Error::from)?;
+                let Some(new_key) = move_key(key, from_prefix, to_prefix) else {
+                    break;
+                };
+                let hash = self.keys.open_value(key, stored)?;
+                let value = self.stored_value(&*txn, hash)?.to_vec();
+                entries.push((key.to_vec(), new_key, value));
+            }
+        }
+        for (_, new_key, _) in &entries {
+            self.keys.check_key(new_key)?;
+        }
+        for (key, _, _) in &entries {
+            self.remove_inner(&mut txn, key)?;
+        }
+        for (_, new_key, value) in &entries {
+            self.replace_inner(&mut txn, new_key, value)?;
+        }
+        mark_transient(txn.commit().map_err(|err| Error::from(err).into()))?;
+        Ok(entries.len())
+    }
+
+    fn try_for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let txn = self.keys.begin_ro_txn().map_err(Error::from)?;
+        let mut cursor = txn.open_ro_cursor(*self.keys.db).map_err(Error::from)?;
+        for item in cursor.iter_start() {
+            let (key, stored) = item.map_err(Error::from)?;
+            let hash = self.keys.open_value(key, stored)?;
+            f(key, self.stored_value(&*txn, hash)?)?;
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> Result<usize> {
+        self.keys.len()
+    }
+
+    /// Clear the keys and the values they share in one write transaction.
+    fn clear(&self) -> Result<()> {
+        self.keys.check_writable()?;
+        let mut txn = self.keys.begin_rw_txn().map_err(Error::from)?;
+        for db in [&self.keys, &self.values, &self.refcounts] {
+            txn.clear_db(*db.db).map_err(Error::from)?;
+        }
+        mark_transient(txn.commit().map_err(|err| Error::from(err).into()))
+    }
+
+    /// Keys and values are both written in one transaction, so this reports the same as
+    /// [`LMDB`].
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            ordered_iteration: true,
+            atomic_batch: true,
+            persistent: true,
+            snapshots: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use db_map_test::impl_db_map_tests;
+
+    fn open_temp_lmdb_split(db_name: Option<&str>) -> (tempfile::TempDir, LMDBSplit) {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDBSplit::open(temp_dir.path(), db_name, LMDBArgs::default()).unwrap();
+        (temp_dir, db)
+    }
+
+    /// Count the distinct values actually stored.
+    fn stored_values(db: &LMDBSplit) -> usize {
+        db.values.len().unwrap()
+    }
+
+    impl_db_map_tests! {
+        let (_temp_dir, db) = open_temp_lmdb_split(Some("split"));
+    }
+
+    #[test]
+    fn capabilities_report_everything() {
+        let (_temp_dir, db) = open_temp_lmdb_split(None);
+        assert_eq!(db.capabilities(), Capabilities {
+            ordered_iteration: true,
+            atomic_batch: true,
+            persistent: true,
+            snapshots: true,
+        });
+    }
+
+    #[test]
+    fn duplicate_values_share_storage() {
+        let (_temp_dir, db) = open_temp_lmdb_split(None);
+        let shared = vec![0x5A_u8; 4096];
+        db.insert(b"key1", &shared).unwrap();
+        db.insert(b"key2", &shared).unwrap();
+        db.insert(b"key3", b"unique").unwrap();
+        assert_eq!(db.len().unwrap(), 3);
+        assert_eq!(stored_values(&db), 2);
+        assert_eq!(db.get(b"key1").unwrap(), Some(shared.clone()));
+        assert_eq!(db.get(b"key2").unwrap(), Some(shared.clone()));
+        assert_eq!(db.get(b"key3").unwrap(), Some(b"unique".to_vec()));
+
+        // Removing one of the sharing keys keeps the value for the other.
+        db.remove(b"key1").unwrap();
+        assert!(db.get(b"key1").unwrap().is_none());
+        assert_eq!(db.get(b"key2").unwrap(), Some(shared.clone()));
+        assert_eq!(stored_values(&db), 2);
+
+        // Once the last reference is gone, the value is deleted.
+        assert_eq!(db.fetch_and_replace(b"key2", b"unique").unwrap(), Some(shared));
+        assert_eq!(stored_values(&db), 1);
+        db.remove(b"key2").unwrap();
+        db.remove(b"key3").unwrap();
+        assert_eq!(stored_values(&db), 0);
+        assert!(db.is_empty().unwrap());
+    }
+
+    #[test]
+    fn dedup_stats_counts_shared_values() {
+        let (_temp_dir, db) = open_temp_lmdb_split(None);
+        assert_eq!(db.dedup_stats().unwrap(), DedupStats::default());
+
+        let shared = vec![0x5A_u8; 1000];
+        for key in ["a", "b", "c"] {
+            db.insert(key, &shared).unwrap();
+        }
+        db.insert("d", [1_u8; 10]).unwrap();
+        db.insert("e", [1_u8; 10]).unwrap();
+        db.insert("f", [2_u8; 50]).unwrap();
+        assert_eq!(db.dedup_stats().unwrap(), DedupStats {
+            logical_keys: 6,
+            unique_values: 3,
+            bytes_saved: 2 * 1000 + 10,
+        });
+
+        // Moving a key off a shared value reduces the savings.
+        db.insert("a", b"now unique").unwrap();
+        assert_eq!(db.dedup_stats().unwrap(), DedupStats {
+            logical_keys: 6,
+            unique_values: 4,
+            bytes_saved: 1000 + 10,
+        });
+    }
+
+    #[test]
+    fn reinsert_same_value_keeps_refcount() {
+        let (_temp_dir, db) = open_temp_lmdb_split(None);
+        db.insert(b"key", b"value").unwrap();
+        db.insert(b"key", b"value").unwrap();
+        db.remove(b"key").unwrap();
+        assert_eq!(stored_values(&db), 0);
+    }
+
+    #[test]
+    fn refcounts_are_kept_under_their_own_key() {
+        let (_temp_dir, db) = open_temp_lmdb_split(None);
+        let hash = LMDBSplit::hash(b"shared");
+        db.insert(b"key1", b"shared").unwrap();
+        db.insert(b"key2", b"shared").unwrap();
+        assert_eq!(db.refcounts.get(hash).unwrap(), Some(2_u64.to_be_bytes().to_vec()));
+        assert_eq!(db.values.get(hash).unwrap(), Some(b"shared".to_vec()));
+
+        db.remove(b"key1").unwrap();
+        assert_eq!(db.refcounts.get(hash).unwrap(), Some(1_u64.to_be_bytes().to_vec()));
+        db.remove(b"key2").unwrap();
+        assert!(db.refcounts.get(hash).unwrap().is_none());
+        assert!(db.values.get(hash).unwrap().is_none());
+    }
+
+    #[test]
+    fn damaged_refcount_is_an_error() {
+        let (_temp_dir, db) = open_temp_lmdb_split(None);
+        let hash = LMDBSplit::hash(b"value");
+        db.insert(b"key", b"value").unwrap();
+        db.refcounts.insert(hash, b"short").unwrap();
+        assert!(matches!(db.dedup_stats(), Err(db_map_trait::Error::Corruption(_))));
+        assert!(matches!(db.remove(b"key"), Err(db_map_trait::Error::Corruption(_))));
+        assert!(matches!(db.insert(b"other", b"value"), Err(db_map_trait::Error::Corruption(_))));
+    }
+
+    #[test]
+    fn value_checksum_covers_every_sub_database() {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDBSplit::open(temp_dir.path(), None, LMDBArgs {
+            value_checksum: true,
+            ..Default::default()
+        }).unwrap();
+        db.insert(b"key", b"value").unwrap();
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(db.dedup_stats().unwrap().unique_values, 1);
+
+        // Damage the stored value behind the checksum's back.
+        let hash = LMDBSplit::hash(b"value");
+        let mut txn = db.keys.env.begin_rw_txn().unwrap();
+        txn.put(*db.values.db, &hash, b"\x01\0\0\0\0value", WriteFlags::empty()).unwrap();
+        txn.commit().unwrap();
+        assert!(matches!(db.get(b"key"), Err(db_map_trait::Error::Corruption(_))));
+    }
+
+    #[test]
+    fn read_only_handle_rejects_writes() {
+        let temp_dir = db_map_test::temp_dir();
+        let db = LMDBSplit::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+        db.insert(b"key", b"value").unwrap();
+        drop(db);
+
+        let db = LMDBSplit::open(temp_dir.path(), None, LMDBArgs {
+            env_flags: Some(lmdb::EnvironmentFlags::READ_ONLY),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+        assert!(matches!(db.insert(b"key", b"new"), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.remove(b"key"), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.update(b"key", |_| None), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.batch_insert([(b"a", b"b")]), Err(db_map_trait::Error::ReadOnly)));
+        assert!(matches!(db.clear(), Err(db_map_trait::Error::ReadOnly)));
+        assert_eq!(db.dedup_stats().unwrap().logical_keys, 1);
+    }
+}
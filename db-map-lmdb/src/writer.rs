@@ -0,0 +1,219 @@
+//! # Streaming writes in one LMDB transaction.
+//!
+//! [`Writer`] keeps a write transaction open so that any number of writes can be made in it and
+//! then committed together, without collecting them first as
+//! [`batch_insert`][db_map_trait::DBMap::batch_insert] needs. [`MultiWriter`] does the same for
+//! writes to several sub-databases of one environment.
+
+use anyhow::{anyhow, Error};
+use db_map_trait::Result;
+use lmdb::{
+    Error as LMDBError,
+    Transaction,
+    WriteFlags,
+};
+use std::sync::Arc;
+use super::{mark_transient, WriteTxn, LMDB};
+
+/// An open write transaction on an [`LMDB`] database, from [`LMDB::writer`].
+///
+/// This is the fastest way to make many small writes: each `DBMap` write opens and commits its
+/// own transaction, which costs far more than the write itself, while every write through a
+/// `Writer` shares one transaction. Writes are visible through the writer straight away, and to
+/// everyone else only once [`commit`][Writer::commit] succeeds. Dropping the writer without
+/// committing aborts the transaction and discards every write made through it.
+///
+/// LMDB allows one write transaction at a time, so other writers (in this process or another)
+/// wait until this one is committed or dropped. Keep it open only as long as the writes need,
+/// and don't use the database through any other handle on the same thread while it is open.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMap;
+/// use db_map_lmdb::{LMDB, LMDBArgs};
+/// use tempfile;
+///
+/// let temp_dir = tempfile::Builder::new()
+///     .prefix("lmdb_test_dir_")
+///     .rand_bytes(5)
+///     .tempdir()
+///     .unwrap();
+/// let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+///
+/// let mut writer = db.writer().unwrap();
+/// for i in 0_u32..1000 {
+///     writer.insert(i.to_be_bytes(), (i * i).to_be_bytes()).unwrap();
+/// }
+/// assert_eq!(writer.fetch_and_replace(7_u32.to_be_bytes(), b"seven").unwrap(), Some(49_u32.to_be_bytes().to_vec()));
+/// assert!(writer.remove(8_u32.to_be_bytes()).unwrap());
+/// assert!(!writer.remove(8_u32.to_be_bytes()).unwrap());
+/// writer.commit().unwrap();
+///
+/// assert_eq!(db.len().unwrap(), 999);
+/// assert_eq!(db.get(7_u32.to_be_bytes()).unwrap(), Some(b"seven".to_vec()));
+///
+/// // Without a commit, nothing is written.
+/// let mut writer = db.writer().unwrap();
+/// writer.insert(b"discarded", b"value").unwrap();
+/// drop(writer);
+/// assert!(db.get(b"discarded").unwrap().is_none());
+/// ```
+pub struct Writer<'env> {
+    db: &'env LMDB,
+    writer: MultiWriter<'env>,
+}
+
+impl<'env> Writer<'env> {
+    /// Begin a write transaction on `db`.
+    pub(crate) fn new(db: &'env LMDB) -> Result<Self> {
+        Ok(Self { db, writer: MultiWriter::new(db)? })
+    }
+
+    /// Get the data for a key, including writes made through this writer.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>> {
+        self.writer.get(self.db, key)
+    }
+
+    /// Insert data for a key.
+    pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, key: K, value: V) -> Result<()> {
+        self.writer.insert(self.db, key, value)
+    }
+
+    /// Insert data for a key, returning the data it replaced.
+    pub fn fetch_and_replace<K, V>(&mut self, key: K, value: V) -> Result<Option<Vec<u8>>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let old = self.get(&key)?;
+        self.insert(key, value)?;
+        Ok(old)
+    }
+
+    /// Remove a key, returning `true` if it was there.
+    ///
+    /// Unlike [`DBMap::remove`][db_map_trait::DBMap::remove], a missing key isn't an error, so a
+    /// stream of removes doesn't have to check first.
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Result<bool> {
+        self.writer.remove(self.db, key)
+    }
+
+    /// Commit every write made through this writer.
+    ///
+    /// If the commit fails, none of the writes are kept.
+    pub fn commit(self) -> Result<()> {
+        self.writer.commit()
+    }
+}
+
+impl std::fmt::Debug for Writer<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Writer").finish_non_exhaustive()
+    }
+}
+
+/// An open write transaction across the sub-databases of one LMDB environment, from
+/// [`LMDB::multi_writer`].
+///
+/// Each operation names the database it applies to, which can be any [`LMDB`] handle on the same
+/// environment: the handle the writer came from, its clones, and the sub-databases opened from
+/// it with [`LMDB::open_db`]. Everything written is committed together by
+/// [`commit`][MultiWriter::commit], so a record and its index can never be seen out of step, and
+/// dropping the writer without committing discards all of it. A handle on another environment is
+/// rejected with an error, even one opened on the same path.
+///
+/// Like a [`Writer`], this holds the environment's only write transaction until it is committed
+/// or dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMap;
+/// use db_map_lmdb::{LMDB, LMDBArgs};
+/// use tempfile;
+///
+/// let temp_dir = tempfile::Builder::new()
+///     .prefix("lmdb_test_dir_")
+///     .rand_bytes(5)
+///     .tempdir()
+///     .unwrap();
+/// let records = LMDB::open(temp_dir.path(), Some("records"), LMDBArgs {
+///     max_dbs: Some(2),
+///     ..Default::default()
+/// }).unwrap();
+/// let by_email = records.open_db(Some("by_email")).unwrap();
+///
+/// let mut writer = records.multi_writer().unwrap();
+/// writer.insert(&records, b"user/1", b"Ada").unwrap();
+/// writer.insert(&by_email, b"ada@example.com", b"user/1").unwrap();
+/// writer.commit().unwrap();
+///
+/// assert_eq!(records.get(b"user/1").unwrap(), Some(b"Ada".to_vec()));
+/// assert_eq!(by_email.get(b"ada@example.com").unwrap(), Some(b"user/1".to_vec()));
+/// ```
+pub struct MultiWriter<'env> {
+    env_db: &'env LMDB,
+    txn: WriteTxn<'env>,
+}
+
+impl<'env> MultiWriter<'env> {
+    /// Begin a write transaction on the environment of `db`.
+    pub(crate) fn new(db: &'env LMDB) -> Result<Self> {
+        db.check_writable()?;
+        let txn = db.begin_rw_txn().map_err(Error::from)?;
+        Ok(Self { env_db: db, txn })
+    }
+
+    /// Return an error unless `db` is in this writer's environment.
+    fn check_env(&self, db: &LMDB) -> Result<()> {
+        match Arc::ptr_eq(&self.env_db.env, &db.env) {
+            true => Ok(()),
+            false => Err(anyhow!("the database isn't in the writer's environment").into()),
+        }
+    }
+
+    /// Get the data for a key in `db`, including writes made through this writer.
+    pub fn get<K: AsRef<[u8]>>(&self, db: &LMDB, key: K) -> Result<Option<Vec<u8>>> {
+        self.check_env(db)?;
+        db.check_key(key.as_ref())?;
+        match self.txn.get(*db.db, &key) {
+            Ok(value) => Ok(Some(db.open_value(key.as_ref(), value)?.to_vec())),
+            Err(LMDBError::NotFound) => Ok(None),
+            Err(err) => Err(Error::from(err).into()),
+        }
+    }
+
+    /// Insert data for a key in `db`.
+    pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, db: &LMDB, key: K, value: V) -> Result<()> {
+        self.check_env(db)?;
+        db.check_new_key(key.as_ref())?;
+        let value = db.seal_value(value.as_ref());
+        mark_transient(self.txn.put(*db.db, &key, &value, WriteFlags::empty())
+            .map_err(|err| Error::from(err).into()))
+    }
+
+    /// Remove a key from `db`, returning `true` if it was there.
+    pub fn remove<K: AsRef<[u8]>>(&mut self, db: &LMDB, key: K) -> Result<bool> {
+        self.check_env(db)?;
+        db.check_key(key.as_ref())?;
+        match self.txn.del(*db.db, &key, None) {
+            Ok(()) => Ok(true),
+            Err(LMDBError::NotFound) => Ok(false),
+            Err(err) => Err(Error::from(err).into()),
+        }
+    }
+
+    /// Commit every write made through this writer, to every database.
+    ///
+    /// If the commit fails, none of the writes are kept.
+    pub fn commit(self) -> Result<()> {
+        mark_transient(self.txn.commit().map_err(|err| Error::from(err).into()))
+    }
+}
+
+impl std::fmt::Debug for MultiWriter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiWriter").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,24 @@
+//! Use the shared `temp_lmdb` helper from `db-map-test`.
+
+use db_map_lmdb::DBMap;
+use db_map_test::temp_lmdb;
+
+#[test]
+fn temp_lmdb_round_trips_a_value() {
+    let (temp_dir, db) = temp_lmdb(None);
+    db.insert(b"key", b"value").unwrap();
+    assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+
+    // The directory is removed once the `TempDir` is dropped.
+    let path = temp_dir.path().to_path_buf();
+    drop(db);
+    drop(temp_dir);
+    assert!(!path.exists());
+}
+
+#[test]
+fn temp_lmdb_opens_named_database() {
+    let (_temp_dir, db) = temp_lmdb(Some("named"));
+    db.insert(b"key", b"value").unwrap();
+    assert_eq!(db.len().unwrap(), 1);
+}
@@ -5,11 +5,18 @@
 use db_key_macro::db_key;
 use db_map_trait::DBMap;
 use proptest::prelude::*;
-use std::fmt::Debug;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+};
 type Result<T> = std::result::Result<T, TestCaseError>;
 
+#[cfg(feature = "lmdb-test")]
+pub mod lmdb;
 pub mod strategy;
 
+#[cfg(feature = "lmdb-test")]
+pub use lmdb::{temp_dir, temp_lmdb};
 pub use strategy::*;
 
 /// A simple key used for test cases.
@@ -44,6 +51,63 @@ pub struct TestValue {
     array: [u8; 9],
 }
 
+/// Encode test key arguments directly as database key bytes.
+///
+/// This produces the same bytes as building a [`TestKey`] from the arguments, so code that works
+/// with `TestKeyArgs` can go straight to the database without constructing the key first.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMap;
+/// use db_map_btreemap::BTreeMapDB;
+/// use db_map_test::{TestKey, TestKeyArgs};
+///
+/// let db = BTreeMapDB::open();
+/// let args = TestKeyArgs { id: 0x12345678, index: 0x9ABCDEF0 };
+///
+/// // Insert with the bytes encoded from the arguments.
+/// db.insert(Vec::<u8>::from(&args), b"value").unwrap();
+///
+/// // Read back with the fully constructed key.
+/// assert_eq!(db.get(TestKey::from(args)).unwrap(), Some(b"value".to_vec()));
+/// ```
+impl From<&TestKeyArgs> for Vec<u8> {
+    fn from(args: &TestKeyArgs) -> Vec<u8> {
+        TestKey::from(*args).as_ref().to_vec()
+    }
+}
+
+/// Encode test value arguments directly as database value bytes.
+///
+/// This produces the same bytes as building a [`TestValue`] from the arguments.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMap;
+/// use db_map_btreemap::BTreeMapDB;
+/// use db_map_test::{TestValue, TestValueArgs};
+///
+/// let db = BTreeMapDB::open();
+/// let args = TestValueArgs {
+///     byte: 0x12,
+///     word: 0x3456,
+///     long: 0x789ABCDE,
+///     quad: 0xFEDCBA9876543210,
+///     octo: 0x13579BDFECA864201F2E3D4C5B6A7988,
+///     array: *b"GHIJKLMNO",
+/// };
+///
+/// db.insert(b"key", Vec::<u8>::from(&args)).unwrap();
+/// assert_eq!(db.get_map(b"key", |v| TestValue::from(v)).unwrap(), Some(TestValue::from(args)));
+/// ```
+impl From<&TestValueArgs> for Vec<u8> {
+    fn from(args: &TestValueArgs) -> Vec<u8> {
+        TestValue::from(*args).as_ref().to_vec()
+    }
+}
+
 macro_rules! tt_to_concat_params {
     (()) => {""};
     (($min:literal, $max:literal)) => { concat!(stringify!($min), ", ", stringify!($max)) }
@@ -175,6 +239,75 @@ where
     Ok(())
 }
 
+/// This is a simple test using one key and value. It tests get_map_sized(), insert(), and remove().
+#[doc = make_test_docs!{get_map_sized_test, (
+    key in random_key(),
+    value in random_value(),
+)}]
+pub fn get_map_sized_test<M, K, V>(db: &M, key: K, value: V) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]> + Clone,
+    V: AsRef<[u8]> + Clone,
+{
+    prop_assert!(db.get_map_sized(key.clone(), |v| v.to_vec())?.is_none());
+    db.insert(key.clone(), value.clone())?;
+    prop_assert_eq!(
+        db.get_map_sized(key.clone(), |v| v.to_vec())?,
+        Some((value.as_ref().len(), value.as_ref().to_vec()))
+    );
+    db.remove(key.clone())?;
+    prop_assert!(db.get_map_sized(key.clone(), |v| v.len())?.is_none());
+    Ok(())
+}
+
+/// This is a simple test using one key and value. It tests that get_cow() returns the same data
+/// as get().
+#[doc = make_test_docs!{get_cow_test, (
+    key in random_key(),
+    value in random_value(),
+)}]
+pub fn get_cow_test<M, K, V>(db: &M, key: K, value: V) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]> + Clone,
+    V: AsRef<[u8]> + Clone,
+{
+    prop_assert!(db.get_cow(key.clone())?.is_none());
+    db.insert(key.clone(), value.clone())?;
+    let cow = db.get_cow(key.clone())?;
+    let value_from_get = db.get(key.clone())?;
+    prop_assert_eq!(cow.as_deref(), value_from_get.as_deref());
+    prop_assert_eq!(cow.as_deref(), Some(value.as_ref()));
+    db.remove(key.clone())?;
+    prop_assert!(db.get_cow(key.clone())?.is_none());
+    Ok(())
+}
+
+/// This is a simple test using one key and value. It tests take_map() and take() on present and
+/// absent keys.
+#[doc = make_test_docs!{take_map_test, (
+    key in random_key(),
+    value in random_value(),
+)}]
+pub fn take_map_test<M, K, V>(db: &M, key: K, value: V) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]> + Clone,
+    V: AsRef<[u8]> + Clone,
+{
+    prop_assert_eq!(db.take_map(key.clone(), |v| v.len())?, None);
+    db.insert(key.clone(), value.clone())?;
+    prop_assert_eq!(db.take_map(key.clone(), |v| v.to_vec())?, Some(value.as_ref().to_vec()));
+    prop_assert!(db.get(key.clone())?.is_none());
+    prop_assert_eq!(db.take_map(key.clone(), |v| v.len())?, None);
+    db.insert(key.clone(), value.clone())?;
+    prop_assert_eq!(db.take(key.clone())?, Some(value.as_ref().to_vec()));
+    prop_assert!(db.get(key.clone())?.is_none());
+    prop_assert!(db.is_empty()?);
+    Ok(())
+}
+
 /// This is a simple test using one key and several data items. It tests fetch_and_replace(), and remove().
 #[doc = make_test_docs!{fetch_and_replace_test, (
     "" key "" in test_key(),
@@ -225,8 +358,599 @@ where
     Ok(())
 }
 
+/// Test `upsert()` by building a running concatenation of values under one key.
+///
+/// The first call must store the default value, and every following call must append to the
+/// existing value through the `modify` function.
+#[doc = make_test_docs!{upsert_test, (
+    "" key "" in random_key(),
+    "&" values "" in random_values(2, 6),
+)}]
+pub fn upsert_test<M, K, V>(db: &M, key: K, values: &[V]) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]> + Clone,
+    V: AsRef<[u8]>,
+{
+    prop_assert!(db.get(key.clone())?.is_none());
+    let mut expected = Vec::new();
+    for value in values {
+        db.upsert(key.clone(), value, |existing| {
+            let mut new_value = existing.to_vec();
+            new_value.extend_from_slice(value.as_ref());
+            new_value
+        })?;
+        expected.extend_from_slice(value.as_ref());
+        prop_assert_eq!(db.get(key.clone())?, Some(expected.clone()));
+    }
+    db.update(key.clone(), |_| None)?;
+    prop_assert!(db.get(key)?.is_none());
+    Ok(())
+}
+
+/// This is a simple test using one key and several data items. It tests fetch_and_replace_if()
+/// with matching and mismatching expected values, starting from an absent key.
+#[doc = make_test_docs!{fetch_and_replace_if_test, (
+    "" key "" in test_key(),
+    "&" values "" in test_values(2, 10),
+)}]
+pub fn fetch_and_replace_if_test<M, K, V>(db: &M, key: K, values: &[V]) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]> + Clone,
+    V: AsRef<[u8]>,
+{
+    let mut current: Option<Vec<u8>> = None;
+    for value in values {
+        let value = value.as_ref();
+        let wrong = [current.as_deref().unwrap_or_default(), b"?"].concat();
+        prop_assert_eq!(
+            db.fetch_and_replace_if(key.clone(), Some(&wrong), value)?,
+            Err(current.clone())
+        );
+        if current.is_some() {
+            prop_assert_eq!(
+                db.fetch_and_replace_if(key.clone(), None::<&[u8]>, value)?,
+                Err(current.clone())
+            );
+        }
+        prop_assert_eq!(
+            db.fetch_and_replace_if(key.clone(), current.as_deref(), value)?,
+            Ok(current.clone())
+        );
+        current = Some(value.to_vec());
+        prop_assert_eq!(db.get(key.clone())?, current.clone());
+    }
+    db.remove(key)?;
+    Ok(())
+}
+
+/// This is a simple test using one key and several data items. It tests that try_insert() only
+/// inserts the first value and returns the existing value for every later one.
+#[doc = make_test_docs!{try_insert_test, (
+    "" key "" in test_key(),
+    "&" values "" in test_values(2, 10),
+)}]
+pub fn try_insert_test<M, K, V>(db: &M, key: K, values: &[V]) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]> + Clone,
+    V: AsRef<[u8]>,
+{
+    let first = values[0].as_ref();
+    prop_assert_eq!(db.try_insert(key.clone(), first)?, Ok(()));
+    for value in &values[1..] {
+        prop_assert_eq!(db.try_insert(key.clone(), value)?, Err(first.to_vec()));
+        prop_assert_eq!(db.get(key.clone())?, Some(first.to_vec()));
+    }
+    db.remove(key.clone())?;
+    prop_assert_eq!(db.try_insert(key.clone(), values[1].as_ref())?, Ok(()));
+    prop_assert_eq!(db.get(key.clone())?, Some(values[1].as_ref().to_vec()));
+    db.remove(key)?;
+    Ok(())
+}
+
+/// Run a sequence of operations that churn a few keys, checking the database against a
+/// `BTreeMap` model after every operation.
+///
+/// After each operation the value of the key it touched must match the model, and at the end the
+/// database must hold exactly the model's entries, in the same order.
+#[doc = make_test_docs!{key_value_ops_test, (
+    "&" ops "" in key_value_ops(1, 30),
+)}]
+pub fn key_value_ops_test<M: DBMap>(db: &M, ops: &[Op]) -> Result<()> {
+    let mut model = std::collections::BTreeMap::new();
+    for op in ops {
+        let key = match op {
+            Op::Insert(key, value) => {
+                db.insert(key, value)?;
+                model.insert(key.clone(), value.clone());
+                key
+            }
+            Op::Overwrite(key, value) => {
+                let old = db.fetch_and_replace(key, value)?;
+                prop_assert_eq!(old, model.insert(key.clone(), value.clone()));
+                key
+            }
+            Op::Remove(key) => {
+                db.remove(key)?;
+                model.remove(key);
+                key
+            }
+        };
+        let value = db.get(key)?;
+        prop_assert_eq!(value.as_ref(), model.get(key), "after {:?}", op);
+    }
+    let mut entries = Vec::new();
+    db.for_each(|key, value| entries.push((key.to_vec(), value.to_vec())))?;
+    prop_assert_eq!(entries, model.into_iter().collect::<Vec<_>>());
+    Ok(())
+}
+
+/// Verify that `len()`, `for_each()`, and `digest()` agree with each other.
+///
+/// After inserting a set of keys and values, the length reported by `len()` must match both the
+/// number of entries inserted and the number of entries visited by `for_each()`. This catches
+/// databases where a statistics-based `len()` disagrees with the actual contents. It also checks
+/// that `digest()` is stable when computed twice with no intervening writes, and that once every
+/// key is removed again `len()` is back to zero and `is_empty()` agrees.
+#[doc = make_test_docs!{scan_matches_len_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 20),
+)}]
+pub fn scan_matches_len_test<M, K, V>(db: &M, keys_and_values: &HashMap<K, V>) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    prop_assert_eq!(db.len()?, 0);
+    prop_assert!(db.is_empty()?);
+    for (key, value) in keys_and_values {
+        db.insert(key, value)?;
+    }
+    let len = db.len()?;
+    prop_assert_eq!(len, keys_and_values.len());
+    prop_assert!(!db.is_empty()?);
+    let mut count = 0;
+    db.for_each(|_, _| count += 1)?;
+    prop_assert_eq!(count, len);
+    prop_assert_eq!(db.digest()?, db.digest()?);
+
+    for (removed, key) in keys_and_values.keys().enumerate() {
+        db.remove(key)?;
+        prop_assert_eq!(db.len()?, len - removed - 1);
+    }
+    prop_assert_eq!(db.len()?, 0);
+    prop_assert!(db.is_empty()?);
+    Ok(())
+}
+
+/// Verify that `iter()` returns exactly the inserted entries, sorted by key.
+///
+/// The keys must be in strictly increasing byte-lexicographic order, whatever order the backend
+/// scans in, and every key must be paired with the value inserted for it.
+#[doc = make_test_docs!{iter_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 20),
+)}]
+pub fn iter_test<M, K, V>(db: &M, keys_and_values: &HashMap<K, V>) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    prop_assert!(db.iter()?.is_empty());
+    db.batch_insert(keys_and_values.iter())?;
+    let entries = db.iter()?;
+    prop_assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0), "entries out of order");
+    let mut expected: Vec<_> = keys_and_values.iter()
+        .map(|(key, value)| (key.as_ref().to_vec(), value.as_ref().to_vec()))
+        .collect();
+    expected.sort();
+    prop_assert_eq!(entries, expected);
+    Ok(())
+}
+
+/// Verify that `keys()` returns exactly the inserted keys, sorted.
+///
+/// The keys must be in strictly increasing byte-lexicographic order and match the keys of
+/// `iter()`.
+#[doc = make_test_docs!{keys_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 20),
+)}]
+pub fn keys_test<M, K, V>(db: &M, keys_and_values: &HashMap<K, V>) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    prop_assert!(db.keys()?.is_empty());
+    db.batch_insert(keys_and_values.iter())?;
+    let keys = db.keys()?;
+    prop_assert!(keys.windows(2).all(|pair| pair[0] < pair[1]), "keys out of order");
+    let mut expected: Vec<_> = keys_and_values.keys().map(|key| key.as_ref().to_vec()).collect();
+    expected.sort();
+    prop_assert_eq!(&keys, &expected);
+    let iter_keys: Vec<_> = db.iter()?.into_iter().map(|(key, _)| key).collect();
+    prop_assert_eq!(keys, iter_keys);
+    Ok(())
+}
+
+/// Verify that `clear()` removes every entry.
+///
+/// After the clear, `len()` must be zero, `is_empty()` must be true, and every key must be gone
+/// from both `get()` and a full scan. Clearing an empty database must succeed, and the database
+/// must still accept writes afterwards.
+#[doc = make_test_docs!{clear_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 20),
+)}]
+pub fn clear_test<M, K, V>(db: &M, keys_and_values: &HashMap<K, V>) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    db.batch_insert(keys_and_values.iter())?;
+    prop_assert_eq!(db.len()?, keys_and_values.len());
+    db.clear()?;
+    prop_assert_eq!(db.len()?, 0);
+    prop_assert!(db.is_empty()?);
+    for key in keys_and_values.keys() {
+        prop_assert_eq!(db.get(key)?, None);
+    }
+    let mut count = 0;
+    db.for_each(|_, _| count += 1)?;
+    prop_assert_eq!(count, 0);
+
+    db.clear()?;
+    let (key, value) = keys_and_values.iter().next().unwrap();
+    db.insert(key, value)?;
+    prop_assert_eq!(db.get(key)?, Some(value.as_ref().to_vec()));
+    prop_assert_eq!(db.len()?, 1);
+    Ok(())
+}
+
+/// Verify that `try_for_each()` stops at the first error and returns it.
+///
+/// The callback fails on the middle key (in key order), so the scan must visit exactly the
+/// entries up to and including that key and then return the callback's error.
+#[doc = make_test_docs!{try_for_each_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 20),
+)}]
+pub fn try_for_each_test<M, K, V>(db: &M, keys_and_values: &HashMap<K, V>) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    for (key, value) in keys_and_values {
+        db.insert(key, value)?;
+    }
+    let mut sorted_keys: Vec<&[u8]> = keys_and_values.keys().map(|k| k.as_ref()).collect();
+    sorted_keys.sort();
+    let stop_index = sorted_keys.len() / 2;
+    let stop_key = sorted_keys[stop_index];
+    let mut visited = Vec::new();
+    let result = db.try_for_each(|key, _| {
+        visited.push(key.to_vec());
+        match key == stop_key {
+            true => Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "stop").into()),
+            false => Ok(()),
+        }
+    });
+    match result {
+        Err(db_map_trait::Error::IoError(err)) => {
+            prop_assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        }
+        other => prop_assert!(false, "unexpected result: {:?}", other),
+    }
+    let expected: Vec<Vec<u8>> = sorted_keys[..=stop_index].iter().map(|k| k.to_vec()).collect();
+    prop_assert_eq!(visited, expected);
+    Ok(())
+}
+
+/// Verify that paging through a prefix with `scan_page()` visits every entry under the prefix
+/// exactly once, in key order.
+///
+/// Every key is inserted twice: once under the prefix `p/` and once under `q/`, which must never
+/// be visited.
+#[doc = make_test_docs!{scan_page_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 20),
+), (3)}]
+pub fn scan_page_test<M, K, V>(db: &M, keys_and_values: &HashMap<K, V>, limit: usize) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let mut expected = Vec::new();
+    for (key, value) in keys_and_values {
+        let key = [b"p/", key.as_ref()].concat();
+        db.insert(&key, value)?;
+        db.insert([b"q/", &key[2..]].concat(), value)?;
+        expected.push((key, value.as_ref().to_vec()));
+    }
+    expected.sort();
+
+    let mut visited = Vec::new();
+    let mut start_after = None;
+    loop {
+        let (page, next) = db.scan_page(b"p/", start_after.as_deref(), limit)?;
+        prop_assert!(page.len() <= limit);
+        visited.extend(page);
+        match next {
+            Some(next) => start_after = Some(next),
+            None => break,
+        }
+    }
+    prop_assert_eq!(visited, expected);
+    Ok(())
+}
+
+/// Verify that a prefix scan includes a key that is exactly equal to the prefix.
+///
+/// The keys `ab`, `abc`, `abcd`, `abd`, and `abz` are inserted, and both `scan_prefix()` and
+/// `scan_page()` with the prefix `abc` must visit `abc` and `abcd`, and nothing else: not the
+/// shorter `ab`, nor the neighbours `abd` and `abz` that share all but the last byte.
+#[doc = make_test_docs!{prefix_is_key_test, (
+    values in random_values(5, 5),
+)}]
+pub fn prefix_is_key_test<M, V>(db: &M, values: Vec<V>) -> Result<()>
+where
+    M: DBMap,
+    V: AsRef<[u8]>,
+{
+    let keys: [&[u8]; 5] = [b"ab", b"abc", b"abcd", b"abd", b"abz"];
+    prop_assert_eq!(values.len(), keys.len());
+    for (key, value) in keys.iter().zip(&values) {
+        db.insert(key, value)?;
+    }
+    let expected = vec![
+        (b"abc".to_vec(), values[1].as_ref().to_vec()),
+        (b"abcd".to_vec(), values[2].as_ref().to_vec()),
+    ];
+
+    let mut visited = Vec::new();
+    db.scan_prefix(b"abc", |key, value| visited.push((key.to_vec(), value.to_vec())))?;
+    visited.sort();
+    prop_assert_eq!(&visited, &expected);
+
+    let (mut page, next) = db.scan_page(b"abc", None, keys.len())?;
+    page.sort();
+    prop_assert_eq!(&page, &expected);
+    prop_assert_eq!(next, None);
+    Ok(())
+}
+
+/// Verify that `count_range()` and `scan_range()` agree with the sorted keys for every kind of
+/// bound.
+///
+/// For every pair of inserted keys, in key order, the ranges between them with each combination of
+/// included and excluded bounds must hold the expected number of keys. Unbounded ranges must
+/// cover everything before or after a key, and a reversed range must be empty.
+#[doc = make_test_docs!{count_range_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 10),
+)}]
+pub fn count_range_test<M, K, V>(db: &M, keys_and_values: &HashMap<K, V>) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    use std::ops::Bound::{Excluded, Included, Unbounded};
+
+    for (key, value) in keys_and_values {
+        db.insert(key, value)?;
+    }
+    let mut keys: Vec<&[u8]> = keys_and_values.keys().map(|k| k.as_ref()).collect();
+    keys.sort();
+    let n = keys.len();
+    prop_assert_eq!(db.count_range(Unbounded, Unbounded)?, n);
+    for (i, &low) in keys.iter().enumerate() {
+        prop_assert_eq!(db.count_range(Included(low), Unbounded)?, n - i);
+        prop_assert_eq!(db.count_range(Excluded(low), Unbounded)?, n - i - 1);
+        prop_assert_eq!(db.count_range(Unbounded, Included(low))?, i + 1);
+        prop_assert_eq!(db.count_range(Unbounded, Excluded(low))?, i);
+        for (j, &high) in keys.iter().enumerate().skip(i) {
+            prop_assert_eq!(db.count_range(Included(low), Included(high))?, j - i + 1);
+            prop_assert_eq!(db.count_range(Included(low), Excluded(high))?, j - i);
+            prop_assert_eq!(db.count_range(Excluded(low), Included(high))?, j - i);
+            prop_assert_eq!(db.count_range(Excluded(low), Excluded(high))?, (j - i).saturating_sub(1));
+            if i < j {
+                prop_assert_eq!(db.count_range(Included(high), Included(low))?, 0);
+            }
+        }
+    }
+    let mut scanned = Vec::new();
+    db.scan_range(Included(keys[0]), Excluded(keys[n - 1]), |key, _| scanned.push(key.to_vec()))?;
+    let expected: Vec<Vec<u8>> = keys[..n - 1].iter().map(|k| k.to_vec()).collect();
+    prop_assert_eq!(scanned, expected);
+    Ok(())
+}
+
+/// Verify the behavior at LMDB's maximum key size.
+///
+/// Backends don't have to share LMDB's limit: a key of any length may be accepted, as
+/// `BTreeMapDB` does. A key up to [`LMDB_MAX_KEY_SIZE`] bytes must be accepted, and a longer key
+/// must either be stored and read back like any other, or be rejected with
+/// [`Error::InvalidKey`][db_map_trait::Error::InvalidKey] without being stored.
+#[doc = make_test_docs!{max_size_key_test, (
+    key in max_size_key(),
+    value in random_value(),
+)}]
+pub fn max_size_key_test<M, K, V>(db: &M, key: K, value: V) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]> + Debug,
+    V: AsRef<[u8]>,
+{
+    let len = key.as_ref().len();
+    match db.insert(&key, &value) {
+        Ok(()) => {
+            let stored = db.get(&key)?;
+            prop_assert_eq!(stored.as_deref(), Some(value.as_ref()));
+        }
+        Err(db_map_trait::Error::InvalidKey(_)) => {
+            prop_assert!(len > LMDB_MAX_KEY_SIZE, "a {}-byte key was rejected", len);
+            match db.get(&key) {
+                Ok(None) | Err(db_map_trait::Error::InvalidKey(_)) => {}
+                other => prop_assert!(false, "a rejected key was read back as {:?}", other),
+            }
+            prop_assert_eq!(db.len()?, 0);
+        }
+        Err(err) => prop_assert!(false, "inserting a {}-byte key failed with {}", len, err),
+    }
+    Ok(())
+}
+
+/// Verify that `first_value()` and `last_value()` return the values of the smallest and largest
+/// keys.
+#[doc = make_test_docs!{first_last_value_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 20),
+)}]
+pub fn first_last_value_test<M, K, V>(db: &M, keys_and_values: &HashMap<K, V>) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    prop_assert_eq!(db.first_value()?, None);
+    prop_assert_eq!(db.last_value()?, None);
+    for (key, value) in keys_and_values {
+        db.insert(key, value)?;
+    }
+    let mut entries: Vec<(&[u8], &[u8])> = keys_and_values.iter()
+        .map(|(key, value)| (key.as_ref(), value.as_ref()))
+        .collect();
+    entries.sort();
+    let first = entries.first().map(|(_, value)| value.to_vec());
+    let last = entries.last().map(|(_, value)| value.to_vec());
+    prop_assert_eq!(db.first_value()?, first);
+    prop_assert_eq!(db.last_value()?, last);
+    Ok(())
+}
+
+/// Verify that `move_prefix()` moves exactly the entries under a prefix.
+///
+/// Every entry is inserted under `from_prefix` and again under `c`, which neither prefix can be
+/// under unless `from_prefix` is empty. After the move, the database must match a model where the
+/// entries under `from_prefix` were taken out and put back under `to_prefix`. When neither prefix
+/// is under the other, nothing may be left under `from_prefix`.
+#[doc = make_test_docs!{move_prefix_test, (
+    "&" keys_and_values "" in random_keys_and_values(1, 10),
+    "&" from_prefix "" in key_prefix(),
+    "&" to_prefix "" in key_prefix(),
+)}]
+pub fn move_prefix_test<M, K, V>(
+    db: &M,
+    keys_and_values: &HashMap<K, V>,
+    from_prefix: &[u8],
+    to_prefix: &[u8],
+) -> Result<()>
+where
+    M: DBMap,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let mut model = std::collections::BTreeMap::new();
+    for (key, value) in keys_and_values {
+        for prefix in [from_prefix, b"c"] {
+            let key = [prefix, key.as_ref()].concat();
+            db.insert(&key, value)?;
+            model.insert(key, value.as_ref().to_vec());
+        }
+    }
+    let moved: Vec<(Vec<u8>, Vec<u8>)> = model.iter()
+        .filter(|(key, _)| key.starts_with(from_prefix))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    for (key, _) in &moved {
+        model.remove(key);
+    }
+    for (key, value) in &moved {
+        model.insert([to_prefix, &key[from_prefix.len()..]].concat(), value.clone());
+    }
+
+    prop_assert_eq!(db.move_prefix(from_prefix, to_prefix)?, moved.len());
+    let mut contents = std::collections::BTreeMap::new();
+    db.for_each(|key, value| { contents.insert(key.to_vec(), value.to_vec()); })?;
+    prop_assert_eq!(contents, model);
+    if !to_prefix.starts_with(from_prefix) && !from_prefix.starts_with(to_prefix) {
+        let mut left = 0;
+        db.scan_prefix(from_prefix, |_, _| left += 1)?;
+        prop_assert_eq!(left, 0);
+    }
+    Ok(())
+}
+
+/// Get the path, relative to the package being tested, where failures of the suite tests
+/// generated in `module_path` are saved.
+///
+/// Each `::` in the module path becomes a directory, so a backend's suites are kept apart from
+/// each other and from every other backend's, like
+/// `proptest-regressions/db_map_btreemap/tests/buffered/db_map_tests.txt`.
+pub fn regression_path(module_path: &str) -> &'static str {
+    static PATHS: std::sync::Mutex<Vec<&'static str>> = std::sync::Mutex::new(Vec::new());
+    let path = format!("proptest-regressions/{}.txt", module_path.replace("::", "/"));
+    let mut paths = PATHS.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    match paths.iter().find(|known| **known == path) {
+        Some(known) => known,
+        // proptest needs a `&'static str`, so each path is leaked once and then reused.
+        None => {
+            let path: &'static str = Box::leak(path.into_boxed_str());
+            paths.push(path);
+            path
+        }
+    }
+}
+
+/// The proptest configuration used by the tests that [`impl_db_map_tests!`] generates in
+/// `module_path`.
+///
+/// By default, proptest saves failures in a file named after the source file, so every suite
+/// generated in one file (like the suites for a backend and each of its wrappers) shares one
+/// file, and replays every saved failure against every test. This saves them in the suite's own
+/// file, at [`regression_path`], so each failure is replayed against the suite it came from.
+/// Check the files in to keep replaying them.
+///
+/// # Examples
+///
+/// A failing property saves its seed in the suite's file:
+///
+/// ```rust
+/// use db_map_test::{regression_path, suite_config};
+/// use proptest::{prelude::*, test_runner::TestRunner};
+///
+/// let module_path = "suite_config_doctest::backend::db_map_tests";
+/// let path = regression_path(module_path);
+/// assert_eq!(path, "proptest-regressions/suite_config_doctest/backend/db_map_tests.txt");
+///
+/// let mut runner = TestRunner::new(suite_config(module_path));
+/// assert!(runner.run(&any::<u8>(), |byte| {
+///     prop_assert!(byte < 200);
+///     Ok(())
+/// }).is_err());
+///
+/// let saved = std::fs::read_to_string(path).unwrap();
+/// std::fs::remove_dir_all("proptest-regressions/suite_config_doctest").unwrap();
+/// // Only remove the top directory if nothing else is saved there.
+/// let _ = std::fs::remove_dir("proptest-regressions");
+/// assert!(saved.lines().any(|line| line.starts_with("cc ") && line.contains("shrinks to 200")));
+/// ```
+pub fn suite_config(module_path: &str) -> ProptestConfig {
+    ProptestConfig {
+        failure_persistence: Some(Box::new(
+            proptest::test_runner::FileFailurePersistence::Direct(regression_path(module_path)),
+        )),
+        ..ProptestConfig::default()
+    }
+}
+
 /// Macro that generates the standard test suite for implementations of the [`DBMap`] trait.
 ///
+/// Failures are saved per suite; see [`suite_config`]. A database that needs something kept
+/// alive while it is used, like the `TempDir` from [`temp_lmdb`], is opened with
+/// `let (_temp_dir, db) = ...;`, and the first value is held until each test ends.
+///
 /// # Examples
 ///
 /// ```rust
@@ -243,154 +967,437 @@ where
 /// use db_map_lmdb::{LMDB, LMDBArgs};
 ///
 /// impl_db_map_tests! {
-///     let db = {
+///     let (_temp_dir, db) = {
 ///         let temp_dir = tempfile::Builder::new()
 ///             .prefix("lmdb_test_dir_")
 ///             .rand_bytes(5)
 ///             .tempdir().unwrap();
-///         LMDB::open(temp_dir.path(), db_name, LMDBArgs::default())
+///         let db = LMDB::open(temp_dir.path(), None, LMDBArgs::default()).unwrap();
+///         (temp_dir, db)
 ///     };
 /// }
 /// ```
 #[macro_export]
 macro_rules! impl_db_map_tests {
     (let db = $let_db:expr;) => {
+        $crate::impl_db_map_tests! {
+            let (_temp_dir, db) = ((), $let_db);
+        }
+    };
+    (let (_temp_dir, db) = $let_db:expr;) => {
         mod db_map_tests {
             use super::*;
             use proptest::prelude::*;
             use db_map_test::*;
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn clone_random_data(
                     keys in random_keys(2, 2),
                     values in random_values(2, 2),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     clone_test(&db, keys, values)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn clone_test_data(
                     keys in test_keys(2, 2),
                     values in test_values(2, 2),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     clone_test(&db, keys, values)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn clone_string_data(
                     keys in string_keys(2, 2),
                     values in string_values(2, 2),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     clone_test(&db, keys, values)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn insert_random_data(
                     key in random_key(),
                     value in random_value(),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     insert_test(&db, key, &value)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn insert_test_data(
                     key in test_key(),
                     value in test_value(),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     insert_test(&db, key, &value)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn insert_string_data(
                     key in string_key(),
                     value in string_value(),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     insert_test(&db, key, &value)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn get_random_data(
                     key in random_key(),
                     value in random_value(),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     get_test(&db, &key, &value)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn get_map_sized_random_data(
+                    key in random_key(),
+                    value in random_value(),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    get_map_sized_test(&db, &key, &value)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn get_cow_random_data(
+                    key in random_key(),
+                    value in random_value(),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    get_cow_test(&db, &key, &value)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn take_map_random_data(
+                    key in random_key(),
+                    value in random_value(),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    take_map_test(&db, &key, &value)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn key_value_ops_random_data(
+                    ops in key_value_ops(1, 30),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    key_value_ops_test(&db, &ops)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn count_range_random_data(
+                    keys_and_values in random_keys_and_values(1, 10),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    count_range_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn max_size_key_random_data(
+                    key in max_size_key(),
+                    value in random_value(),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    max_size_key_test(&db, &key, &value)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn prefix_is_key_random_data(
+                    values in random_values(5, 5),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    prefix_is_key_test(&db, values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn first_last_value_random_data(
+                    keys_and_values in random_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    first_last_value_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn move_prefix_random_data(
+                    keys_and_values in random_keys_and_values(1, 10),
+                    from_prefix in key_prefix(),
+                    to_prefix in key_prefix(),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    move_prefix_test(&db, &keys_and_values, &from_prefix, &to_prefix)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn fetch_and_replace_if_random_data(
+                    key in random_key(),
+                    values in random_values(2, 5),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    fetch_and_replace_if_test(&db, &key, &values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn fetch_and_replace_if_test_data(
+                    key in test_key(),
+                    values in test_values(2, 5),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    fetch_and_replace_if_test(&db, &key, &values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn try_insert_random_data(
+                    key in random_key(),
+                    values in random_values(2, 5),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    try_insert_test(&db, &key, &values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn fetch_and_replace_random_data(
                     key in random_key(),
                     values in random_values(2, 5),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     fetch_and_replace_test(&db, &key, &values)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn fetch_and_replace_test_data(
                     key in test_key(),
                     values in test_values(2, 5),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     fetch_and_replace_test(&db, &key, &values)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn fetch_and_replace_string_data(
                     key in string_key(),
                     values in string_values(2, 5),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     fetch_and_replace_test(&db, &key, &values)?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn fetch_and_replace_map_test_data(
                     key in test_key(),
                     values in test_values(2, 5),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     fetch_and_replace_map_test(&db, key, &values, |v| TestValue::from(v))?;
                 }
             }
 
             proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
                 #[test]
                 fn fetch_and_replace_map_string_data(
                     key in string_key(),
                     values in string_values(2, 5),
                 ) {
-                    let db = $let_db;
+                    let (_temp_dir, db) = $let_db;
                     fetch_and_replace_map_test(&db, key, &values, |v| String::from_utf8(v.to_vec()).unwrap())?;
                 }
             }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn scan_page_random_data(
+                    keys_and_values in random_keys_and_values(1, 20),
+                    limit in 1_usize..5,
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    scan_page_test(&db, &keys_and_values, limit)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn try_for_each_random_data(
+                    keys_and_values in random_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    try_for_each_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn try_for_each_string_data(
+                    keys_and_values in string_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    try_for_each_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn upsert_random_data(
+                    key in random_key(),
+                    values in random_values(2, 5),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    upsert_test(&db, &key, &values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn upsert_string_data(
+                    key in string_key(),
+                    values in string_values(2, 5),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    upsert_test(&db, &key, &values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn scan_matches_len_random_data(
+                    keys_and_values in random_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    scan_matches_len_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn scan_matches_len_test_data(
+                    keys_and_values in test_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    scan_matches_len_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn scan_matches_len_string_data(
+                    keys_and_values in string_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    scan_matches_len_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn clear_random_data(
+                    keys_and_values in random_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    clear_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn iter_random_data(
+                    keys_and_values in random_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    iter_test(&db, &keys_and_values)?;
+                }
+            }
+
+            proptest! {
+                #![proptest_config($crate::suite_config(module_path!()))]
+                #[test]
+                fn keys_random_data(
+                    keys_and_values in random_keys_and_values(1, 20),
+                ) {
+                    let (_temp_dir, db) = $let_db;
+                    keys_test(&db, &keys_and_values)?;
+                }
+            }
         }
     };
 }
@@ -0,0 +1,60 @@
+//! # LMDB test helpers.
+//!
+//! These are enabled by the `lmdb-test` feature.
+use db_map_lmdb::{LMDB, LMDBArgs};
+use tempfile::TempDir;
+
+/// The memory map size used by [`temp_lmdb`] (64 MiB).
+///
+/// This is large enough that tests don't fail with `MapFull`, and since the data file only grows
+/// as pages are written, it doesn't cost disk space.
+pub const TEMP_LMDB_MAP_SIZE: usize = 64 * 1024 * 1024;
+
+/// Create a new temporary directory for a test database.
+///
+/// The directory is deleted when the `TempDir` is dropped, so keep it alive until every
+/// database opened in it is closed. [`temp_lmdb`] opens a database in one; use this directly to
+/// open a database with other arguments, or to open it more than once.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_test::temp_dir;
+///
+/// let temp_dir = temp_dir();
+/// assert!(temp_dir.path().is_dir());
+/// ```
+pub fn temp_dir() -> TempDir {
+    tempfile::Builder::new()
+        .prefix("lmdb_test_dir_")
+        .rand_bytes(5)
+        .tempdir()
+        .expect("failed to create a temporary directory")
+}
+
+/// Open an LMDB database in a new temporary directory.
+///
+/// The `TempDir` is returned along with the database so that the directory lives as long as the
+/// test needs it, and it is deleted when the `TempDir` is dropped. Keep it alive (for example,
+/// by binding it to `_temp_dir`, not `_`) until the database is no longer used.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMap;
+/// use db_map_test::temp_lmdb;
+///
+/// let (temp_dir, db) = temp_lmdb(None);
+/// db.insert(b"key", b"value").unwrap();
+/// assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+/// assert!(temp_dir.path().join("data.mdb").exists());
+/// ```
+pub fn temp_lmdb(db_name: Option<&str>) -> (TempDir, LMDB) {
+    let temp_dir = temp_dir();
+    let db = LMDB::open(temp_dir.path(), db_name, LMDBArgs {
+        max_dbs: db_name.map(|_| 1),
+        map_size: Some(TEMP_LMDB_MAP_SIZE),
+        ..Default::default()
+    }).expect("failed to open a temporary LMDB database");
+    (temp_dir, db)
+}
@@ -9,6 +9,9 @@ use proptest::prelude::*;
 pub const RANDOM_KEY_MAX: usize = 20;
 /// Maximum length of the random value generated by `random_value()`.
 pub const RANDOM_VALUE_MAX: usize = 100;
+/// The longest key LMDB accepts with its default build settings, which `max_size_key()` generates
+/// keys around.
+pub const LMDB_MAX_KEY_SIZE: usize = 511;
 
 macro_rules! prop_compose_single_multi {
     (
@@ -472,3 +475,249 @@ use db_map_test::TestValue;"#, r#"
     { TestValue::from(slice) },
     test_keys_and_values
 }
+
+/// One operation in a sequence generated by [`key_value_ops`] or [`namespaced_ops`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Insert a value for a key from the sequence's small key pool. The key may already be
+    /// present, or may have been removed earlier in the sequence.
+    Insert(Vec<u8>, Vec<u8>),
+    /// Replace the value of a key that is present.
+    Overwrite(Vec<u8>, Vec<u8>),
+    /// Remove a key that is present.
+    Remove(Vec<u8>),
+}
+
+prop_compose! {
+    /// A proptest strategy for generating a sequence of `min` to `max` operations that keep
+    /// reusing the same few keys.
+    ///
+    /// Every key comes from a pool of one to four random keys, so keys are inserted, overwritten,
+    /// removed, and inserted again many times within one sequence. [`Op::Overwrite`] and
+    /// [`Op::Remove`] only ever target keys that are present at that point in the sequence.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_test::strategy::{key_value_ops, Op};
+    /// use proptest::prelude::*;
+    /// use std::collections::BTreeSet;
+    ///
+    /// proptest! {
+    ///     fn key_value_ops_test(ops in key_value_ops(1, 20)) {
+    ///         prop_assert!(!ops.is_empty() && ops.len() <= 20);
+    ///         let mut present = BTreeSet::new();
+    ///         for op in ops {
+    ///             match op {
+    ///                 Op::Insert(key, _) => { present.insert(key); }
+    ///                 Op::Overwrite(key, _) => prop_assert!(present.contains(&key)),
+    ///                 Op::Remove(key) => prop_assert!(present.remove(&key)),
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// // Run the proptest.
+    /// key_value_ops_test();
+    /// ```
+    pub fn key_value_ops(min: usize, max: usize)(
+        pool in proptest::collection::vec(random_key(), 1..=4),
+        choices in proptest::collection::vec(
+            (0_u8..3, any::<proptest::sample::Index>(), random_value()),
+            min..=max,
+        ),
+    ) -> Vec<Op> {
+        let mut present: Vec<Vec<u8>> = Vec::new();
+        choices.into_iter()
+            .map(|(kind, index, value)| next_op(&pool, &mut present, kind, index, value))
+            .collect()
+    }
+}
+
+/// Turn one random choice into an [`Op`] on a key from `pool`, keeping `present` up to date.
+fn next_op(
+    pool: &[Vec<u8>],
+    present: &mut Vec<Vec<u8>>,
+    kind: u8,
+    index: proptest::sample::Index,
+    value: Vec<u8>,
+) -> Op {
+    match kind {
+        1 if !present.is_empty() => Op::Overwrite(index.get(present).clone(), value),
+        2 if !present.is_empty() => Op::Remove(present.remove(index.index(present.len()))),
+        _ => {
+            let key = index.get(pool).clone();
+            if !present.contains(&key) {
+                present.push(key.clone());
+            }
+            Op::Insert(key, value)
+        }
+    }
+}
+
+prop_compose! {
+    /// A proptest strategy for generating a sequence of `min` to `max` operations, each tagged
+    /// with the namespace (`0` or `1`) it applies to.
+    ///
+    /// Both namespaces draw their keys from the same pool of one to four random keys, so the
+    /// same key is usually written in both, which is what isolation tests need. As with
+    /// [`key_value_ops`], [`Op::Overwrite`] and [`Op::Remove`] only target keys that are present
+    /// in their own namespace at that point in the sequence.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_test::strategy::{namespaced_ops, Op};
+    /// use proptest::prelude::*;
+    /// use std::collections::BTreeSet;
+    ///
+    /// proptest! {
+    ///     fn namespaced_ops_test(ops in namespaced_ops(1, 20)) {
+    ///         prop_assert!(!ops.is_empty() && ops.len() <= 20);
+    ///         let mut present = [BTreeSet::new(), BTreeSet::new()];
+    ///         for (namespace, op) in ops {
+    ///             prop_assert!(namespace < 2);
+    ///             let present = &mut present[namespace];
+    ///             match op {
+    ///                 Op::Insert(key, _) => { present.insert(key); }
+    ///                 Op::Overwrite(key, _) => prop_assert!(present.contains(&key)),
+    ///                 Op::Remove(key) => prop_assert!(present.remove(&key)),
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// // Run the proptest.
+    /// namespaced_ops_test();
+    /// ```
+    pub fn namespaced_ops(min: usize, max: usize)(
+        pool in proptest::collection::vec(random_key(), 1..=4),
+        choices in proptest::collection::vec(
+            (0_usize..2, 0_u8..3, any::<proptest::sample::Index>(), random_value()),
+            min..=max,
+        ),
+    ) -> Vec<(usize, Op)> {
+        let mut present: [Vec<Vec<u8>>; 2] = Default::default();
+        choices.into_iter()
+            .map(|(namespace, kind, index, value)| {
+                (namespace, next_op(&pool, &mut present[namespace], kind, index, value))
+            })
+            .collect()
+    }
+}
+
+prop_compose! {
+    /// A proptest strategy for generating random keys around [`LMDB_MAX_KEY_SIZE`].
+    ///
+    /// Most keys are within a few bytes of the limit on either side, and the rest are well over
+    /// it, up to four times the limit.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_test::{strategy::max_size_key, LMDB_MAX_KEY_SIZE};
+    /// use proptest::prelude::*;
+    ///
+    /// proptest! {
+    ///     fn max_size_key_test(key in max_size_key()) {
+    ///         prop_assert!(key.len() >= LMDB_MAX_KEY_SIZE - 4);
+    ///         prop_assert!(key.len() <= 4 * LMDB_MAX_KEY_SIZE);
+    ///     }
+    /// }
+    ///
+    /// // Run the proptest.
+    /// max_size_key_test();
+    /// ```
+    pub fn max_size_key()(key in prop_oneof![
+        4 => proptest::collection::vec(any::<u8>(), (LMDB_MAX_KEY_SIZE - 4)..=(LMDB_MAX_KEY_SIZE + 4)),
+        1 => proptest::collection::vec(any::<u8>(), (LMDB_MAX_KEY_SIZE + 5)..=(4 * LMDB_MAX_KEY_SIZE)),
+    ]) -> Vec<u8> {
+        key
+    }
+}
+
+prop_compose! {
+    /// A proptest strategy for generating short key prefixes to test moving entries between.
+    ///
+    /// The prefixes are up to three bytes, each `a` or `b`, so two of them are often the same or
+    /// one is under the other. An empty prefix is included.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_test::strategy::key_prefix;
+    /// use proptest::prelude::*;
+    ///
+    /// proptest! {
+    ///     fn key_prefix_test(prefix in key_prefix()) {
+    ///         prop_assert!(prefix.len() <= 3);
+    ///         prop_assert!(prefix.iter().all(|&b| b == b'a' || b == b'b'));
+    ///     }
+    /// }
+    ///
+    /// // Run the proptest.
+    /// key_prefix_test();
+    /// ```
+    pub fn key_prefix()(prefix in proptest::collection::vec(prop_oneof![Just(b'a'), Just(b'b')], 0..=3)) -> Vec<u8> {
+        prefix
+    }
+}
+
+prop_compose! {
+    /// A proptest strategy for generating `count` keys drawn from `universe` distinct keys with a
+    /// Zipf distribution, so that a few hot keys make up most of the draws.
+    ///
+    /// Key `k` (counting from zero) is drawn with probability proportional to `1 / (k + 1)^s`,
+    /// so `s` of zero is uniform and larger values are more skewed; around 1 is typical of real
+    /// access patterns. Each key is its rank as a big-endian `u32`, so the hottest key is
+    /// `[0, 0, 0, 0]`. This is meant for exercising caches, like the `Cached` wrapper, with
+    /// realistic hit rates.
+    ///
+    /// # Panics
+    ///
+    /// The strategy panics if `universe` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_test::strategy::zipf_keys;
+    /// use proptest::prelude::*;
+    /// use std::collections::HashMap;
+    ///
+    /// proptest! {
+    ///     fn zipf_keys_test(keys in zipf_keys(100, 1.2, 2000)) {
+    ///         prop_assert_eq!(keys.len(), 2000);
+    ///         let mut counts = HashMap::new();
+    ///         for key in &keys {
+    ///             prop_assert!(u32::from_be_bytes(key[..].try_into().unwrap()) < 100);
+    ///             *counts.entry(key).or_insert(0) += 1;
+    ///         }
+    ///         // The hottest key turns up far more often than the coldest one drawn.
+    ///         let most = counts.values().max().unwrap();
+    ///         let least = counts.values().min().unwrap();
+    ///         prop_assert!(*most > 10 * least, "most {} least {}", most, least);
+    ///         prop_assert!(counts[&0_u32.to_be_bytes().to_vec()] > 400);
+    ///     }
+    /// }
+    ///
+    /// // Run the proptest.
+    /// zipf_keys_test();
+    /// ```
+    pub fn zipf_keys(universe: usize, s: f64, count: usize)(
+        samples in proptest::collection::vec(0.0..1.0_f64, count..=count),
+    ) -> Vec<Vec<u8>> {
+        assert!(universe > 0, "zipf_keys needs at least one key to draw from");
+        let mut cumulative: Vec<f64> = (1..=universe)
+            .scan(0.0, |total, rank| {
+                *total += (rank as f64).powf(-s);
+                Some(*total)
+            })
+            .collect();
+        let total = cumulative[universe - 1];
+        cumulative.iter_mut().for_each(|weight| *weight /= total);
+        samples.into_iter().map(|sample| {
+            let rank = cumulative.partition_point(|&weight| weight <= sample).min(universe - 1);
+            (rank as u32).to_be_bytes().to_vec()
+        }).collect()
+    }
+}
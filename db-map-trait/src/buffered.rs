@@ -0,0 +1,380 @@
+//! # Write coalescing for the `DBMap` trait.
+//!
+//! The [`Buffered`] wrapper collects inserts and removes in memory and writes them to the wrapped
+//! database in one batch, so a burst of small writes costs one
+//! [`batch_insert`][DBMap::batch_insert] instead of one transaction each.
+use crate::{
+    observe::{notify, Observer},
+    Capabilities, DBMap, Page, Result,
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::Bound,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+
+/// The writes waiting to be flushed: `Some(value)` for an insert and `None` for a remove.
+type Pending = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+/// The state shared between clones of a [`Buffered`].
+struct Shared<M: DBMap> {
+    inner: M,
+    pending: Mutex<Pending>,
+    max_pending: usize,
+    flush_on_drop: AtomicBool,
+}
+
+impl<M: DBMap> Shared<M> {
+    fn lock(&self) -> MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Write the pending writes to the inner database.
+    ///
+    /// The buffer is only emptied once everything is written, so a failed flush can be retried.
+    fn flush_locked(&self, pending: &mut Pending) -> Result<()> {
+        self.inner.batch_insert(pending.iter()
+            .filter_map(|(key, value)| value.as_ref().map(|value| (key, value))))?;
+        for (key, _) in pending.iter().filter(|(_, value)| value.is_none()) {
+            // Removing through `update` doesn't fail when the key is already missing.
+            self.inner.update(key, |_| None)?;
+        }
+        pending.clear();
+        Ok(())
+    }
+
+    /// Buffer a write, flushing if the buffer is full.
+    fn buffer(&self, pending: &mut Pending, key: &[u8], value: Option<Vec<u8>>) -> Result<()> {
+        pending.insert(key.to_vec(), value);
+        match pending.len() >= self.max_pending {
+            true => self.flush_locked(pending),
+            false => Ok(()),
+        }
+    }
+}
+
+impl<M: DBMap> Drop for Shared<M> {
+    fn drop(&mut self) {
+        if self.flush_on_drop.load(Ordering::Relaxed) {
+            let mut pending = std::mem::take(self.pending.get_mut()
+                .unwrap_or_else(PoisonError::into_inner));
+            // There is no way to report an error from `drop`.
+            let _ = self.flush_locked(&mut pending);
+        }
+    }
+}
+
+/// A `DBMap` wrapper that buffers writes and flushes them to the inner database in batches.
+///
+/// [`insert`][DBMap::insert], [`remove`][DBMap::remove], and everything built on them only
+/// change an in-memory buffer, where the last write to each key wins. When the buffer holds
+/// `max_pending` keys, or when [`flush`][Buffered::flush] is called, the buffered inserts are
+/// written with one [`batch_insert`][DBMap::batch_insert] and then the removes are applied.
+///
+/// Reads look in the buffer before the inner database, so every write is visible through the
+/// wrapper (and its clones, which share the buffer) as soon as it is made. Writes made directly
+/// to the inner database can be hidden by buffered writes to the same keys. Scans and
+/// [`len`][DBMap::len] flush the buffer first and then read the inner database.
+///
+/// # Data loss
+///
+/// Buffered writes that haven't been flushed are lost when the last clone is dropped, unless
+/// [`flush_on_drop`][Buffered::flush_on_drop] is enabled. Even then, an error from that final
+/// flush can't be reported, so call `flush` when the writes have to be durable.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{Buffered, DBMap};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let inner = BTreeMapDB::open();
+/// let db = Buffered::new(inner.clone(), 3);
+///
+/// // The writes are visible through the wrapper, but not yet in the inner database.
+/// db.insert(b"a", b"1").unwrap();
+/// db.insert(b"b", b"2").unwrap();
+/// db.insert(b"a", b"3").unwrap();
+/// assert_eq!(db.get(b"a").unwrap(), Some(b"3".to_vec()));
+/// assert_eq!(db.pending(), 2);
+/// assert!(inner.is_empty().unwrap());
+///
+/// // The third buffered key fills the buffer and flushes it.
+/// db.insert(b"c", b"4").unwrap();
+/// assert_eq!(db.pending(), 0);
+/// assert_eq!(inner.get(b"a").unwrap(), Some(b"3".to_vec()));
+/// assert_eq!(inner.len().unwrap(), 3);
+///
+/// // Removes are buffered too.
+/// db.remove(b"b").unwrap();
+/// assert!(db.get(b"b").unwrap().is_none());
+/// assert!(inner.get(b"b").unwrap().is_some());
+/// db.flush().unwrap();
+/// assert!(inner.get(b"b").unwrap().is_none());
+/// ```
+pub struct Buffered<M: DBMap> {
+    shared: Arc<Shared<M>>,
+    observe: Option<Observer>,
+}
+
+impl<M: DBMap> Buffered<M> {
+    /// Wrap `inner` so that writes are buffered and flushed once `max_pending` keys have
+    /// buffered writes.
+    ///
+    /// A `max_pending` of 0 or 1 flushes every write straight away.
+    pub fn new(inner: M, max_pending: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                inner,
+                pending: Mutex::default(),
+                max_pending,
+                flush_on_drop: AtomicBool::new(false),
+            }),
+            observe: None,
+        }
+    }
+
+    /// Choose whether the buffer is flushed when the last clone is dropped (off by default).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{Buffered, DBMap};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let inner = BTreeMapDB::open();
+    ///
+    /// // Without flush_on_drop, unflushed writes are lost.
+    /// let db = Buffered::new(inner.clone(), 100);
+    /// db.insert(b"lost", b"value").unwrap();
+    /// drop(db);
+    /// assert!(inner.get(b"lost").unwrap().is_none());
+    ///
+    /// // With it, they are written when the last clone is dropped.
+    /// let db = Buffered::new(inner.clone(), 100).flush_on_drop(true);
+    /// db.insert(b"kept", b"value").unwrap();
+    /// let clone = db.clone();
+    /// drop(db);
+    /// assert!(inner.get(b"kept").unwrap().is_none());
+    /// drop(clone);
+    /// assert_eq!(inner.get(b"kept").unwrap(), Some(b"value".to_vec()));
+    /// ```
+    pub fn flush_on_drop(self, flush: bool) -> Self {
+        self.shared.flush_on_drop.store(flush, Ordering::Relaxed);
+        self
+    }
+
+    /// Report every read through this handle to `observer` as a `"buffered"` event, where a hit
+    /// means the key had a buffered write.
+    ///
+    /// See the [`observe`][crate::observe] module for an example.
+    pub fn with_observer(self, observer: Observer) -> Self {
+        Self {
+            observe: Some(observer),
+            ..self
+        }
+    }
+
+    /// Get a reference to the wrapped database.
+    pub fn inner(&self) -> &M {
+        &self.shared.inner
+    }
+
+    /// Get the number of buffered writes that haven't been flushed.
+    ///
+    /// Writes to the same key replace each other in the buffer, so this is the number of keys
+    /// with buffered writes. A flush is triggered when it reaches `max_pending`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{Buffered, DBMap};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = Buffered::new(BTreeMapDB::open(), 100);
+    /// assert_eq!((db.pending(), db.pending_bytes()), (0, 0));
+    ///
+    /// db.insert(b"a", b"12345").unwrap();
+    /// db.insert(b"bb", b"1").unwrap();
+    /// assert_eq!((db.pending(), db.pending_bytes()), (2, 6 + 3));
+    ///
+    /// // A second write to a key replaces the first, and a remove only holds its key.
+    /// db.insert(b"a", b"1").unwrap();
+    /// db.remove(b"ccc").unwrap();
+    /// assert_eq!((db.pending(), db.pending_bytes()), (3, 2 + 3 + 3));
+    ///
+    /// db.flush().unwrap();
+    /// assert_eq!((db.pending(), db.pending_bytes()), (0, 0));
+    /// assert_eq!(db.inner().len().unwrap(), 2);
+    /// ```
+    pub fn pending(&self) -> usize {
+        self.shared.lock().len()
+    }
+
+    /// Get the total size of the buffered writes that haven't been flushed: the key and value
+    /// bytes of each buffered insert, and the key bytes of each buffered remove.
+    ///
+    /// See [`pending`][Buffered::pending] for an example.
+    pub fn pending_bytes(&self) -> usize {
+        self.shared.lock().iter()
+            .map(|(key, value)| key.len() + value.as_ref().map_or(0, Vec::len))
+            .sum()
+    }
+
+    /// Write every buffered write to the inner database.
+    ///
+    /// If this fails, the writes stay in the buffer and the flush can be tried again.
+    pub fn flush(&self) -> Result<()> {
+        self.shared.flush_locked(&mut self.shared.lock())
+    }
+}
+
+impl<M: DBMap> Clone for Buffered<M> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            observe: self.observe.clone(),
+        }
+    }
+}
+
+impl<M: DBMap + fmt::Debug> fmt::Debug for Buffered<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Buffered")
+            .field("inner", &self.shared.inner)
+            .field("pending", &self.pending())
+            .field("max_pending", &self.shared.max_pending)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M: DBMap> DBMap for Buffered<M> {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        // Hold the lock while reading the inner database so that a flush can't run in between.
+        let pending = self.shared.lock();
+        let buffered = pending.get(key.as_ref());
+        notify(&self.observe, "buffered", "get", key.as_ref(), buffered.is_some());
+        match buffered {
+            Some(value) => Ok(value.as_deref().map(mapper)),
+            None => self.shared.inner.get_map(key, mapper),
+        }
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        self.shared.buffer(&mut self.shared.lock(), key.as_ref(), Some(value.as_ref().to_vec()))
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let key = key.as_ref();
+        let mut pending = self.shared.lock();
+        let old = match pending.get(key) {
+            Some(old) => old.as_deref().map(mapper),
+            None => self.shared.inner.get_map(key, mapper)?,
+        };
+        self.shared.buffer(&mut pending, key, Some(value.as_ref().to_vec()))?;
+        Ok(old)
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        self.shared.buffer(&mut self.shared.lock(), key.as_ref(), None)
+    }
+
+    /// The new value is buffered like any other write. The update is atomic with respect to
+    /// other operations through the wrapper, but not to writes made directly to the inner
+    /// database.
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let key = key.as_ref();
+        let mut pending = self.shared.lock();
+        let new_value = match pending.get(key) {
+            Some(current) => f(current.as_deref()),
+            None => f(self.shared.inner.get(key)?.as_deref()),
+        };
+        self.shared.buffer(&mut pending, key, new_value)
+    }
+
+    /// Flush the buffer and scan the inner database.
+    fn try_for_each<F>(&self, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.flush()?;
+        self.shared.inner.try_for_each(f)
+    }
+
+    /// Flush the buffer and scan the inner database.
+    fn try_scan_prefix<P, F>(&self, prefix: P, f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.flush()?;
+        self.shared.inner.try_scan_prefix(prefix, f)
+    }
+
+    /// Flush the buffer and scan the inner database.
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.flush()?;
+        self.shared.inner.try_scan_range(start, end, f)
+    }
+
+    /// Flush the buffer and count the entries in the inner database.
+    fn count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        self.flush()?;
+        self.shared.inner.count_range(start, end)
+    }
+
+    /// Flush the buffer and read the page from the inner database.
+    fn scan_page<P: AsRef<[u8]>>(&self, prefix: P, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        self.flush()?;
+        self.shared.inner.scan_page(prefix, start_after, limit)
+    }
+
+    /// Flush the buffer and count the entries in the inner database.
+    fn len(&self) -> Result<usize> {
+        self.flush()?;
+        self.shared.inner.len()
+    }
+
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        self.shared.inner.prepare_range(start, end)
+    }
+
+    /// Clear the inner database and discard the buffered writes, which would all be cleared
+    /// anyway.
+    fn clear(&self) -> Result<()> {
+        let mut pending = self.shared.lock();
+        self.shared.inner.clear()?;
+        pending.clear();
+        Ok(())
+    }
+
+    /// The inner database's capabilities, except that batches aren't atomic: a batch is buffered
+    /// one write at a time, so a full buffer can flush part of it.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            atomic_batch: false,
+            ..self.shared.inner.capabilities()
+        }
+    }
+}
@@ -0,0 +1,556 @@
+//! # Read caching for the `DBMap` trait.
+//!
+//! The [`Cached`] wrapper keeps recently read values in memory so that repeated reads of the same
+//! keys don't go to the wrapped database. A [`CachePolicy`] decides which entry is evicted when
+//! the cache is full and whether an entry has expired. Three policies are provided:
+//!
+//! * [`Lru`] evicts the least recently used entry.
+//! * [`Lfu`] evicts the least frequently used entry, and the least recently used of those on a
+//!   tie.
+//! * [`Ttl`] expires entries a fixed time after they were cached, and evicts the oldest entry
+//!   when the cache is full.
+use crate::{
+    observe::{notify, Observer},
+    Capabilities, DBMap, Page, Result,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    ops::Bound,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Decide which entries a [`Cached`] wrapper evicts and when they expire.
+///
+/// The cache calls the `on_` methods to tell the policy which keys it holds and how they are
+/// used, and calls [`victim`][CachePolicy::victim] when it is full and a new key has to be
+/// cached.
+pub trait CachePolicy {
+    /// A key was added to the cache.
+    fn on_insert(&mut self, key: &[u8]);
+
+    /// A cached key was read.
+    fn on_access(&mut self, key: &[u8]);
+
+    /// A key was removed from the cache.
+    fn on_remove(&mut self, key: &[u8]);
+
+    /// Pick the cached key to evict next, or `None` if the policy isn't tracking any keys.
+    fn victim(&mut self) -> Option<Vec<u8>>;
+
+    /// Check whether a cached key has expired. Expired entries are treated as missing.
+    ///
+    /// The default implementation never expires entries.
+    fn is_expired(&self, _key: &[u8]) -> bool {
+        false
+    }
+}
+
+/// Evict the least recently used entry.
+#[derive(Clone, Debug, Default)]
+pub struct Lru {
+    tick: u64,
+    last_used: HashMap<Vec<u8>, u64>,
+    by_use: BTreeMap<u64, Vec<u8>>,
+}
+
+impl Lru {
+    /// Create an empty `Lru` policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn touch(&mut self, key: &[u8]) {
+        self.tick += 1;
+        if let Some(old) = self.last_used.insert(key.to_vec(), self.tick) {
+            self.by_use.remove(&old);
+        }
+        self.by_use.insert(self.tick, key.to_vec());
+    }
+}
+
+impl CachePolicy for Lru {
+    fn on_insert(&mut self, key: &[u8]) {
+        self.touch(key);
+    }
+
+    fn on_access(&mut self, key: &[u8]) {
+        self.touch(key);
+    }
+
+    fn on_remove(&mut self, key: &[u8]) {
+        if let Some(tick) = self.last_used.remove(key) {
+            self.by_use.remove(&tick);
+        }
+    }
+
+    fn victim(&mut self) -> Option<Vec<u8>> {
+        self.by_use.values().next().cloned()
+    }
+}
+
+/// Evict the least frequently used entry, breaking ties by evicting the least recently used.
+///
+/// An entry's use count starts at one when it is cached and is forgotten when it is evicted.
+#[derive(Clone, Debug, Default)]
+pub struct Lfu {
+    tick: u64,
+    uses: HashMap<Vec<u8>, (u64, u64)>,
+    by_uses: BTreeSet<(u64, u64, Vec<u8>)>,
+}
+
+impl Lfu {
+    /// Create an empty `Lfu` policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&mut self, key: &[u8], count: u64) {
+        self.tick += 1;
+        self.uses.insert(key.to_vec(), (count, self.tick));
+        self.by_uses.insert((count, self.tick, key.to_vec()));
+    }
+}
+
+impl CachePolicy for Lfu {
+    fn on_insert(&mut self, key: &[u8]) {
+        self.on_remove(key);
+        self.record(key, 1);
+    }
+
+    fn on_access(&mut self, key: &[u8]) {
+        if let Some((count, tick)) = self.uses.remove(key) {
+            self.by_uses.remove(&(count, tick, key.to_vec()));
+            self.record(key, count + 1);
+        }
+    }
+
+    fn on_remove(&mut self, key: &[u8]) {
+        if let Some((count, tick)) = self.uses.remove(key) {
+            self.by_uses.remove(&(count, tick, key.to_vec()));
+        }
+    }
+
+    fn victim(&mut self) -> Option<Vec<u8>> {
+        self.by_uses.iter().next().map(|(_, _, key)| key.clone())
+    }
+}
+
+/// The clock used by [`Ttl`].
+pub type Clock = Arc<dyn Fn() -> Instant + Send + Sync>;
+
+/// Expire entries a fixed time after they were cached, and evict the oldest entry when the cache
+/// is full.
+///
+/// Reads don't extend an entry's lifetime, but writing the key through the cache does.
+#[derive(Clone)]
+pub struct Ttl {
+    ttl: Duration,
+    clock: Clock,
+    cached_at: HashMap<Vec<u8>, Instant>,
+    by_age: BTreeSet<(Instant, Vec<u8>)>,
+}
+
+impl Ttl {
+    /// Expire entries `ttl` after they were cached, using the system clock.
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_clock(ttl, Arc::new(Instant::now))
+    }
+
+    /// Expire entries `ttl` after they were cached, using `clock` to tell the time.
+    ///
+    /// This is mostly useful for tests, which can use a clock they control.
+    pub fn with_clock(ttl: Duration, clock: Clock) -> Self {
+        Self {
+            ttl,
+            clock,
+            cached_at: HashMap::new(),
+            by_age: BTreeSet::new(),
+        }
+    }
+}
+
+impl fmt::Debug for Ttl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ttl")
+            .field("ttl", &self.ttl)
+            .field("cached", &self.cached_at.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CachePolicy for Ttl {
+    fn on_insert(&mut self, key: &[u8]) {
+        self.on_remove(key);
+        let now = (self.clock)();
+        self.cached_at.insert(key.to_vec(), now);
+        self.by_age.insert((now, key.to_vec()));
+    }
+
+    fn on_access(&mut self, _key: &[u8]) {}
+
+    fn on_remove(&mut self, key: &[u8]) {
+        if let Some(cached_at) = self.cached_at.remove(key) {
+            self.by_age.remove(&(cached_at, key.to_vec()));
+        }
+    }
+
+    fn victim(&mut self) -> Option<Vec<u8>> {
+        self.by_age.iter().next().map(|(_, key)| key.clone())
+    }
+
+    fn is_expired(&self, key: &[u8]) -> bool {
+        self.cached_at.get(key)
+            .is_some_and(|&cached_at| (self.clock)().saturating_duration_since(cached_at) >= self.ttl)
+    }
+}
+
+/// The cached values and the policy that manages them.
+struct Cache<P> {
+    entries: HashMap<Vec<u8>, Vec<u8>>,
+    policy: P,
+    capacity: usize,
+}
+
+impl<P: CachePolicy> Cache<P> {
+    /// Get a cached value, dropping it if it has expired.
+    fn get(&mut self, key: &[u8]) -> Option<&[u8]> {
+        if !self.entries.contains_key(key) {
+            return None;
+        }
+        if self.policy.is_expired(key) {
+            self.remove(key);
+            return None;
+        }
+        self.policy.on_access(key);
+        self.entries.get(key).map(|v| v.as_slice())
+    }
+
+    /// Cache a value, first evicting entries to make room for it if the key is new.
+    ///
+    /// Evicting before inserting means the new entry is never its own victim.
+    fn insert(&mut self, key: &[u8], value: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.entries.contains_key(key) {
+            while self.entries.len() >= self.capacity {
+                match self.policy.victim() {
+                    Some(victim) => self.remove(&victim),
+                    // The policy lost track of its keys, so start over.
+                    None => self.entries.clear(),
+                }
+            }
+        }
+        self.entries.insert(key.to_vec(), value);
+        self.policy.on_insert(key);
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.entries.remove(key);
+        self.policy.on_remove(key);
+    }
+
+    /// Drop every cached value.
+    fn clear(&mut self) {
+        let keys: Vec<_> = self.entries.keys().cloned().collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    /// Record the result of a write: cache the new value, or drop the key if it was removed.
+    fn store(&mut self, key: &[u8], value: Option<Vec<u8>>) {
+        match value {
+            Some(value) => self.insert(key, value),
+            None => self.remove(key),
+        }
+    }
+}
+
+/// A `DBMap` wrapper that caches values read from the inner database.
+///
+/// Reads are served from the cache when possible. Writes go straight through to the inner
+/// database and update the cached value, so the cache never holds stale data written through the
+/// wrapper (or its clones, which share the cache). Writes made directly to the inner database are
+/// not seen until the cached entry is evicted or expires. Scans and [`len`][DBMap::len] always go
+/// to the inner database.
+///
+/// The cache lock is held while the inner database is read or written, so operations through
+/// the wrapper are serialized.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{cached::{Cached, Lru}, DBMap};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let db = Cached::new(BTreeMapDB::open(), 2);
+/// for key in [b"a", b"b", b"c"] {
+///     db.insert(key, key).unwrap();
+/// }
+/// // Only the two most recently used keys are cached.
+/// assert!(!db.is_cached(b"a"));
+/// assert!(db.is_cached(b"b") && db.is_cached(b"c"));
+///
+/// // Reading "b" makes "c" the least recently used, so caching "a" evicts "c".
+/// assert_eq!(db.get(b"b").unwrap(), Some(b"b".to_vec()));
+/// assert_eq!(db.get(b"a").unwrap(), Some(b"a".to_vec()));
+/// assert!(db.is_cached(b"a") && db.is_cached(b"b"));
+/// assert!(!db.is_cached(b"c"));
+/// ```
+pub struct Cached<M, P = Lru> {
+    inner: M,
+    cache: Arc<Mutex<Cache<P>>>,
+    observe: Option<Observer>,
+}
+
+impl<M: DBMap> Cached<M, Lru> {
+    /// Wrap `inner` with a least-recently-used cache of up to `capacity` entries.
+    pub fn new(inner: M, capacity: usize) -> Self {
+        Self::with_policy(inner, capacity, Lru::new())
+    }
+}
+
+impl<M: DBMap, P: CachePolicy> Cached<M, P> {
+    /// Wrap `inner` with a cache of up to `capacity` entries managed by `policy`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{cached::{Cached, Lfu, Ttl}, DBMap};
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+    ///
+    /// // LFU keeps the most used keys.
+    /// let db = Cached::with_policy(BTreeMapDB::open(), 2, Lfu::new());
+    /// db.insert(b"hot", b"1").unwrap();
+    /// db.insert(b"warm", b"2").unwrap();
+    /// for _ in 0..3 {
+    ///     db.get(b"hot").unwrap();
+    /// }
+    /// db.get(b"warm").unwrap();
+    /// db.insert(b"cold", b"3").unwrap();
+    /// // "warm" was used less than "hot", so it was evicted to make room.
+    /// assert!(db.is_cached(b"hot") && db.is_cached(b"cold"));
+    /// assert!(!db.is_cached(b"warm"));
+    ///
+    /// // TTL expires entries, here with a clock the test controls.
+    /// let start = Instant::now();
+    /// let elapsed = Arc::new(Mutex::new(Duration::ZERO));
+    /// let clock = {
+    ///     let elapsed = elapsed.clone();
+    ///     Arc::new(move || start + *elapsed.lock().unwrap())
+    /// };
+    /// let db = Cached::with_policy(BTreeMapDB::open(), 10, Ttl::with_clock(Duration::from_secs(60), clock));
+    /// db.insert(b"old", b"1").unwrap();
+    /// *elapsed.lock().unwrap() = Duration::from_secs(30);
+    /// db.insert(b"new", b"2").unwrap();
+    /// *elapsed.lock().unwrap() = Duration::from_secs(75);
+    /// // "old" was cached 75 seconds ago and has expired, but it is read again from the database.
+    /// assert!(!db.is_cached(b"old"));
+    /// assert!(db.is_cached(b"new"));
+    /// assert_eq!(db.get(b"old").unwrap(), Some(b"1".to_vec()));
+    /// assert!(db.is_cached(b"old"));
+    /// ```
+    pub fn with_policy(inner: M, capacity: usize, policy: P) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(Mutex::new(Cache {
+                entries: HashMap::new(),
+                policy,
+                capacity,
+            })),
+            observe: None,
+        }
+    }
+
+    /// Report every read through this handle to `observer` as a `"cached"` event, where a hit
+    /// means the value came from the cache.
+    ///
+    /// See the [`observe`][crate::observe] module for an example.
+    pub fn with_observer(self, observer: Observer) -> Self {
+        Self {
+            observe: Some(observer),
+            ..self
+        }
+    }
+
+    /// Get a reference to the wrapped database.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Check whether a key has a cached value that hasn't expired.
+    ///
+    /// This doesn't count as a use of the key.
+    pub fn is_cached<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        let cache = self.lock();
+        let key = key.as_ref();
+        cache.entries.contains_key(key) && !cache.policy.is_expired(key)
+    }
+
+    /// Drop every cached value.
+    pub fn clear_cache(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cache<P>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<M: Clone, P> Clone for Cached<M, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            cache: self.cache.clone(),
+            observe: self.observe.clone(),
+        }
+    }
+}
+
+impl<M: fmt::Debug, P> fmt::Debug for Cached<M, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cached")
+            .field("inner", &self.inner)
+            .field("policy", &std::any::type_name::<P>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M: DBMap, P: CachePolicy> DBMap for Cached<M, P> {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let key = key.as_ref();
+        let mut cache = self.lock();
+        if let Some(value) = cache.get(key) {
+            notify(&self.observe, "cached", "get", key, true);
+            return Ok(Some(mapper(value)));
+        }
+        notify(&self.observe, "cached", "get", key, false);
+        match self.inner.get(key)? {
+            Some(value) => {
+                let result = mapper(&value);
+                cache.insert(key, value);
+                Ok(Some(result))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        let mut cache = self.lock();
+        self.inner.insert(key, value)?;
+        cache.insert(key, value.to_vec());
+        Ok(())
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        let mut cache = self.lock();
+        let old = self.inner.fetch_and_replace_map(key, value, mapper)?;
+        cache.insert(key, value.to_vec());
+        Ok(old)
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        let key = key.as_ref();
+        let mut cache = self.lock();
+        // Drop the cached value first, in case the inner database fails after removing the key.
+        cache.remove(key);
+        self.inner.remove(key)
+    }
+
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let key = key.as_ref();
+        let mut cache = self.lock();
+        cache.remove(key);
+        let mut new_value = None;
+        self.inner.update(key, |existing| {
+            new_value = f(existing);
+            new_value.clone()
+        })?;
+        cache.store(key, new_value);
+        Ok(())
+    }
+
+    /// Write the batch to the inner database and drop the cached values for its keys.
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let items: Vec<_> = items.into_iter().collect();
+        let mut cache = self.lock();
+        for (key, _) in &items {
+            cache.remove(key.as_ref());
+        }
+        self.inner.batch_insert(items)
+    }
+
+    fn try_for_each<F>(&self, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_for_each(f)
+    }
+
+    fn try_scan_prefix<Q, F>(&self, prefix: Q, f: F) -> Result<()>
+        where
+            Q: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_scan_prefix(prefix, f)
+    }
+
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_scan_range(start, end, f)
+    }
+
+    fn count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        self.inner.count_range(start, end)
+    }
+
+    fn scan_page<Q: AsRef<[u8]>>(&self, prefix: Q, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        self.inner.scan_page(prefix, start_after, limit)
+    }
+
+    fn len(&self) -> Result<usize> {
+        self.inner.len()
+    }
+
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        self.inner.prepare_range(start, end)
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut cache = self.lock();
+        // Drop the cached values first, in case the inner database fails after removing some keys.
+        cache.clear();
+        self.inner.clear()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
@@ -0,0 +1,136 @@
+//! # Ready-made codecs for [`Typed`][crate::Typed].
+//!
+//! Each codec encodes any `K` and `V` that implement `serde::Serialize` and
+//! `serde::de::DeserializeOwned`, and is enabled by the feature with the same name:
+//!
+//! * `BincodeCodec` (feature `bincode`) uses `bincode` 1.x with its default options.
+//! * `PostcardCodec` (feature `postcard`) uses `postcard`, which is more compact.
+//!
+//! # Key ordering
+//!
+//! The databases keep keys in byte-lexicographic order, and neither format is designed to keep
+//! that order the same as the logical order of the keys. Bincode writes integers in
+//! little-endian order and postcard writes them as variable-length integers, so, for example,
+//! the encoded key for `256_u32` sorts before the one for `200_u32` under both. Range scans and
+//! `for_each` over encoded keys therefore do not follow the logical key order. Use a custom
+//! [`Codec`][crate::Codec] (for example, big-endian integers) when key order matters.
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+use crate::{Codec, Error, Result};
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+use std::marker::PhantomData;
+
+/// A [`Codec`] that encodes keys and values with [`bincode`].
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{codec::BincodeCodec, Typed};
+/// use db_map_btreemap::BTreeMapDB;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Point { x: i32, y: i32 }
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Shape { name: String, points: Vec<Point> }
+///
+/// let db: Typed<_, BincodeCodec<u64, Shape>> = Typed::new(BTreeMapDB::open());
+/// let triangle = Shape {
+///     name: "triangle".to_string(),
+///     points: vec![Point { x: 0, y: 0 }, Point { x: 4, y: 0 }, Point { x: 0, y: 3 }],
+/// };
+/// db.insert(&1, &triangle).unwrap();
+/// assert_eq!(db.get(&1).unwrap(), Some(triangle));
+///
+/// // Encoded keys do not sort in numeric order.
+/// use db_map_trait::Codec;
+/// type C = BincodeCodec<u32, ()>;
+/// assert!(C::encode_key(&256).unwrap() < C::encode_key(&200).unwrap());
+/// ```
+#[cfg(feature = "bincode")]
+#[derive(Debug)]
+pub struct BincodeCodec<K, V>(PhantomData<fn() -> (K, V)>);
+
+#[cfg(feature = "bincode")]
+impl<K, V> Codec for BincodeCodec<K, V>
+where
+    K: Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    type Key = K;
+    type Value = V;
+
+    fn encode_key(key: &K) -> Result<Vec<u8>> {
+        bincode::serialize(key).map_err(codec_error)
+    }
+
+    fn encode_value(value: &V) -> Result<Vec<u8>> {
+        bincode::serialize(value).map_err(codec_error)
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<V> {
+        bincode::deserialize(bytes).map_err(codec_error)
+    }
+}
+
+/// A [`Codec`] that encodes keys and values with [`postcard`].
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{codec::PostcardCodec, Typed};
+/// use db_map_btreemap::BTreeMapDB;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Point { x: i32, y: i32 }
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Shape { name: String, points: Vec<Point> }
+///
+/// let db: Typed<_, PostcardCodec<String, Shape>> = Typed::new(BTreeMapDB::open());
+/// let line = Shape {
+///     name: "line".to_string(),
+///     points: vec![Point { x: -1, y: -1 }, Point { x: 1, y: 1 }],
+/// };
+/// db.insert(&"line".to_string(), &line).unwrap();
+/// assert_eq!(db.get(&"line".to_string()).unwrap(), Some(line));
+///
+/// // Encoded keys do not sort in numeric order.
+/// use db_map_trait::Codec;
+/// type C = PostcardCodec<u32, ()>;
+/// assert!(C::encode_key(&256).unwrap() < C::encode_key(&200).unwrap());
+/// ```
+#[cfg(feature = "postcard")]
+#[derive(Debug)]
+pub struct PostcardCodec<K, V>(PhantomData<fn() -> (K, V)>);
+
+#[cfg(feature = "postcard")]
+impl<K, V> Codec for PostcardCodec<K, V>
+where
+    K: Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    type Key = K;
+    type Value = V;
+
+    fn encode_key(key: &K) -> Result<Vec<u8>> {
+        postcard::to_allocvec(key).map_err(codec_error)
+    }
+
+    fn encode_value(value: &V) -> Result<Vec<u8>> {
+        postcard::to_allocvec(value).map_err(codec_error)
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<V> {
+        postcard::from_bytes(bytes).map_err(codec_error)
+    }
+}
+
+/// Convert an encoding error into a database error.
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+fn codec_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> Error {
+    Error::DBError(err.into())
+}
@@ -0,0 +1,168 @@
+//! # Atomic counters stored in a `DBMap`.
+//!
+//! The [`AtomicCounter`] type keeps a `u64` under a single key and changes it with
+//! [`update`][DBMap::update], so every change is atomic.
+use crate::{decode_u64, DBMap, HexKey, Result};
+use std::fmt;
+
+/// A `u64` counter stored under one key of a `DBMap`.
+///
+/// The counter is stored as 8 big-endian bytes. A missing key counts as zero. Every change goes
+/// through [`update`][DBMap::update], so concurrent changes from any number of clones of the
+/// database (or threads sharing a counter) are never lost.
+///
+/// The arithmetic saturates: [`incr`][AtomicCounter::incr] stops at `u64::MAX` and
+/// [`decr`][AtomicCounter::decr] stops at zero rather than returning an error. If the key holds a
+/// value that is not 8 bytes long, it is left alone and
+/// [`Error::Corruption`][crate::Error::Corruption] is returned.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::AtomicCounter;
+/// use db_map_btreemap::BTreeMapDB;
+/// use std::{sync::Arc, thread};
+///
+/// let counter = Arc::new(AtomicCounter::new(BTreeMapDB::open(), b"requests"));
+///
+/// let threads: Vec<_> = (0..8).map(|_| {
+///     let counter = Arc::clone(&counter);
+///     thread::spawn(move || {
+///         for _ in 0..100 {
+///             counter.incr(1).unwrap();
+///         }
+///     })
+/// }).collect();
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+///
+/// assert_eq!(counter.get().unwrap(), 800);
+/// ```
+#[derive(Clone)]
+pub struct AtomicCounter<M> {
+    db: M,
+    key: Vec<u8>,
+}
+
+impl<M: DBMap> AtomicCounter<M> {
+    /// Create a counter stored under `key` in `db`.
+    pub fn new<K: AsRef<[u8]>>(db: M, key: K) -> Self {
+        Self {
+            db,
+            key: key.as_ref().to_vec(),
+        }
+    }
+
+    /// Get the key the counter is stored under.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Get the current value of the counter.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{AtomicCounter, DBMap};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// let counter = AtomicCounter::new(db.clone(), b"count");
+    ///
+    /// // A missing counter is zero.
+    /// assert_eq!(counter.get().unwrap(), 0);
+    ///
+    /// db.insert(b"count", 42_u64.to_be_bytes()).unwrap();
+    /// assert_eq!(counter.get().unwrap(), 42);
+    /// ```
+    pub fn get(&self) -> Result<u64> {
+        Ok(self.db.get_map(&self.key, |value| self.decode(value))?.transpose()?.unwrap_or(0))
+    }
+
+    /// Add `delta` to the counter, saturating at `u64::MAX`, and return the new value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::AtomicCounter;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let counter = AtomicCounter::new(BTreeMapDB::open(), b"count");
+    ///
+    /// assert_eq!(counter.incr(5).unwrap(), 5);
+    /// assert_eq!(counter.incr(u64::MAX).unwrap(), u64::MAX);
+    /// ```
+    pub fn incr(&self, delta: u64) -> Result<u64> {
+        self.apply(|value| value.saturating_add(delta))
+    }
+
+    /// Subtract `delta` from the counter, saturating at zero, and return the new value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::AtomicCounter;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let counter = AtomicCounter::new(BTreeMapDB::open(), b"count");
+    ///
+    /// counter.incr(5).unwrap();
+    /// assert_eq!(counter.decr(3).unwrap(), 2);
+    /// assert_eq!(counter.decr(3).unwrap(), 0);
+    /// ```
+    pub fn decr(&self, delta: u64) -> Result<u64> {
+        self.apply(|value| value.saturating_sub(delta))
+    }
+
+    /// Reset the counter to zero by removing its key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{AtomicCounter, DBMap};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// let counter = AtomicCounter::new(db.clone(), b"count");
+    ///
+    /// counter.incr(5).unwrap();
+    /// counter.reset().unwrap();
+    /// assert_eq!(counter.get().unwrap(), 0);
+    /// assert!(db.get(b"count").unwrap().is_none());
+    /// ```
+    pub fn reset(&self) -> Result<()> {
+        self.db.remove(&self.key)
+    }
+
+    /// Atomically replace the counter with `f(counter)` and return the new value.
+    fn apply<F: FnOnce(u64) -> u64>(&self, f: F) -> Result<u64> {
+        let mut new_value = Ok(0);
+        self.db.update(&self.key, |old| match old.map(|value| self.decode(value)).transpose() {
+            Ok(value) => {
+                let value = f(value.unwrap_or(0));
+                new_value = Ok(value);
+                Some(value.to_be_bytes().to_vec())
+            }
+            Err(err) => {
+                new_value = Err(err);
+                old.map(|v| v.to_vec())
+            }
+        })?;
+        new_value
+    }
+
+    /// Decode the stored counter.
+    fn decode(&self, value: &[u8]) -> Result<u64> {
+        decode_u64(value, format_args!("the value for counter key {}", HexKey(&self.key)))
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for AtomicCounter<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicCounter")
+            .field("db", &self.db)
+            .field("key", &HexKey(&self.key))
+            .finish()
+    }
+}
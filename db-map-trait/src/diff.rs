@@ -0,0 +1,167 @@
+//! # Comparing the contents of two `DBMap`s.
+//!
+//! The [`diff`] function finds the keys that differ between two databases, which is useful for
+//! replication and debugging. [`contents_eq`] only answers whether they differ, and stops at the
+//! first difference.
+use crate::{DBMap, Error, Result};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// The differences between two databases, as returned by [`diff`].
+///
+/// Every list is in byte-lexicographic key order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DbDiff {
+    /// Keys that are only in the first database.
+    pub only_in_a: Vec<Vec<u8>>,
+    /// Keys that are only in the second database.
+    pub only_in_b: Vec<Vec<u8>>,
+    /// Keys that are in both databases with different values.
+    pub differing: Vec<Vec<u8>>,
+}
+
+impl DbDiff {
+    /// Return `true` if the two databases had the same contents.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.differing.is_empty()
+    }
+}
+
+/// Find the keys that differ between two databases.
+///
+/// Both databases are scanned once in key order and the scans are merged. Only the keys of `b`
+/// and a hash of each of its values are held in memory, not the values themselves. A database
+/// whose scans aren't in key order (see
+/// [`Capabilities::ordered_iteration`][crate::Capabilities::ordered_iteration]) has its keys and
+/// value hashes read into memory and sorted before the merge, so `a` is held too if it is one.
+///
+/// The databases are not locked against each other, so if either one changes during the scan
+/// the result is a mix of the old and new contents.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, diff::{diff, DbDiff}};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let a = BTreeMapDB::from_iter([
+///     ("apple", "red"),
+///     ("banana", "yellow"),
+///     ("grape", "purple"),
+///     ("lime", "green"),
+/// ]);
+/// let b = BTreeMapDB::from_iter([
+///     ("apple", "red"),
+///     ("cherry", "red"),
+///     ("grape", "green"),
+///     ("lime", "green"),
+///     ("plum", "purple"),
+/// ]);
+///
+/// assert_eq!(diff(&a, &b).unwrap(), DbDiff {
+///     only_in_a: vec![b"banana".to_vec()],
+///     only_in_b: vec![b"cherry".to_vec(), b"plum".to_vec()],
+///     differing: vec![b"grape".to_vec()],
+/// });
+/// assert!(diff(&a, &a.clone()).unwrap().is_empty());
+/// ```
+pub fn diff<A: DBMap, B: DBMap>(a: &A, b: &B) -> Result<DbDiff> {
+    let mut result = DbDiff::default();
+    let mut b_entries = sorted_hashes(b)?.into_iter().peekable();
+    let mut merge = |key: &[u8], a_hash: [u8; 32]| {
+        while let Some((b_key, _)) = b_entries.next_if(|(b_key, _)| b_key.as_slice() < key) {
+            result.only_in_b.push(b_key);
+        }
+        match b_entries.next_if(|(b_key, _)| b_key.as_slice() == key) {
+            Some((_, b_hash)) if b_hash != a_hash => result.differing.push(key.to_vec()),
+            Some(_) => {}
+            None => result.only_in_a.push(key.to_vec()),
+        }
+    };
+    match a.capabilities().ordered_iteration {
+        true => a.for_each(|key, value| merge(key, hash(value)))?,
+        false => sorted_hashes(a)?.into_iter().for_each(|(key, a_hash)| merge(&key, a_hash)),
+    }
+    result.only_in_b.extend(b_entries.map(|(b_key, _)| b_key));
+    Ok(result)
+}
+
+/// Read the keys of a database and the hashes of their values, in key order.
+fn sorted_hashes<M: DBMap>(db: &M) -> Result<Vec<(Vec<u8>, [u8; 32])>> {
+    let mut entries = Vec::new();
+    db.for_each(|key, value| entries.push((key.to_vec(), hash(value))))?;
+    if !db.capabilities().ordered_iteration {
+        entries.sort_unstable_by(|(x, _), (y, _)| x.cmp(y));
+    }
+    Ok(entries)
+}
+
+/// Return `true` if two databases have exactly the same entries.
+///
+/// This is the yes-or-no version of [`diff`]: the keys and value hashes of `b` are read into
+/// memory, sorted, and then `a` is scanned entry by entry against them. The scan stops at the
+/// first key or value that doesn't match. Neither database has to scan in key order.
+///
+/// Like `diff`, the databases are not locked against each other while they are compared.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, diff::contents_eq};
+/// use db_map_btreemap::{BTreeMapDB, ShardedMemDb};
+///
+/// let a = BTreeMapDB::from_iter([("apple", "red"), ("lime", "green")]);
+/// let b = ShardedMemDb::open();
+/// b.batch_insert([("lime", "green"), ("apple", "red")]).unwrap();
+/// assert!(contents_eq(&a, &b).unwrap());
+///
+/// // One value differs.
+/// b.insert(b"lime", b"yellow").unwrap();
+/// assert!(!contents_eq(&a, &b).unwrap());
+///
+/// // One database has an extra key, in either order.
+/// b.insert(b"lime", b"green").unwrap();
+/// b.insert(b"plum", b"purple").unwrap();
+/// assert!(!contents_eq(&a, &b).unwrap());
+/// assert!(!contents_eq(&b, &a).unwrap());
+///
+/// // Empty databases are equal.
+/// assert!(contents_eq(&BTreeMapDB::open(), &ShardedMemDb::open()).unwrap());
+/// ```
+pub fn contents_eq<A: DBMap, B: DBMap>(a: &A, b: &B) -> Result<bool> {
+    let b_entries = sorted_hashes(b)?;
+
+    let mut matched = 0;
+    let result = a.try_for_each(|key, value| {
+        match b_entries.binary_search_by(|(b_key, _)| b_key.as_slice().cmp(key)) {
+            Ok(index) if b_entries[index].1 == hash(value) => {
+                matched += 1;
+                Ok(())
+            }
+            _ => Err(Error::DBError(anyhow::Error::new(Different))),
+        }
+    });
+    match result {
+        // Every key of `a` is in `b`, so they are equal if `b` has no others.
+        Ok(()) => Ok(matched == b_entries.len()),
+        Err(err) if err.downcast_ref::<Different>().is_some() => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// The error [`contents_eq`] uses to stop its scan at the first difference.
+#[derive(Debug)]
+struct Different;
+
+impl fmt::Display for Different {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the databases differ")
+    }
+}
+
+impl std::error::Error for Different {}
+
+/// Hash a value for comparison.
+fn hash(value: &[u8]) -> [u8; 32] {
+    Sha256::digest(value).into()
+}
@@ -0,0 +1,305 @@
+//! # Runtime polymorphism for `DBMap`.
+//!
+//! `DBMap` has generic methods, so it can't be used as a trait object. The [`DynDbMap`] trait
+//! has the same operations with plain slice arguments, is implemented for every `DBMap`, and can
+//! be boxed. `Box<dyn DynDbMap>` implements `DBMap` again, so a boxed database works with
+//! everything that takes a `DBMap`.
+//!
+//! The price is an extra copy: [`get_map`][DBMap::get_map] on a box reads the whole value into
+//! a `Vec` before calling the mapper.
+//!
+//! The `DynDbMap` methods all start with `dyn_` so that they never clash with the `DBMap`
+//! methods when both traits are in scope.
+use crate::{Capabilities, DBMap, Page, Result};
+use std::ops::Bound;
+
+/// The function passed to [`DynDbMap::dyn_update`].
+pub type DynUpdateFn<'a> = dyn FnMut(Option<&[u8]>) -> Option<Vec<u8>> + 'a;
+
+/// The function passed to the `DynDbMap` scanning methods.
+pub type DynScanFn<'a> = dyn FnMut(&[u8], &[u8]) -> Result<()> + 'a;
+
+/// An object-safe version of [`DBMap`].
+///
+/// This is implemented for every `DBMap`, so there is never a need to implement it directly.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, DynDbMap};
+/// use db_map_btreemap::{BTreeMapDB, ShardedMemDb};
+///
+/// // Pick a backend at runtime.
+/// fn open(sharded: bool) -> Box<dyn DynDbMap> {
+///     match sharded {
+///         true => Box::new(ShardedMemDb::open()),
+///         false => Box::new(BTreeMapDB::open()),
+///     }
+/// }
+///
+/// for sharded in [false, true] {
+///     let db = open(sharded);
+///
+///     // The box is a `DBMap`, with all of the usual methods.
+///     db.insert(b"key", b"value").unwrap();
+///     assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+///     assert_eq!(db.get_map(b"key", |v| v.len()).unwrap(), Some(5));
+///     assert_eq!(db.fetch_and_replace(b"key", b"new").unwrap(), Some(b"value".to_vec()));
+///     db.upsert(b"count", [1], |v| vec![v[0] + 1]).unwrap();
+///     db.upsert(b"count", [1], |v| vec![v[0] + 1]).unwrap();
+///     assert_eq!(db.get(b"count").unwrap(), Some(vec![2]));
+///     assert_eq!(db.len().unwrap(), 2);
+///
+///     // Clones share the same database.
+///     let clone = db.clone();
+///     clone.remove(b"key").unwrap();
+///     assert!(db.get(b"key").unwrap().is_none());
+/// }
+/// ```
+pub trait DynDbMap {
+    /// Get the data for a key. See [`DBMap::get`].
+    fn dyn_get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Insert data for a key. See [`DBMap::insert`].
+    fn dyn_insert(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    /// Insert data for a key, returning the old value. See [`DBMap::fetch_and_replace`].
+    fn dyn_fetch_and_replace(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Remove a key. See [`DBMap::remove`].
+    fn dyn_remove(&self, key: &[u8]) -> Result<()>;
+
+    /// Atomically replace the value for a key. See [`DBMap::update`].
+    ///
+    /// The function is called exactly once.
+    fn dyn_update(&self, key: &[u8], f: &mut DynUpdateFn) -> Result<()>;
+
+    /// Insert every pair, atomically if the database's batches are. See [`DBMap::batch_insert`].
+    fn dyn_batch_insert(&self, items: &[(&[u8], &[u8])]) -> Result<()>;
+
+    /// Scan every entry until the function fails. See [`DBMap::try_for_each`].
+    fn dyn_try_for_each(&self, f: &mut DynScanFn) -> Result<()>;
+
+    /// Scan the entries under a prefix until the function fails. See
+    /// [`DBMap::try_scan_prefix`].
+    fn dyn_try_scan_prefix(&self, prefix: &[u8], f: &mut DynScanFn) -> Result<()>;
+
+    /// Scan the entries in a key range until the function fails. See
+    /// [`DBMap::try_scan_range`].
+    fn dyn_try_scan_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, f: &mut DynScanFn) -> Result<()>;
+
+    /// Count the entries in a key range. See [`DBMap::count_range`].
+    fn dyn_count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize>;
+
+    /// Get one page of the entries under a prefix. See [`DBMap::scan_page`].
+    fn dyn_scan_page(&self, prefix: &[u8], start_after: Option<&[u8]>, limit: usize) -> Result<Page>;
+
+    /// Move the entries under a prefix to another prefix. See [`DBMap::move_prefix`].
+    fn dyn_move_prefix(&self, from_prefix: &[u8], to_prefix: &[u8]) -> Result<usize>;
+
+    /// Hint that a key range is about to be used. See [`DBMap::prepare_range`].
+    fn dyn_prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()>;
+
+    /// Get the number of entries. See [`DBMap::len`].
+    fn dyn_len(&self) -> Result<usize>;
+
+    /// Remove every entry. See [`DBMap::clear`].
+    fn dyn_clear(&self) -> Result<()>;
+
+    /// Report the features the database supports. See [`DBMap::capabilities`].
+    fn dyn_capabilities(&self) -> Capabilities;
+
+    /// Clone the database into a new box.
+    fn clone_box(&self) -> Box<dyn DynDbMap>;
+}
+
+impl<M: DBMap + 'static> DynDbMap for M {
+    fn dyn_get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        DBMap::get(self, key)
+    }
+
+    fn dyn_insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        DBMap::insert(self, key, value)
+    }
+
+    fn dyn_fetch_and_replace(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        DBMap::fetch_and_replace(self, key, value)
+    }
+
+    fn dyn_remove(&self, key: &[u8]) -> Result<()> {
+        DBMap::remove(self, key)
+    }
+
+    fn dyn_update(&self, key: &[u8], f: &mut DynUpdateFn) -> Result<()> {
+        DBMap::update(self, key, f)
+    }
+
+    fn dyn_batch_insert(&self, items: &[(&[u8], &[u8])]) -> Result<()> {
+        DBMap::batch_insert(self, items.iter().copied())
+    }
+
+    fn dyn_try_for_each(&self, f: &mut DynScanFn) -> Result<()> {
+        DBMap::try_for_each(self, f)
+    }
+
+    fn dyn_try_scan_prefix(&self, prefix: &[u8], f: &mut DynScanFn) -> Result<()> {
+        DBMap::try_scan_prefix(self, prefix, f)
+    }
+
+    fn dyn_try_scan_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, f: &mut DynScanFn) -> Result<()> {
+        DBMap::try_scan_range(self, start, end, f)
+    }
+
+    fn dyn_count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        DBMap::count_range(self, start, end)
+    }
+
+    fn dyn_scan_page(&self, prefix: &[u8], start_after: Option<&[u8]>, limit: usize) -> Result<Page> {
+        DBMap::scan_page(self, prefix, start_after, limit)
+    }
+
+    fn dyn_move_prefix(&self, from_prefix: &[u8], to_prefix: &[u8]) -> Result<usize> {
+        DBMap::move_prefix(self, from_prefix, to_prefix)
+    }
+
+    fn dyn_prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        DBMap::prepare_range(self, start, end)
+    }
+
+    fn dyn_len(&self) -> Result<usize> {
+        DBMap::len(self)
+    }
+
+    fn dyn_clear(&self) -> Result<()> {
+        DBMap::clear(self)
+    }
+
+    fn dyn_capabilities(&self) -> Capabilities {
+        DBMap::capabilities(self)
+    }
+
+    fn clone_box(&self) -> Box<dyn DynDbMap> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn DynDbMap> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+impl std::fmt::Debug for dyn DynDbMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("dyn DynDbMap")
+    }
+}
+
+/// Every method calls through to the boxed database with `(**self)`. Calling a `dyn_` method on
+/// the box itself would use the blanket implementation for the box and recurse forever.
+impl DBMap for Box<dyn DynDbMap> {
+    /// Get the data for a key as an owned `Vec` and then map it.
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        Ok((**self).dyn_get(key.as_ref())?.map(|value| mapper(&value)))
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        (**self).dyn_insert(key.as_ref(), value.as_ref())
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        Ok((**self).dyn_fetch_and_replace(key.as_ref(), value.as_ref())?
+            .map(|old| mapper(&old)))
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        (**self).dyn_remove(key.as_ref())
+    }
+
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let mut f = Some(f);
+        (**self).dyn_update(key.as_ref(), &mut |existing| {
+            let f = f.take().expect("update calls the function exactly once");
+            f(existing)
+        })
+    }
+
+    /// Collect the pairs so that the boxed database gets the whole batch in one call, and keeps
+    /// its [`atomic_batch`][Capabilities::atomic_batch] guarantee.
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let items: Vec<(K, V)> = items.into_iter().collect();
+        let items: Vec<(&[u8], &[u8])> = items.iter()
+            .map(|(key, value)| (key.as_ref(), value.as_ref()))
+            .collect();
+        (**self).dyn_batch_insert(&items)
+    }
+
+    fn try_for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        (**self).dyn_try_for_each(&mut f)
+    }
+
+    fn try_scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        (**self).dyn_try_scan_prefix(prefix.as_ref(), &mut f)
+    }
+
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        (**self).dyn_try_scan_range(start, end, &mut f)
+    }
+
+    fn count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        (**self).dyn_count_range(start, end)
+    }
+
+    fn scan_page<P: AsRef<[u8]>>(&self, prefix: P, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        (**self).dyn_scan_page(prefix.as_ref(), start_after, limit)
+    }
+
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        (**self).dyn_move_prefix(from_prefix.as_ref(), to_prefix.as_ref())
+    }
+
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        (**self).dyn_prepare_range(start, end)
+    }
+
+    fn len(&self) -> Result<usize> {
+        (**self).dyn_len()
+    }
+
+    fn clear(&self) -> Result<()> {
+        (**self).dyn_clear()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        (**self).dyn_capabilities()
+    }
+}
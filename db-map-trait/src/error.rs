@@ -1,5 +1,5 @@
 //! # Error handling for the `DBMap` trait.
-use std::io;
+use std::{io, time::Duration};
 
 /// The standard error type for the `DBMap` trait.
 #[derive(Debug, thiserror::Error)]
@@ -11,6 +11,142 @@ pub enum Error {
     /// Passthrough database error.
     #[error(transparent)]
     DBError(#[from] anyhow::Error),
+
+    /// A value was rejected by a validator before it was written.
+    #[error("value failed validation: {0}")]
+    Validation(String),
+
+    /// A key was rejected because the database can't store it correctly.
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+
+    /// A stored value wasn't the length that was asked for.
+    #[error("expected a value of {expected} bytes, but the stored value is {actual} bytes")]
+    InvalidLength {
+        /// The length that was asked for.
+        expected: usize,
+        /// The length of the stored value.
+        actual: usize,
+    },
+
+    /// A key passed to [`bulk_load_sorted`][crate::DBMap::bulk_load_sorted] was out of order.
+    #[error("keys are not in ascending order: {0}")]
+    NotSorted(String),
+
+    /// A stored value failed an integrity check, so it was damaged or changed outside the database.
+    #[error("corrupt data: {0}")]
+    Corruption(String),
+
+    /// A saved file does not start with the expected magic bytes.
+    #[error("unrecognized file format (magic bytes {magic:02X?})")]
+    BadMagic {
+        /// The magic bytes that were found.
+        magic: [u8; 4],
+    },
+
+    /// A saved file uses a format version that this library does not support.
+    #[error("unsupported file format version {version}")]
+    UnsupportedFormat {
+        /// The format version that was found.
+        version: u16,
+    },
+
+    /// A database was written by newer code, with a schema version this code doesn't support.
+    #[error("the database schema version {stored} is newer than the supported version {supported}")]
+    SchemaTooNew {
+        /// The schema version stored in the database.
+        stored: u64,
+        /// The newest schema version the code supports.
+        supported: u64,
+    },
+
+    /// A write was attempted through a read-only handle.
+    #[error("the database was opened read-only")]
+    ReadOnly,
+
+    /// An operation did not finish within its time limit.
+    #[error("operation timed out after {0:?}")]
+    Timeout(Duration),
+
+    /// A database error that is expected to go away, like a full map or a busy resource, so the
+    /// operation may succeed if it is tried again.
+    #[error(transparent)]
+    Transient(anyhow::Error),
+}
+
+impl Error {
+    /// Returns `true` if the operation that failed may succeed if it is tried again.
+    ///
+    /// [`Transient`][Error::Transient] errors, [`Timeout`][Error::Timeout]s, and I/O errors that
+    /// were interrupted, timed out, or would have blocked are retryable. Everything else will
+    /// fail the same way again. [`with_retry`][crate::retry::with_retry] uses this to decide
+    /// whether to retry.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Transient(_) | Error::Timeout(_) => true,
+            Error::IoError(err) => matches!(
+                err.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock,
+            ),
+            _ => false,
+        }
+    }
+
+    /// Get the backend error behind this error, if it is an `E`.
+    ///
+    /// Backends report their own errors as [`DBError`][Error::DBError] (or
+    /// [`Transient`][Error::Transient]) wrapping an [`anyhow::Error`], which keeps the original
+    /// error and its type. This looks through the `anyhow::Error`, or through an
+    /// [`IoError`][Error::IoError] to the error it carries, and downcasts to `E`.
+    ///
+    /// [`std::error::Error::source`] doesn't help here: both variants are transparent, so the
+    /// source is the backend error's own source, not the backend error itself.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::Error;
+    /// use std::{fmt, io};
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// struct Corrupt(u32);
+    ///
+    /// impl fmt::Display for Corrupt {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         write!(f, "page {} is corrupt", self.0)
+    ///     }
+    /// }
+    ///
+    /// impl std::error::Error for Corrupt {}
+    ///
+    /// let err = Error::from(anyhow::Error::new(Corrupt(7)));
+    /// assert_eq!(err.downcast_ref::<Corrupt>(), Some(&Corrupt(7)));
+    /// assert!(err.downcast_ref::<io::Error>().is_none());
+    ///
+    /// let err = Error::from(io::Error::new(io::ErrorKind::Other, Corrupt(8)));
+    /// assert_eq!(err.downcast_ref::<Corrupt>(), Some(&Corrupt(8)));
+    /// assert!(Error::ReadOnly.downcast_ref::<Corrupt>().is_none());
+    /// ```
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+        where
+            E: std::error::Error + Send + Sync + 'static,
+    {
+        match self {
+            Error::DBError(err) | Error::Transient(err) => err.downcast_ref(),
+            Error::IoError(err) => err.get_ref()?.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Get the [`lmdb::Error`] behind this error, if it came from LMDB.
+    ///
+    /// This is [`downcast_ref::<lmdb::Error>`][Error::downcast_ref], for deciding what to do
+    /// based on the LMDB error code, like growing the map on [`lmdb::Error::MapFull`]. It needs
+    /// the `lmdb` feature.
+    #[cfg(feature = "lmdb")]
+    pub fn source_lmdb(&self) -> Option<&lmdb::Error> {
+        self.downcast_ref()
+    }
 }
 
 /// The standard result type for the `DBMap` trait.
@@ -0,0 +1,363 @@
+//! # Entries that expire for the `DBMap` trait.
+//!
+//! The [`Expiring`] wrapper stores an expiry time with every value. Expired entries are hidden
+//! from reads and removed lazily when they are read, or eagerly by
+//! [`purge_expired`][Expiring::purge_expired].
+use crate::{Capabilities, DBMap, Error, Result};
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The clock used by [`Expiring`].
+pub type WallClock = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+/// The number of bytes of expiry time stored in front of every value.
+const HEADER_LEN: usize = std::mem::size_of::<u64>();
+
+/// A clock that only moves when it is told to, for testing code that uses [`Expiring`].
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::expiring::ManualClock;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let clock = ManualClock::new(UNIX_EPOCH);
+/// let wall_clock = clock.clock();
+/// clock.advance(Duration::from_secs(5));
+/// assert_eq!(wall_clock(), UNIX_EPOCH + Duration::from_secs(5));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ManualClock(Arc<Mutex<SystemTime>>);
+
+impl ManualClock {
+    /// Create a clock that starts at `start`.
+    pub fn new(start: SystemTime) -> Self {
+        Self(Arc::new(Mutex::new(start)))
+    }
+
+    /// Get the current time.
+    pub fn now(&self) -> SystemTime {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) += by;
+    }
+
+    /// Get a [`WallClock`] that reads this clock, to pass to [`Expiring::with_clock`].
+    pub fn clock(&self) -> WallClock {
+        let clock = self.clone();
+        Arc::new(move || clock.now())
+    }
+}
+
+/// A `DBMap` wrapper that stores an expiry time with every value.
+///
+/// Values written with [`insert`][DBMap::insert] and the other `DBMap` methods expire the
+/// default `ttl` after they are written, and [`insert_with_ttl`][Expiring::insert_with_ttl] sets
+/// the time to live of a single entry. Each value is stored in the inner database behind an
+/// 8-byte header holding its expiry time, in big-endian milliseconds since the Unix epoch.
+///
+/// Expired entries are treated as missing by every read and scan. A point read that finds an
+/// expired entry removes it, but entries that are never read again stay in the inner database
+/// until [`purge_expired`][Expiring::purge_expired] is called, so call it periodically.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{expiring::{Expiring, ManualClock}, DBMap};
+/// use db_map_btreemap::BTreeMapDB;
+/// use std::time::{Duration, SystemTime};
+///
+/// let clock = ManualClock::new(SystemTime::now());
+/// let db = Expiring::with_clock(BTreeMapDB::open(), Duration::from_secs(60), clock.clock());
+///
+/// db.insert(b"session", b"alice").unwrap();
+/// db.insert_with_ttl(b"token", b"secret", Duration::from_secs(10)).unwrap();
+///
+/// clock.advance(Duration::from_secs(30));
+/// assert_eq!(db.get(b"session").unwrap(), Some(b"alice".to_vec()));
+/// assert!(db.get(b"token").unwrap().is_none());
+///
+/// // Reading the expired entry removed it from the inner database.
+/// assert_eq!(db.inner().len().unwrap(), 1);
+/// ```
+pub struct Expiring<M> {
+    inner: M,
+    ttl: Duration,
+    clock: WallClock,
+}
+
+impl<M: DBMap> Expiring<M> {
+    /// Wrap `inner` so that entries expire `ttl` after they are written, using the system clock.
+    pub fn new(inner: M, ttl: Duration) -> Self {
+        Self::with_clock(inner, ttl, Arc::new(SystemTime::now))
+    }
+
+    /// Wrap `inner` so that entries expire `ttl` after they are written, using `clock` to tell
+    /// the time.
+    ///
+    /// This is mostly useful for tests, which can use a [`ManualClock`].
+    pub fn with_clock(inner: M, ttl: Duration, clock: WallClock) -> Self {
+        Self { inner, ttl, clock }
+    }
+
+    /// Get a reference to the wrapped database.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Insert data for a key that expires `ttl` from now, instead of after the default time.
+    pub fn insert_with_ttl<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V, ttl: Duration) -> Result<()> {
+        self.inner.insert(key, self.encode(value.as_ref(), ttl))
+    }
+
+    /// Remove every expired entry from the inner database, returning the number removed.
+    ///
+    /// The expired keys are found with a full scan, and then each one is removed with
+    /// [`update`][DBMap::update], which checks again that it has expired so that an entry
+    /// written in the meantime is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{expiring::{Expiring, ManualClock}, DBMap};
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_000_000));
+    /// let db = Expiring::with_clock(BTreeMapDB::open(), Duration::from_secs(3600), clock.clock());
+    ///
+    /// for (key, secs) in [(b"a", 10), (b"b", 20), (b"c", 30), (b"d", 40)] {
+    ///     db.insert_with_ttl(key, b"value", Duration::from_secs(secs)).unwrap();
+    /// }
+    /// db.insert(b"e", b"value").unwrap();
+    ///
+    /// clock.advance(Duration::from_secs(25));
+    /// assert_eq!(db.purge_expired().unwrap(), 2);
+    /// let mut left = Vec::new();
+    /// db.inner().for_each(|key, _| left.push(key.to_vec())).unwrap();
+    /// assert_eq!(left, [b"c", b"d", b"e"]);
+    ///
+    /// // Nothing else has expired yet.
+    /// assert_eq!(db.purge_expired().unwrap(), 0);
+    /// clock.advance(Duration::from_secs(3600));
+    /// assert_eq!(db.purge_expired().unwrap(), 3);
+    /// assert!(db.inner().is_empty().unwrap());
+    /// ```
+    pub fn purge_expired(&self) -> Result<usize> {
+        let now = self.now();
+        let mut expired = Vec::new();
+        self.inner.try_for_each(|key, stored| {
+            if split_header(stored)?.0 <= now {
+                expired.push(key.to_vec());
+            }
+            Ok(())
+        })?;
+        let mut purged = 0;
+        for key in expired {
+            if self.remove_if_expired(&key)? {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Get the current time in milliseconds since the Unix epoch.
+    fn now(&self) -> u64 {
+        millis((self.clock)())
+    }
+
+    /// Put the expiry header in front of a value.
+    fn encode(&self, value: &[u8], ttl: Duration) -> Vec<u8> {
+        let expires = self.now().saturating_add(ttl.as_millis().try_into().unwrap_or(u64::MAX));
+        [&expires.to_be_bytes()[..], value].concat()
+    }
+
+    /// Get the value from a stored entry, or `None` if it has expired.
+    fn live<'a>(&self, stored: &'a [u8], now: u64) -> Result<Option<&'a [u8]>> {
+        let (expires, value) = split_header(stored)?;
+        Ok((expires > now).then_some(value))
+    }
+
+    /// Remove a key if its entry has expired, returning `true` if it was removed.
+    fn remove_if_expired(&self, key: &[u8]) -> Result<bool> {
+        let now = self.now();
+        let mut removed = false;
+        self.inner.update(key, |stored| {
+            let stored = stored?;
+            match split_header(stored) {
+                Ok((expires, _)) if expires <= now => {
+                    removed = true;
+                    None
+                }
+                _ => Some(stored.to_vec()),
+            }
+        })?;
+        Ok(removed)
+    }
+}
+
+/// Convert a time to milliseconds since the Unix epoch.
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// Split a stored entry into its expiry time and its value.
+fn split_header(stored: &[u8]) -> Result<(u64, &[u8])> {
+    match stored.split_first_chunk::<HEADER_LEN>() {
+        Some((expires, value)) => Ok((u64::from_be_bytes(*expires), value)),
+        None => Err(Error::Corruption(format!(
+            "stored value is {} bytes, too short to have an expiry time", stored.len(),
+        ))),
+    }
+}
+
+impl<M: Clone> Clone for Expiring<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            ttl: self.ttl,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for Expiring<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expiring")
+            .field("inner", &self.inner)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M: DBMap> DBMap for Expiring<M> {
+    /// An expired entry is removed and reported as missing.
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let key = key.as_ref();
+        let now = self.now();
+        let mut expired = false;
+        let result = self.inner.get_map(key, |stored| -> Result<Option<T>> {
+            Ok(match self.live(stored, now)? {
+                Some(value) => Some(mapper(value)),
+                None => {
+                    expired = true;
+                    None
+                }
+            })
+        })?.transpose()?.flatten();
+        if expired {
+            self.remove_if_expired(key)?;
+        }
+        Ok(result)
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        self.insert_with_ttl(key, value, self.ttl)
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let now = self.now();
+        let stored = self.encode(value.as_ref(), self.ttl);
+        self.inner.fetch_and_replace_map(key, stored, |old| self.live(old, now).map(|old| old.map(mapper)))?
+            .transpose()
+            .map(Option::flatten)
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        self.inner.remove(key)
+    }
+
+    /// `f` sees an expired entry as missing, and a new value gets the default time to live.
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let now = self.now();
+        let mut error = None;
+        self.inner.update(key, |stored| {
+            let current = match stored.map(|stored| self.live(stored, now)).transpose() {
+                Ok(current) => current.flatten(),
+                Err(err) => {
+                    error = Some(err);
+                    return stored.map(<[u8]>::to_vec);
+                }
+            };
+            f(current).map(|value| self.encode(&value, self.ttl))
+        })?;
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Write every value with the default time to live in one batch.
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        self.inner.batch_insert(items.into_iter()
+            .map(|(key, value)| (key, self.encode(value.as_ref(), self.ttl))))
+    }
+
+    /// Scan the inner database, skipping expired entries.
+    fn try_for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let now = self.now();
+        self.inner.try_for_each(|key, stored| match self.live(stored, now)? {
+            Some(value) => f(key, value),
+            None => Ok(()),
+        })
+    }
+
+    /// Scan the inner database, skipping expired entries.
+    fn try_scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let now = self.now();
+        self.inner.try_scan_prefix(prefix, |key, stored| match self.live(stored, now)? {
+            Some(value) => f(key, value),
+            None => Ok(()),
+        })
+    }
+
+    /// Move the stored entries, keeping their expiry times. Expired entries are moved too.
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        self.inner.move_prefix(from_prefix, to_prefix)
+    }
+
+    /// Prepare the stored entries in the inner database, expired or not.
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        self.inner.prepare_range(start, end)
+    }
+
+    /// Remove the stored entries, expired or not.
+    fn clear(&self) -> Result<()> {
+        self.inner.clear()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
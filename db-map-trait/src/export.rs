@@ -0,0 +1,272 @@
+//! # Exporting and importing the contents of a `DBMap`.
+//!
+//! [`export`] writes every entry of a database to a writer and [`import`] inserts the entries
+//! read back from a reader, into any `DBMap`. The [`ExportFormat`] picks how the entries are
+//! written.
+use crate::{DBMap, Result};
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+/// The format used by [`export`] and [`import`].
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, export::{export, import, ExportFormat}};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let db = BTreeMapDB::open();
+/// db.insert(b"", b"empty key").unwrap();
+/// db.insert(b"empty value", b"").unwrap();
+/// db.insert([0x00, 0xFF, b'\n', b'"'], vec![0xAB; 1000]).unwrap();
+///
+/// for format in [ExportFormat::Binary, ExportFormat::JsonLines] {
+///     let mut dump = Vec::new();
+///     assert_eq!(export(&db, &mut dump, format).unwrap(), 3);
+///
+///     let copy = BTreeMapDB::open();
+///     assert_eq!(import(&copy, dump.as_slice(), format).unwrap(), 3);
+///     assert_eq!(copy.digest().unwrap(), db.digest().unwrap());
+///
+///     // Every JSON-lines line is a JSON object with hex strings.
+///     if format == ExportFormat::JsonLines {
+///         let text = String::from_utf8(dump).unwrap();
+///         assert_eq!(text.lines().count(), 3);
+///         for line in text.lines() {
+///             let object: serde_json::Value = serde_json::from_str(line).unwrap();
+///             let object = object.as_object().unwrap();
+///             assert_eq!(object.len(), 2);
+///             for field in ["key", "value"] {
+///                 assert!(object[field].as_str().unwrap().bytes().all(|b| b.is_ascii_hexdigit()));
+///             }
+///         }
+///     }
+/// }
+/// assert_eq!(ExportFormat::default(), ExportFormat::Binary);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Each entry as the key length (big-endian `u64`), the key, the value length (big-endian
+    /// `u64`), and the value, with nothing between entries.
+    ///
+    /// This is the most compact format, and the default.
+    #[default]
+    Binary,
+    /// One JSON object per line, with the key and value as lowercase hex strings:
+    ///
+    /// ```text
+    /// {"key":"6b6579","value":"76616c7565"}
+    /// ```
+    ///
+    /// This is about twice the size of `Binary`, but it can be read, searched, and diffed as
+    /// text. Blank lines are skipped on import.
+    JsonLines,
+}
+
+/// Write every entry in `db`, in key order, to `writer`.
+///
+/// When scans are in key order (see
+/// [`Capabilities::ordered_iteration`][crate::Capabilities::ordered_iteration]) the entries are
+/// streamed straight from the scan. Otherwise they are collected and sorted with
+/// [`DBMap::iter`] first.
+///
+/// Returns the number of entries written.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, export::{export, import, ExportFormat}};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let db = BTreeMapDB::from_iter([("apple", "red"), ("grape", "purple")]);
+///
+/// let mut dump = Vec::new();
+/// assert_eq!(export(&db, &mut dump, ExportFormat::JsonLines).unwrap(), 2);
+/// assert_eq!(String::from_utf8(dump.clone()).unwrap(), concat!(
+///     "{\"key\":\"6170706c65\",\"value\":\"726564\"}\n",
+///     "{\"key\":\"6772617065\",\"value\":\"707572706c65\"}\n",
+/// ));
+///
+/// let copy = BTreeMapDB::open();
+/// assert_eq!(import(&copy, dump.as_slice(), ExportFormat::JsonLines).unwrap(), 2);
+/// assert_eq!(copy.digest().unwrap(), db.digest().unwrap());
+/// ```
+pub fn export<M: DBMap, W: Write>(db: &M, mut writer: W, format: ExportFormat) -> Result<usize> {
+    let mut count = 0;
+    let mut write_entry = |key: &[u8], value: &[u8]| -> Result<()> {
+        match format {
+            ExportFormat::Binary => write_binary_entry(&mut writer, key, value)?,
+            ExportFormat::JsonLines => {
+                writeln!(writer, "{{\"key\":\"{}\",\"value\":\"{}\"}}", to_hex(key), to_hex(value))?;
+            }
+        }
+        count += 1;
+        Ok(())
+    };
+    match db.capabilities().ordered_iteration {
+        true => db.try_for_each(&mut write_entry)?,
+        false => db.iter()?.iter().try_for_each(|(key, value)| write_entry(key, value))?,
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Read entries written by [`export`] from `reader` and insert them into `db`.
+///
+/// Every entry is read before anything is inserted, and then they are all inserted with one
+/// [`batch_insert`][DBMap::batch_insert], so malformed input leaves the database unchanged.
+/// Entries already in `db` are kept unless the import replaces them. Malformed input fails with
+/// an [`io::ErrorKind::InvalidData`] (or, for truncated binary input,
+/// [`io::ErrorKind::UnexpectedEof`]) I/O error.
+///
+/// Returns the number of entries read.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, Error, export::{import, ExportFormat}};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let db = BTreeMapDB::open();
+/// let dump = "{\"key\":\"01\",\"value\":\"\"}\n\n{\"value\": \"ff\", \"key\": \"02\"}\n";
+/// assert_eq!(import(&db, dump.as_bytes(), ExportFormat::JsonLines).unwrap(), 2);
+/// assert_eq!(db.get([1]).unwrap(), Some(vec![]));
+/// assert_eq!(db.get([2]).unwrap(), Some(vec![0xFF]));
+///
+/// // A bad line fails the whole import.
+/// let dump = "{\"key\":\"03\",\"value\":\"\"}\n{\"key\":\"0g\",\"value\":\"\"}\n";
+/// assert!(matches!(
+///     import(&db, dump.as_bytes(), ExportFormat::JsonLines),
+///     Err(Error::IoError(err)) if err.to_string() == "line 2 is not a valid export entry",
+/// ));
+/// assert!(db.get([3]).unwrap().is_none());
+/// ```
+pub fn import<M: DBMap, R: Read>(db: &M, reader: R, format: ExportFormat) -> Result<usize> {
+    let entries = match format {
+        ExportFormat::Binary => read_binary(reader)?,
+        ExportFormat::JsonLines => read_json_lines(reader)?,
+    };
+    let count = entries.len();
+    db.batch_insert(entries)?;
+    Ok(count)
+}
+
+/// Read every entry in the `Binary` format.
+fn read_binary<R: Read>(mut reader: R) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut entries = Vec::new();
+    while let Some(entry) = read_binary_entry(&mut reader)? {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Write one entry in the [`Binary`][ExportFormat::Binary] format.
+///
+/// This is public so that other formats built from the same entries, like
+/// `BTreeMapDB::save`, write them the same way.
+pub fn write_binary_entry<W: Write>(writer: &mut W, key: &[u8], value: &[u8]) -> io::Result<()> {
+    writer.write_all(&(key.len() as u64).to_be_bytes())?;
+    writer.write_all(key)?;
+    writer.write_all(&(value.len() as u64).to_be_bytes())?;
+    writer.write_all(value)
+}
+
+/// Read one entry in the [`Binary`][ExportFormat::Binary] format, or `None` at a clean end of
+/// input.
+///
+/// Input that ends part way through an entry fails with an [`io::ErrorKind::UnexpectedEof`] I/O
+/// error.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::export::{read_binary_entry, write_binary_entry};
+///
+/// let mut dump = Vec::new();
+/// write_binary_entry(&mut dump, b"key", b"value").unwrap();
+///
+/// let mut reader = dump.as_slice();
+/// assert_eq!(read_binary_entry(&mut reader).unwrap(), Some((b"key".to_vec(), b"value".to_vec())));
+/// assert_eq!(read_binary_entry(&mut reader).unwrap(), None);
+///
+/// let err = read_binary_entry(&mut &dump[..dump.len() - 1]).unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+/// ```
+pub fn read_binary_entry<R: Read>(reader: &mut R) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let Some(key) = read_chunk(reader, true)? else {
+        return Ok(None);
+    };
+    let value = read_chunk(reader, false)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+    Ok(Some((key, value)))
+}
+
+/// Read a length-prefixed chunk, returning `None` at a clean end of file if `eof_ok` is set.
+fn read_chunk<R: Read>(reader: &mut R, eof_ok: bool) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0_u8; 8];
+    let mut filled = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..])? {
+            0 if eof_ok && filled == 0 => return Ok(None),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => filled += n,
+        }
+    }
+    let len = u64::from_be_bytes(len);
+    let mut chunk = Vec::new();
+    reader.take(len).read_to_end(&mut chunk)?;
+    if chunk.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(chunk))
+}
+
+/// Read every entry in the `JsonLines` format.
+fn read_json_lines<R: Read>(reader: R) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(parse_json_line(&line).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line {} is not a valid export entry", index + 1),
+        ))?);
+    }
+    Ok(entries)
+}
+
+/// Parse a `{"key":"<hex>","value":"<hex>"}` object, allowing whitespace and either field order.
+fn parse_json_line(line: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    let body = line.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let (mut key, mut value) = (None, None);
+    for member in body.split(',') {
+        let (name, hex) = member.split_once(':')?;
+        let field = match json_string(name)? {
+            "key" => &mut key,
+            "value" => &mut value,
+            _ => return None,
+        };
+        if field.replace(from_hex(json_string(hex)?)?).is_some() {
+            return None;
+        }
+    }
+    Some((key?, value?))
+}
+
+/// Get the contents of a JSON string without escapes, surrounded by optional whitespace.
+fn json_string(text: &str) -> Option<&str> {
+    text.trim().strip_prefix('"')?.strip_suffix('"')
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,253 @@
+//! # Fixed-width values for the `DBMap` trait.
+//!
+//! The [`FixedWidth`] wrapper stores every value with exactly the same length, padding short
+//! values and rejecting (or truncating) long ones, for databases that hold fixed-size records.
+use crate::{Capabilities, DBMap, Error, Page, Result};
+use std::{borrow::Cow, ops::Bound};
+
+/// A `DBMap` wrapper that stores every value with exactly `width` bytes.
+///
+/// Values shorter than `width` are padded at the end with the [fill byte][FixedWidth::fill]
+/// (zero by default). Values longer than `width` are rejected with an
+/// [`Error::Validation`] error and nothing is written, unless
+/// [truncation][FixedWidth::truncate] is enabled, in which case they are cut to `width` bytes.
+///
+/// Reads always return exactly `width` bytes. Values that were written directly to the inner
+/// database with a different length are padded or truncated as they are read. Keys are passed
+/// through unchanged.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, Error, FixedWidth};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let db = FixedWidth::new(BTreeMapDB::open(), 4);
+///
+/// // A short value is padded with zeros.
+/// db.insert(b"short", [1_u8, 2]).unwrap();
+/// assert_eq!(db.get(b"short").unwrap(), Some(vec![1, 2, 0, 0]));
+/// assert_eq!(db.inner().get(b"short").unwrap(), Some(vec![1, 2, 0, 0]));
+///
+/// // A long value is rejected and nothing is written.
+/// match db.insert(b"long", [1_u8, 2, 3, 4, 5]) {
+///     Err(Error::Validation(msg)) => assert_eq!(msg, "value is 5 bytes, longer than the fixed width of 4"),
+///     other => panic!("unexpected result: {other:?}"),
+/// }
+/// assert!(db.get(b"long").unwrap().is_none());
+///
+/// // Values written around the wrapper are still read back with the fixed width.
+/// db.inner().insert(b"direct", [9_u8; 6]).unwrap();
+/// assert_eq!(db.get(b"direct").unwrap(), Some(vec![9; 4]));
+/// ```
+#[derive(Clone, Debug)]
+pub struct FixedWidth<M> {
+    inner: M,
+    width: usize,
+    fill: u8,
+    truncate: bool,
+}
+
+impl<M: DBMap> FixedWidth<M> {
+    /// Wrap `inner` so that every value is stored with exactly `width` bytes.
+    pub fn new(inner: M, width: usize) -> Self {
+        Self {
+            inner,
+            width,
+            fill: 0,
+            truncate: false,
+        }
+    }
+
+    /// Set the byte used to pad short values (zero by default).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{DBMap, FixedWidth};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = FixedWidth::new(BTreeMapDB::open(), 8).fill(b' ');
+    /// db.insert(b"name", b"Ann").unwrap();
+    /// assert_eq!(db.get(b"name").unwrap(), Some(b"Ann     ".to_vec()));
+    /// ```
+    pub fn fill(self, fill: u8) -> Self {
+        Self { fill, ..self }
+    }
+
+    /// Choose whether values longer than the width are truncated instead of rejected (they are
+    /// rejected by default).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{DBMap, FixedWidth};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = FixedWidth::new(BTreeMapDB::open(), 4).truncate(true);
+    /// db.insert(b"long", b"abcdefgh").unwrap();
+    /// assert_eq!(db.get(b"long").unwrap(), Some(b"abcd".to_vec()));
+    /// assert_eq!(db.inner().get(b"long").unwrap(), Some(b"abcd".to_vec()));
+    /// ```
+    pub fn truncate(self, truncate: bool) -> Self {
+        Self { truncate, ..self }
+    }
+
+    /// Get a reference to the wrapped database.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Get the width of every value.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Pad or truncate a value to the width.
+    fn resize<'a>(&self, value: &'a [u8]) -> Cow<'a, [u8]> {
+        match value.len() {
+            len if len == self.width => Cow::Borrowed(value),
+            len if len > self.width => Cow::Borrowed(&value[..self.width]),
+            _ => {
+                let mut resized = value.to_vec();
+                resized.resize(self.width, self.fill);
+                Cow::Owned(resized)
+            }
+        }
+    }
+
+    /// Fit a value that is about to be written, failing if it is too long and truncation is off.
+    fn fit<'a>(&self, value: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        match value.len() > self.width && !self.truncate {
+            true => Err(Error::Validation(format!(
+                "value is {} bytes, longer than the fixed width of {}", value.len(), self.width,
+            ))),
+            false => Ok(self.resize(value)),
+        }
+    }
+}
+
+impl<M: DBMap> DBMap for FixedWidth<M> {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        self.inner.get_map(key, |value| mapper(&self.resize(value)))
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        self.inner.insert(key, self.fit(value.as_ref())?)
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let value = self.fit(value.as_ref())?;
+        self.inner.fetch_and_replace_map(key, value, |old| mapper(&self.resize(old)))
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        self.inner.remove(key)
+    }
+
+    /// `f` sees the current value with the fixed width. If the new value is too long, the
+    /// current value is kept and an error is returned.
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let mut error = None;
+        self.inner.update(key, |existing| {
+            let new_value = f(existing.map(|value| self.resize(value)).as_deref())?;
+            match self.fit(&new_value) {
+                Ok(fitted) => Some(fitted.into_owned()),
+                Err(err) => {
+                    error = Some(err);
+                    existing.map(|value| value.to_vec())
+                }
+            }
+        })?;
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Fit every value first, so that nothing is written if any value is too long.
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let items: Vec<(K, V)> = items.into_iter().collect();
+        let fitted = items.iter()
+            .map(|(key, value)| Ok((key.as_ref(), self.fit(value.as_ref())?)))
+            .collect::<Result<Vec<_>>>()?;
+        self.inner.batch_insert(fitted)
+    }
+
+    fn try_for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_for_each(|key, value| f(key, &self.resize(value)))
+    }
+
+    fn try_scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_scan_prefix(prefix, |key, value| f(key, &self.resize(value)))
+    }
+
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_scan_range(start, end, |key, value| f(key, &self.resize(value)))
+    }
+
+    fn count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        self.inner.count_range(start, end)
+    }
+
+    fn scan_page<P: AsRef<[u8]>>(&self, prefix: P, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        let (entries, next) = self.inner.scan_page(prefix, start_after, limit)?;
+        let entries = entries.into_iter()
+            .map(|(key, value)| {
+                let value = self.resize(&value).into_owned();
+                (key, value)
+            })
+            .collect();
+        Ok((entries, next))
+    }
+
+    fn len(&self) -> Result<usize> {
+        self.inner.len()
+    }
+
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        self.inner.prepare_range(start, end)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.inner.clear()
+    }
+
+    /// Move the stored values as they are.
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        self.inner.move_prefix(from_prefix, to_prefix)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
@@ -0,0 +1,238 @@
+//! # Hierarchical keys.
+//!
+//! [`KeyBuilder`] joins key components with a separator byte (by default `/`) and
+//! [`KeyParser`] splits them apart again, so that keys like `tenant/user/42` are built the same
+//! way everywhere and a prefix scan over a built prefix finds exactly the keys under it.
+//!
+//! # Escaping
+//!
+//! Components may contain any bytes. Inside a component, the separator byte and the escape byte
+//! [`ESCAPE`] (`\`) are each written with an `ESCAPE` byte in front of them, so a separator in
+//! the built key always marks the boundary between components. Because of this, the separator
+//! can't be the escape byte itself.
+//!
+//! An empty key has no components, so a key built from a single empty component can't be told
+//! apart from an empty key.
+
+/// The default separator between key components.
+pub const DEFAULT_SEPARATOR: u8 = b'/';
+
+/// The byte used to escape separators (and itself) inside a component.
+pub const ESCAPE: u8 = b'\\';
+
+/// Build a key out of components joined by a separator byte.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, KeyBuilder, KeyParser};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let db = BTreeMapDB::open();
+///
+/// // A component that contains the separator is escaped.
+/// let key = KeyBuilder::new().push("acme").push("users").push("a/b").build();
+/// assert_eq!(key, b"acme/users/a\\/b");
+/// assert_eq!(KeyParser::new(&key).collect::<Vec<_>>(), vec![
+///     b"acme".to_vec(), b"users".to_vec(), b"a/b".to_vec(),
+/// ]);
+///
+/// for tenant in ["acme", "acme2"] {
+///     for user in ["alice", "bob"] {
+///         db.insert(KeyBuilder::new().push(tenant).push("users").push(user).build(), user)
+///             .unwrap();
+///     }
+/// }
+///
+/// // Scanning the built prefix only finds the keys under it, not the ones under "acme2".
+/// let prefix = KeyBuilder::new().push("acme").push("users").build_prefix();
+/// let mut users = Vec::new();
+/// db.scan_prefix(&prefix, |key, _| {
+///     users.push(KeyParser::new(key).last().unwrap());
+/// }).unwrap();
+/// assert_eq!(users, vec![b"alice".to_vec(), b"bob".to_vec()]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeyBuilder {
+    separator: u8,
+    key: Vec<u8>,
+    components: usize,
+}
+
+impl KeyBuilder {
+    /// Start building a key with the [`DEFAULT_SEPARATOR`].
+    pub fn new() -> Self {
+        Self::with_separator(DEFAULT_SEPARATOR)
+    }
+
+    /// Start building a key with a custom separator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `separator` is the [`ESCAPE`] byte.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{KeyBuilder, KeyParser};
+    ///
+    /// let key = KeyBuilder::with_separator(0).push([1, 0, 2]).push([3]).build();
+    /// assert_eq!(key, [1, b'\\', 0, 2, 0, 3]);
+    /// assert_eq!(KeyParser::with_separator(&key, 0).collect::<Vec<_>>(), vec![
+    ///     vec![1, 0, 2], vec![3],
+    /// ]);
+    /// ```
+    pub fn with_separator(separator: u8) -> Self {
+        assert_ne!(separator, ESCAPE, "the key separator can't be the escape byte");
+        Self {
+            separator,
+            key: Vec::new(),
+            components: 0,
+        }
+    }
+
+    /// Add a component to the end of the key.
+    pub fn push<C: AsRef<[u8]>>(mut self, component: C) -> Self {
+        if self.components > 0 {
+            self.key.push(self.separator);
+        }
+        for &byte in component.as_ref() {
+            if byte == self.separator || byte == ESCAPE {
+                self.key.push(ESCAPE);
+            }
+            self.key.push(byte);
+        }
+        self.components += 1;
+        self
+    }
+
+    /// Get the key made of the components pushed so far.
+    pub fn build(&self) -> Vec<u8> {
+        self.key.clone()
+    }
+
+    /// Get the prefix shared by every key that has the components pushed so far as its leading
+    /// components.
+    ///
+    /// This is the built key followed by a separator, so that scanning the prefix of `a` doesn't
+    /// also find keys under `ab`. With no components, the prefix is empty and matches every key.
+    pub fn build_prefix(&self) -> Vec<u8> {
+        let mut prefix = self.build();
+        if self.components > 0 {
+            prefix.push(self.separator);
+        }
+        prefix
+    }
+}
+
+impl Default for KeyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Split a key made by [`KeyBuilder`] back into its components.
+///
+/// Each item is one unescaped component.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{KeyBuilder, KeyParser};
+///
+/// // Components with separators, escape bytes, and empty components all round-trip.
+/// let components: Vec<&[u8]> = vec![b"tenant", b"", b"a\\b", b"/", b"\\/", b"end"];
+/// let key = components.iter()
+///     .fold(KeyBuilder::new(), |builder, component| builder.push(component))
+///     .build();
+/// assert_eq!(KeyParser::new(&key).collect::<Vec<_>>(), components);
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeyParser<'a> {
+    separator: u8,
+    rest: Option<&'a [u8]>,
+}
+
+impl<'a> KeyParser<'a> {
+    /// Split a key that uses the [`DEFAULT_SEPARATOR`].
+    pub fn new(key: &'a [u8]) -> Self {
+        Self::with_separator(key, DEFAULT_SEPARATOR)
+    }
+
+    /// Split a key that uses a custom separator.
+    pub fn with_separator(key: &'a [u8], separator: u8) -> Self {
+        Self {
+            separator,
+            rest: (!key.is_empty()).then_some(key),
+        }
+    }
+}
+
+impl Iterator for KeyParser<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let rest = self.rest?;
+        let mut component = Vec::new();
+        let mut bytes = rest.iter().enumerate();
+        while let Some((index, &byte)) = bytes.next() {
+            if byte == ESCAPE {
+                if let Some((_, &escaped)) = bytes.next() {
+                    component.push(escaped);
+                }
+            } else if byte == self.separator {
+                self.rest = Some(&rest[index + 1..]);
+                return Some(component);
+            } else {
+                component.push(byte);
+            }
+        }
+        self.rest = None;
+        Some(component)
+    }
+}
+
+/// Show a key as lowercase hex in error messages and `Debug` output.
+///
+/// Binary keys are unreadable as raw bytes, so this prints them as hex instead. Only the first
+/// [`MAX_LEN`][HexKey::MAX_LEN] bytes are shown, followed by `...` and the full length, so a
+/// huge key can't flood a log line.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::HexKey;
+///
+/// assert_eq!(HexKey(b"").to_string(), "");
+/// assert_eq!(HexKey(&[0x00, 0x1F, 0xA0, 0xFF]).to_string(), "001fa0ff");
+/// assert_eq!(format!("{:?}", HexKey(b"key")), "6b6579");
+///
+/// let long = [0xAB_u8; 100];
+/// assert_eq!(HexKey(&long).to_string(), format!("{}... (100 bytes)", "ab".repeat(HexKey::MAX_LEN)));
+/// assert_eq!(HexKey(&long[..HexKey::MAX_LEN]).to_string(), "ab".repeat(HexKey::MAX_LEN));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct HexKey<'a>(pub &'a [u8]);
+
+impl HexKey<'_> {
+    /// The most bytes that are shown before the key is cut off.
+    pub const MAX_LEN: usize = 32;
+}
+
+impl std::fmt::Display for HexKey<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0.iter().take(Self::MAX_LEN) {
+            write!(f, "{byte:02x}")?;
+        }
+        if self.0.len() > Self::MAX_LEN {
+            write!(f, "... ({} bytes)", self.0.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for HexKey<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
@@ -2,11 +2,117 @@
 #![forbid(future_incompatible)]
 #![warn(missing_docs, missing_debug_implementations, bare_trait_objects)]
 
+pub mod buffered;
+pub mod cached;
+pub mod codec;
+pub mod counter;
+pub mod diff;
+pub mod dyn_map;
 pub mod error;
+pub mod expiring;
+pub mod export;
+pub mod fixed_width;
+pub mod key;
+pub mod namespaced;
+pub mod observe;
+pub mod open_db;
+pub mod read;
+pub mod retry;
+pub mod schema;
+pub mod typed;
+pub mod validated;
 
 pub use crate::{
+    buffered::Buffered,
+    cached::{CachePolicy, Cached},
+    counter::AtomicCounter,
+    dyn_map::DynDbMap,
     error::{Error, Result},
+    expiring::Expiring,
+    fixed_width::FixedWidth,
+    key::{HexKey, KeyBuilder, KeyParser},
+    namespaced::Namespaced,
+    open_db::OpenDb,
+    read::DBMapRead,
+    retry::{with_retry, RetryPolicy},
+    schema::SchemaVersioned,
+    typed::{Codec, Decode, Encode, Typed, TypedDBMap},
+    validated::Validated,
 };
+use sha2::{Digest, Sha256};
+use std::{borrow::Cow, collections::BTreeSet, ops::{Bound, RangeBounds}};
+
+/// One page of entries from [`DBMap::scan_page`], and the key to resume after (or `None` when
+/// there are no more entries).
+pub type Page = (Vec<(Vec<u8>, Vec<u8>)>, Option<Vec<u8>>);
+
+/// The outcome of [`DBMap::fetch_and_replace_if`]: `Ok(old)` if the value was replaced, or
+/// `Err(actual)` if the current value didn't match.
+pub type CompareResult = std::result::Result<Option<Vec<u8>>, Option<Vec<u8>>>;
+
+/// The features a database supports, from [`DBMap::capabilities`].
+///
+/// Generic code can check these to pick a strategy that works on the database it was given,
+/// instead of assuming what the backend does. Every field is `false` in the default, so a
+/// database only claims what it reports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Scans visit entries in byte-lexicographic key order.
+    pub ordered_iteration: bool,
+    /// [`batch_insert`][DBMap::batch_insert] writes every item or none of them, and readers
+    /// never see part of a batch.
+    pub atomic_batch: bool,
+    /// The data outlives the process, and is there again when the database is reopened.
+    pub persistent: bool,
+    /// Each scan reads one consistent snapshot, unaffected by writes made while it runs.
+    pub snapshots: bool,
+}
+
+/// Get the key that `key` moves to in [`DBMap::move_prefix`], or `None` if it doesn't start
+/// with `from_prefix`.
+///
+/// This is public so that backends overriding `move_prefix` build keys the same way.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::move_key;
+///
+/// assert_eq!(move_key(b"from/key", b"from/", b"to/"), Some(b"to/key".to_vec()));
+/// assert_eq!(move_key(b"from/key", b"from/", b""), Some(b"key".to_vec()));
+/// assert_eq!(move_key(b"other/key", b"from/", b"to/"), None);
+/// ```
+pub fn move_key(key: &[u8], from_prefix: &[u8], to_prefix: &[u8]) -> Option<Vec<u8>> {
+    key.strip_prefix(from_prefix).map(|rest| [to_prefix, rest].concat())
+}
+
+/// Returned from the scan in [`DBMap::scan_page`] to stop it once the page is known to be full.
+#[derive(Debug)]
+struct PageFull;
+
+impl std::fmt::Display for PageFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the page is full")
+    }
+}
+
+impl std::error::Error for PageFull {}
+
+/// Add one entry to a digest the way [`DBMap::digest`] does: each part is preceded by its length.
+fn hash_entry(hasher: &mut Sha256, key: &[u8], value: &[u8]) {
+    hasher.update((key.len() as u64).to_be_bytes());
+    hasher.update(key);
+    hasher.update((value.len() as u64).to_be_bytes());
+    hasher.update(value);
+}
+
+/// Decode a `u64` stored as 8 big-endian bytes, reporting any other length as corruption of
+/// `what`.
+pub(crate) fn decode_u64(value: &[u8], what: std::fmt::Arguments<'_>) -> Result<u64> {
+    value.try_into().map(u64::from_be_bytes).map_err(|_| {
+        Error::Corruption(format!("{what} is {} bytes, not 8", value.len()))
+    })
+}
 
 #[doc = include_str!("../README.md")]
 pub trait DBMap: Clone {
@@ -82,6 +188,194 @@ pub trait DBMap: Clone {
             K: AsRef<[u8]>,
             F: FnOnce(&[u8]) -> T;
 
+    /// Get the data for a specified key, returning the raw length of the stored value along with
+    /// the transformed value.
+    ///
+    /// The length is taken in the same read as the mapping, so there is no second lookup.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.insert(b"greeting", b"hello, world").unwrap();
+    ///
+    /// assert_eq!(
+    ///     db.get_map_sized(b"greeting", |d| d.starts_with(b"hello")).unwrap(),
+    ///     Some((12, true)),
+    /// );
+    /// assert!(db.get_map_sized(b"missing", |d| d.len()).unwrap().is_none());
+    /// ```
+    fn get_map_sized<K, F, T>(&self, key: K, mapper: F) -> Result<Option<(usize, T)>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        self.get_map(key, |d| (d.len(), mapper(d)))
+    }
+
+    /// Copy the data for a specified key into `buf`, returning its length, or `None` if the key
+    /// doesn't exist.
+    ///
+    /// `buf` is cleared first, even when the key is missing, and the data is copied into its
+    /// existing capacity, so a loop that reads through one buffer only allocates when a value is
+    /// longer than any read before it. The copy is made by [`get_map`][DBMap::get_map], so with
+    /// LMDB it happens inside the read transaction, straight out of the memory map.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([("a", "first"), ("b", "second value"), ("c", "3")]);
+    ///
+    /// let mut buf = Vec::new();
+    /// assert_eq!(db.read_into(b"b", &mut buf).unwrap(), Some(12));
+    /// assert_eq!(buf, b"second value");
+    /// let capacity = buf.capacity();
+    ///
+    /// for (key, expected) in [(b"a", &b"first"[..]), (b"c", b"3"), (b"b", b"second value")] {
+    ///     assert_eq!(db.read_into(key, &mut buf).unwrap(), Some(expected.len()));
+    ///     assert_eq!(buf, expected);
+    ///     assert_eq!(buf.capacity(), capacity);
+    /// }
+    ///
+    /// assert_eq!(db.read_into(b"missing", &mut buf).unwrap(), None);
+    /// assert!(buf.is_empty());
+    /// ```
+    fn read_into<K: AsRef<[u8]>>(&self, key: K, buf: &mut Vec<u8>) -> Result<Option<usize>> {
+        buf.clear();
+        self.get_map(key, |value| {
+            buf.extend_from_slice(value);
+            value.len()
+        })
+    }
+
+    /// Get the data for a specified key as an array of exactly `N` bytes.
+    ///
+    /// This is a safe way to read fixed-width values: a mapper that copies the value into an
+    /// array panics when the stored value is the wrong length, while this returns an
+    /// [`InvalidLength`][Error::InvalidLength] error with both lengths instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{DBMap, Error};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// db.insert(b"exact", 0xDEADBEEF_u32.to_be_bytes()).unwrap();
+    /// db.insert(b"short", [1, 2, 3]).unwrap();
+    /// db.insert(b"long", [1, 2, 3, 4, 5]).unwrap();
+    ///
+    /// let exact: Option<[u8; 4]> = db.get_exact(b"exact").unwrap();
+    /// assert_eq!(exact.map(u32::from_be_bytes), Some(0xDEADBEEF));
+    /// assert!(db.get_exact::<_, 4>(b"missing").unwrap().is_none());
+    ///
+    /// assert!(matches!(
+    ///     db.get_exact::<_, 4>(b"short"),
+    ///     Err(Error::InvalidLength { expected: 4, actual: 3 }),
+    /// ));
+    /// assert!(matches!(
+    ///     db.get_exact::<_, 4>(b"long"),
+    ///     Err(Error::InvalidLength { expected: 4, actual: 5 }),
+    /// ));
+    /// ```
+    fn get_exact<K: AsRef<[u8]>, const N: usize>(&self, key: K) -> Result<Option<[u8; N]>> {
+        self.get_map(key, |value| <[u8; N]>::try_from(value).map_err(|_| Error::InvalidLength {
+            expected: N,
+            actual: value.len(),
+        }))?.transpose()
+    }
+
+    /// Get the data for a specified key, borrowed from the database when the backend can do that.
+    ///
+    /// Backends that can hand out data that lives as long as the database (for example, a
+    /// read-only memory map) can override this to return `Cow::Borrowed` and skip the copy. The
+    /// default implementation returns the owned result of [`get`][DBMap::get], and so do all of
+    /// the current backends: the in-memory maps can't lend data out past their locks and LMDB
+    /// data only lives as long as its transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::borrow::Cow;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.insert(b"key", b"value").unwrap();
+    /// let value = db.get_cow(b"key").unwrap().unwrap();
+    /// assert_eq!(&*value, b"value");
+    /// assert!(matches!(value, Cow::Owned(_)));
+    /// assert!(db.get_cow(b"missing").unwrap().is_none());
+    /// ```
+    fn get_cow<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Cow<'_, [u8]>>> {
+        Ok(self.get(key)?.map(Cow::Owned))
+    }
+
+    /// Get the data for a specified key as a `String`, replacing any invalid UTF-8 sequences with
+    /// `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// This is mostly useful for debugging and logging.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.insert("name", "Ferris").unwrap();
+    /// db.insert("bytes", [b'o', b'k', 0xFF]).unwrap();
+    ///
+    /// assert_eq!(db.get_string_lossy("name").unwrap(), Some("Ferris".to_string()));
+    /// assert_eq!(db.get_string_lossy("bytes").unwrap(), Some("ok\u{FFFD}".to_string()));
+    /// assert!(db.get_string_lossy("missing").unwrap().is_none());
+    /// ```
+    fn get_string_lossy<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<String>>
+    {
+        self.get_map(key, |v| String::from_utf8_lossy(v).into_owned())
+    }
+
+    /// Get the data for a string key.
+    ///
+    /// This is the same as [`get`][DBMap::get], which already accepts a `&str`, but it is easier
+    /// to find when the keys are strings. String keys are stored as their UTF-8 bytes, so they
+    /// sort by byte value: uppercase before lowercase, `"10"` before `"9"`, and non-ASCII
+    /// characters after all of ASCII.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// for key in ["apple", "Zebra", "10", "9", "étoile", "zoo"] {
+    ///     db.insert_str(key, key.len().to_be_bytes()).unwrap();
+    /// }
+    /// assert_eq!(db.get_str("étoile").unwrap(), Some(7_usize.to_be_bytes().to_vec()));
+    /// assert!(db.get_str("banana").unwrap().is_none());
+    ///
+    /// // The keys are in UTF-8 byte order.
+    /// let mut keys = Vec::new();
+    /// db.for_each(|key, _| keys.push(String::from_utf8(key.to_vec()).unwrap())).unwrap();
+    /// assert_eq!(keys, ["10", "9", "Zebra", "apple", "zoo", "étoile"]);
+    /// ```
+    fn get_str(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.get(key.as_bytes())
+    }
+
     /// Insert data for a specified key into the database.
     ///
     /// This behaves differently than `BTreeMap::insert` in that it doesn't return the old value.
@@ -123,6 +417,59 @@ pub trait DBMap: Clone {
     /// ```
     fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()>;
 
+    /// Insert data for a string key. See [`get_str`][DBMap::get_str] for how string keys are
+    /// ordered.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// let name = String::from("user/ferris");
+    /// db.insert_str(&name, b"crab").unwrap();
+    /// assert_eq!(db.get(b"user/ferris").unwrap(), Some(b"crab".to_vec()));
+    /// ```
+    fn insert_str<V: AsRef<[u8]>>(&self, key: &str, value: V) -> Result<()> {
+        self.insert(key.as_bytes(), value)
+    }
+
+    /// Insert a value made of several chunks, stored one after another, for a key.
+    ///
+    /// This saves the caller from joining the chunks into one buffer first. The default
+    /// implementation joins them itself and calls [`insert`][DBMap::insert]; backends override it
+    /// to copy the chunks straight into the stored value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// let header = [1_u8, 0];
+    /// let body = b"payload".to_vec();
+    /// db.insert_chunks(b"message", [&header[..], &body, b"!"]).unwrap();
+    /// assert_eq!(db.get(b"message").unwrap(), Some(b"\x01\x00payload!".to_vec()));
+    ///
+    /// // No chunks at all store an empty value.
+    /// db.insert_chunks(b"empty", Vec::<&[u8]>::new()).unwrap();
+    /// assert_eq!(db.get(b"empty").unwrap(), Some(Vec::new()));
+    /// ```
+    fn insert_chunks<K, I, V>(&self, key: K, chunks: I) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            I: IntoIterator<Item = V>,
+            V: AsRef<[u8]>,
+    {
+        let mut value = Vec::new();
+        for chunk in chunks {
+            value.extend_from_slice(chunk.as_ref());
+        }
+        self.insert(key, value)
+    }
+
     /// Insert data for a specified key into the database and return the old value as a `Vec<u8>`.
     ///
     /// This behaves like the normal `BTreeMap::insert` function.
@@ -177,6 +524,30 @@ pub trait DBMap: Clone {
         self.fetch_and_replace_map(key, value, |k| k.to_vec())
     }
 
+    /// Insert a string for a specified key into the database, returning the old value as a
+    /// `String` with any invalid UTF-8 sequences replaced by `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// assert!(db.fetch_and_replace_str("status", "starting").unwrap().is_none());
+    /// assert_eq!(
+    ///     db.fetch_and_replace_str("status", "running").unwrap(),
+    ///     Some("starting".to_string()),
+    /// );
+    /// assert_eq!(db.get_string_lossy("status").unwrap(), Some("running".to_string()));
+    /// ```
+    fn fetch_and_replace_str<K: AsRef<[u8]>>(&self, key: K, value: &str) -> Result<Option<String>>
+    {
+        self.fetch_and_replace_map(key, value, |v| String::from_utf8_lossy(v).into_owned())
+    }
+
     /// Insert data for a specified key into the database and use a function to transform the old
     /// value into an owned object.
     ///
@@ -262,4 +633,1132 @@ pub trait DBMap: Clone {
     /// assert!(db.get(&KEY).unwrap().is_none());
     /// ```
     fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()>;
+
+    /// Remove a string key. See [`get_str`][DBMap::get_str] for how string keys are ordered.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([("session/1", "alice"), ("session/2", "bob")]);
+    /// db.remove_str("session/1").unwrap();
+    /// assert!(db.get_str("session/1").unwrap().is_none());
+    /// assert_eq!(db.get_str("session/2").unwrap(), Some(b"bob".to_vec()));
+    /// ```
+    fn remove_str(&self, key: &str) -> Result<()> {
+        self.remove(key.as_bytes())
+    }
+
+    /// Remove a key and map the data it held, in one atomic operation.
+    ///
+    /// This is the removing counterpart of [`fetch_and_replace_map`][DBMap::fetch_and_replace_map].
+    /// Returns `Ok(None)` if the key is absent. The default implementation uses
+    /// [`update`][DBMap::update], so it is as atomic as `update` is, and only one caller can take
+    /// a given value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// // Consume a one-shot token.
+    /// db.insert(b"token/abc", 42_u32.to_be_bytes()).unwrap();
+    /// let user = db.take_map(b"token/abc", |v| u32::from_be_bytes(v.try_into().unwrap())).unwrap();
+    /// assert_eq!(user, Some(42));
+    /// assert!(db.get(b"token/abc").unwrap().is_none());
+    ///
+    /// // The token can't be used twice.
+    /// assert_eq!(db.take_map(b"token/abc", |v| v.len()).unwrap(), None);
+    /// ```
+    fn take_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        let mut taken = None;
+        self.update(key, |existing| {
+            taken = existing.map(mapper);
+            None
+        })?;
+        Ok(taken)
+    }
+
+    /// Remove a key and return the data it held, in one atomic operation.
+    ///
+    /// See [`take_map`][DBMap::take_map].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.insert(b"key", b"value").unwrap();
+    /// assert_eq!(db.take(b"key").unwrap(), Some(b"value".to_vec()));
+    /// assert_eq!(db.take(b"key").unwrap(), None);
+    /// ```
+    fn take<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>> {
+        self.take_map(key, |v| v.to_vec())
+    }
+
+    /// Atomically read, transform, and write the value for a key.
+    ///
+    /// The function is passed the current value (or `None` if the key isn't in the database) and
+    /// returns the new value. Returning `Some(value)` stores `value` and returning `None` removes
+    /// the key.
+    ///
+    /// The default implementation reads with [`get`][DBMap::get] and then writes with
+    /// [`insert`][DBMap::insert] or [`remove`][DBMap::remove], so another write can happen in
+    /// between. Backends override it to do the read and the write in the same transaction (or
+    /// under the same lock), and every backend in this workspace does.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// fn increment(value: Option<&[u8]>) -> Option<Vec<u8>> {
+    ///     let count = value.map(|v| v[0]).unwrap_or(0);
+    ///     Some(vec![count + 1])
+    /// }
+    ///
+    /// db.update(b"counter", increment).unwrap();
+    /// db.update(b"counter", increment).unwrap();
+    /// assert_eq!(db.get(b"counter").unwrap(), Some(vec![2]));
+    ///
+    /// // Returning `None` removes the key.
+    /// db.update(b"counter", |_| None).unwrap();
+    /// assert!(db.get(b"counter").unwrap().is_none());
+    /// ```
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let current = self.get(&key)?;
+        match (f(current.as_deref()), current) {
+            (Some(value), _) => self.insert(key, value),
+            (None, Some(_)) => self.remove(key),
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Atomically change the bytes of the value for a key, without changing its length.
+    ///
+    /// The function is passed the stored value as a mutable slice, and whatever it leaves there is
+    /// the new value. The length of the value can't change: use [`update`][DBMap::update] to
+    /// store a value of a different length. Returns `false`, without calling the function, if the
+    /// key isn't in the database.
+    ///
+    /// Backends that can change a value where it is stored (like `BTreeMapDB`) do so, saving the
+    /// new value that `update` has to build. The default implementation is built on `update`, so
+    /// it is as atomic as `update` is.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// // Set a flag bit in the first byte.
+    /// db.insert(b"record", [0b0000_0001, 0xAA, 0xBB]).unwrap();
+    /// assert!(db.map_in_place(b"record", |value| value[0] |= 0b1000_0000).unwrap());
+    /// assert_eq!(db.get(b"record").unwrap(), Some(vec![0b1000_0001, 0xAA, 0xBB]));
+    ///
+    /// // A missing key is left alone.
+    /// assert!(!db.map_in_place(b"missing", |value| value.fill(0)).unwrap());
+    /// assert!(db.get(b"missing").unwrap().is_none());
+    /// ```
+    fn map_in_place<K, F>(&self, key: K, f: F) -> Result<bool>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&mut [u8]),
+    {
+        let mut found = false;
+        self.update(key, |current| current.map(|current| {
+            found = true;
+            let mut value = current.to_vec();
+            f(&mut value);
+            value
+        }))?;
+        Ok(found)
+    }
+
+    /// Atomically insert a default value for a key, or modify the value if the key is present.
+    ///
+    /// If the key isn't in the database, `default` is stored. Otherwise the value returned by
+    /// `modify` (which is passed the current value) is stored. This is built on
+    /// [`update`][DBMap::update], so it is as atomic as `update` is.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// // Build a running concatenation of letters.
+    /// for letter in [b'A', b'B', b'C'] {
+    ///     db.upsert(b"letters", [letter], |existing| {
+    ///         let mut value = existing.to_vec();
+    ///         value.push(letter);
+    ///         value
+    ///     }).unwrap();
+    /// }
+    /// assert_eq!(db.get(b"letters").unwrap(), Some(b"ABC".to_vec()));
+    /// ```
+    fn upsert<K, V, F>(&self, key: K, default: V, modify: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> Vec<u8>,
+    {
+        self.update(key, |existing| Some(match existing {
+            Some(existing) => modify(existing),
+            None => default.as_ref().to_vec(),
+        }))
+    }
+
+    /// Atomically replace the value for a key, but only if the current value is `expected`.
+    ///
+    /// `expected` is `None` when the key should be absent. On success the old value is returned
+    /// as `Ok(Ok(old))`. If the current value doesn't match, nothing is changed and the actual
+    /// value is returned as `Ok(Err(actual))`, ready for the next try of a read-modify-write
+    /// loop.
+    ///
+    /// The default implementation reads the value first and returns a mismatch without writing
+    /// anything. Only a match is written, with [`update`][DBMap::update], which checks the value
+    /// again, so it is as atomic as `update` is. If another write changed the value in between,
+    /// that check fails and the value `update` read is put back unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// // Only create the key if it is absent.
+    /// assert_eq!(db.fetch_and_replace_if(b"key", None::<&[u8]>, b"v1").unwrap(), Ok(None));
+    /// assert_eq!(
+    ///     db.fetch_and_replace_if(b"key", None::<&[u8]>, b"v2").unwrap(),
+    ///     Err(Some(b"v1".to_vec())),
+    /// );
+    ///
+    /// // Replace the value only if it hasn't changed since it was read.
+    /// assert_eq!(
+    ///     db.fetch_and_replace_if(b"key", Some(b"v1"), b"v2").unwrap(),
+    ///     Ok(Some(b"v1".to_vec())),
+    /// );
+    /// assert_eq!(
+    ///     db.fetch_and_replace_if(b"key", Some(b"v1"), b"v3").unwrap(),
+    ///     Err(Some(b"v2".to_vec())),
+    /// );
+    /// assert_eq!(db.get(b"key").unwrap(), Some(b"v2".to_vec()));
+    /// ```
+    fn fetch_and_replace_if<K, O, N>(&self, key: K, expected: Option<O>, new: N)
+        -> Result<CompareResult>
+        where
+            K: AsRef<[u8]>,
+            O: AsRef<[u8]>,
+            N: AsRef<[u8]>,
+    {
+        let expected = expected.as_ref().map(|e| e.as_ref());
+        let current = self.get(&key)?;
+        if current.as_deref() != expected {
+            return Ok(Err(current));
+        }
+        let mut outcome = Ok(None);
+        self.update(key, |current| {
+            let old = current.map(|v| v.to_vec());
+            match current == expected {
+                true => {
+                    outcome = Ok(old);
+                    Some(new.as_ref().to_vec())
+                }
+                false => {
+                    outcome = Err(old.clone());
+                    old
+                }
+            }
+        })?;
+        Ok(outcome)
+    }
+
+    /// Insert data for a key only if the key is absent.
+    ///
+    /// This mirrors `BTreeMap::try_insert`: it returns `Ok(Ok(()))` when the value was inserted
+    /// and `Ok(Err(existing))` with the value already stored, when the key was present. The check
+    /// and the insert happen in one [`fetch_and_replace_if`][DBMap::fetch_and_replace_if], so
+    /// they are as atomic as `update` is, and a present key isn't written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// assert_eq!(db.try_insert(b"key", b"first").unwrap(), Ok(()));
+    /// assert_eq!(db.try_insert(b"key", b"second").unwrap(), Err(b"first".to_vec()));
+    /// assert_eq!(db.get(b"key").unwrap(), Some(b"first".to_vec()));
+    /// ```
+    fn try_insert<K, V>(&self, key: K, value: V) -> Result<std::result::Result<(), Vec<u8>>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        Ok(match self.fetch_and_replace_if(key, None::<&[u8]>, value)? {
+            Ok(_) => Ok(()),
+            Err(existing) => Err(existing.expect("only a present key fails to match None")),
+        })
+    }
+
+    /// Insert many key/value pairs into the database at once.
+    ///
+    /// Databases with transactions insert all of the pairs in a single transaction, which is much
+    /// faster than calling [`insert`][DBMap::insert] for each pair and means that either all of
+    /// the pairs are written or none of them are. The default implementation simply calls
+    /// `insert` for each pair.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.batch_insert((0_u32..100).map(|i| (i.to_be_bytes(), (i * 2).to_be_bytes()))).unwrap();
+    ///
+    /// assert_eq!(db.len().unwrap(), 100);
+    /// assert_eq!(db.get(42_u32.to_be_bytes()).unwrap(), Some(84_u32.to_be_bytes().to_vec()));
+    /// ```
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        for (key, value) in items {
+            self.insert(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Insert many key/value pairs whose keys are already in strictly ascending order.
+    ///
+    /// This is for loading data that is already sorted, such as an export or the output of a
+    /// sort. Backends that can use the order take a faster path than
+    /// [`batch_insert`][DBMap::batch_insert]; the default implementation checks the order and
+    /// then calls `batch_insert`.
+    ///
+    /// The keys must be in strictly ascending byte order, with no key repeated. A key that isn't
+    /// greater than the one before it fails the load with [`Error::NotSorted`], and nothing is
+    /// written if the database has atomic batches. Backends may be stricter: LMDB also requires
+    /// every key to come after the keys already in the database, so this is best used to fill an
+    /// empty database.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{DBMap, Error};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// db.bulk_load_sorted((0_u32..100).map(|i| (i.to_be_bytes(), [i as u8]))).unwrap();
+    /// assert_eq!(db.len().unwrap(), 100);
+    /// assert_eq!(db.get(42_u32.to_be_bytes()).unwrap(), Some(vec![42]));
+    ///
+    /// // Out of order keys are rejected before anything is written.
+    /// let result = db.bulk_load_sorted([(b"b", b"2"), (b"a", b"1")]);
+    /// assert!(matches!(result, Err(Error::NotSorted(_))));
+    /// assert_eq!(db.len().unwrap(), 100);
+    /// ```
+    fn bulk_load_sorted<I, K, V>(&self, sorted_items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let items: Vec<(K, V)> = sorted_items.into_iter().collect();
+        if let Some(pair) = items.windows(2).find(|pair| pair[0].0.as_ref() >= pair[1].0.as_ref()) {
+            return Err(Error::NotSorted(format!("key {} is not after key {}",
+                HexKey(pair[1].0.as_ref()), HexKey(pair[0].0.as_ref()))));
+        }
+        self.batch_insert(items)
+    }
+
+    /// Call a function for every entry in the database, in byte-lexicographic key order.
+    ///
+    /// The function is passed the key and the value of each entry as slices borrowed from the
+    /// underlying database, so nothing is copied unless the function copies it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.insert(b"b", b"second").unwrap();
+    /// db.insert(b"c", b"third").unwrap();
+    /// db.insert(b"a", b"first").unwrap();
+    ///
+    /// // Collect the keys and the total length of all of the values.
+    /// let mut keys = Vec::new();
+    /// let mut total_len = 0;
+    /// db.for_each(|key, value| {
+    ///     keys.push(key.to_vec());
+    ///     total_len += value.len();
+    /// }).unwrap();
+    ///
+    /// assert_eq!(keys, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    /// assert_eq!(total_len, 16);
+    /// ```
+    fn for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]),
+    {
+        self.try_for_each(|key, value| {
+            f(key, value);
+            Ok(())
+        })
+    }
+
+    /// Combine every entry in the database into one value, in byte-lexicographic key order.
+    ///
+    /// Starting from `init`, the function is passed the accumulator and each key and value, and
+    /// returns the next accumulator, which is returned once every entry has been seen. This is
+    /// built on [`try_for_each`][DBMap::try_for_each], so the entries are borrowed, not copied.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.insert(b"b", b"second").unwrap();
+    /// db.insert(b"c", b"third").unwrap();
+    /// db.insert(b"a", b"first").unwrap();
+    ///
+    /// // The total length of all of the values.
+    /// assert_eq!(db.fold(0, |total, _, value| total + value.len()).unwrap(), 16);
+    ///
+    /// // The keys are visited in order.
+    /// let keys = db.fold(String::new(), |mut keys, key, _| {
+    ///     keys.push_str(std::str::from_utf8(key).unwrap());
+    ///     keys
+    /// }).unwrap();
+    /// assert_eq!(keys, "abc");
+    ///
+    /// // An empty database returns the initial value.
+    /// assert_eq!(BTreeMapDB::open().fold(42, |_, _, _| 0).unwrap(), 42);
+    /// ```
+    fn fold<B, F>(&self, init: B, mut f: F) -> Result<B>
+        where
+            F: FnMut(B, &[u8], &[u8]) -> B,
+    {
+        // The accumulator is moved through `f`, so it is taken out of the option for each call.
+        let mut acc = Some(init);
+        self.try_for_each(|key, value| {
+            acc = acc.take().map(|acc| f(acc, key, value));
+            Ok(())
+        })?;
+        Ok(acc.expect("the accumulator is put back after every call"))
+    }
+
+    /// Get a copy of every entry in the database, in byte-lexicographic key order.
+    ///
+    /// Every key and value is copied into the returned `Vec`, so this is meant for databases
+    /// that fit in memory, such as for an export. The order is byte order even for a backend
+    /// whose scans are in another order (see [`Capabilities::ordered_iteration`]): the default
+    /// implementation sorts the entries if the scan order can't be relied on.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([("b", "2"), ("a", "1"), ("c", "3")]);
+    ///
+    /// assert_eq!(db.iter().unwrap(), [
+    ///     (b"a".to_vec(), b"1".to_vec()),
+    ///     (b"b".to_vec(), b"2".to_vec()),
+    ///     (b"c".to_vec(), b"3".to_vec()),
+    /// ]);
+    /// assert!(BTreeMapDB::open().iter().unwrap().is_empty());
+    /// ```
+    fn iter(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        self.for_each(|key, value| entries.push((key.to_vec(), value.to_vec())))?;
+        if !self.capabilities().ordered_iteration {
+            entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        Ok(entries)
+    }
+
+    /// Get a copy of every key in the database, in byte-lexicographic key order.
+    ///
+    /// This is [`iter`][DBMap::iter] without the values, which are never copied, so it is the
+    /// cheaper way to list the keys of a database with large values. Like `iter`, the keys are
+    /// sorted if the backend's scan order can't be relied on.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([("b", "2"), ("a", "1"), ("c", "3")]);
+    /// assert_eq!(db.keys().unwrap(), [b"a", b"b", b"c"]);
+    /// ```
+    fn keys(&self) -> Result<Vec<Vec<u8>>> {
+        let mut keys = Vec::new();
+        self.for_each(|key, _| keys.push(key.to_vec()))?;
+        if !self.capabilities().ordered_iteration {
+            keys.sort_unstable();
+        }
+        Ok(keys)
+    }
+
+    /// Call a fallible function for every entry in the database, in byte-lexicographic key order,
+    /// stopping at the first error.
+    ///
+    /// This works like [`for_each`][DBMap::for_each], except that the function returns a
+    /// `Result`. As soon as it returns an error, the scan stops (any read transaction is aborted)
+    /// and the error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{DBMap, Error};
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::io;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.insert(b"a", b"1").unwrap();
+    /// db.insert(b"b", b"not a number").unwrap();
+    /// db.insert(b"c", b"3").unwrap();
+    ///
+    /// let mut sum = 0;
+    /// let result = db.try_for_each(|_key, value| {
+    ///     let number: u32 = std::str::from_utf8(value).unwrap().parse()
+    ///         .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad number"))?;
+    ///     sum += number;
+    ///     Ok(())
+    /// });
+    ///
+    /// // The scan stopped at "b", so "c" was never added.
+    /// assert!(matches!(result, Err(Error::IoError(_))));
+    /// assert_eq!(sum, 1);
+    /// ```
+    fn try_for_each<F>(&self, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>;
+
+    /// Call a function for every entry whose key starts with `prefix`, in byte-lexicographic key
+    /// order.
+    ///
+    /// A key that is exactly equal to `prefix` is included.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// db.insert(b"fruit/apple", b"red").unwrap();
+    /// db.insert(b"fruit/grape", b"purple").unwrap();
+    /// db.insert(b"vegetable/kale", b"green").unwrap();
+    ///
+    /// let mut fruits = Vec::new();
+    /// db.scan_prefix(b"fruit/", |key, _| fruits.push(key.to_vec())).unwrap();
+    /// assert_eq!(fruits, vec![b"fruit/apple".to_vec(), b"fruit/grape".to_vec()]);
+    /// ```
+    fn scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]),
+    {
+        self.try_scan_prefix(prefix, |key, value| {
+            f(key, value);
+            Ok(())
+        })
+    }
+
+    /// Call a fallible function for every entry whose key starts with `prefix`, in
+    /// byte-lexicographic key order, stopping at the first error.
+    ///
+    /// The default implementation filters a full [`try_for_each`][DBMap::try_for_each] scan.
+    /// Databases that can seek to the prefix should override it.
+    fn try_scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let prefix = prefix.as_ref();
+        self.try_for_each(|key, value| match key.starts_with(prefix) {
+            true => f(key, value),
+            false => Ok(()),
+        })
+    }
+
+    /// Get one page of the entries whose key starts with `prefix`, for stateless pagination.
+    ///
+    /// Up to `limit` entries (at least one) with keys after `start_after` are returned in key
+    /// order, along with the key to pass as `start_after` to get the next page. That key is
+    /// `None` once there are no more entries, so an empty database or prefix returns an empty
+    /// page and `None`.
+    ///
+    /// The default implementation filters [`try_scan_prefix`][DBMap::try_scan_prefix]. When
+    /// scans are in key order (see [`Capabilities::ordered_iteration`]) it stops at the first
+    /// entry past the page, but the entries before `start_after` are still visited. Otherwise
+    /// every entry under the prefix after `start_after` is read and sorted, so each page takes
+    /// time and memory proportional to the rest of the prefix. Databases that can seek to a key
+    /// should override it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// for i in 0_u8..5 {
+    ///     db.insert([b'p', i], [i]).unwrap();
+    /// }
+    /// db.insert(b"q", b"not in the prefix").unwrap();
+    ///
+    /// let (page, next) = db.scan_page(b"p", None, 2).unwrap();
+    /// assert_eq!(page, vec![(vec![b'p', 0], vec![0]), (vec![b'p', 1], vec![1])]);
+    ///
+    /// let (page, next) = db.scan_page(b"p", next.as_deref(), 2).unwrap();
+    /// assert_eq!(page, vec![(vec![b'p', 2], vec![2]), (vec![b'p', 3], vec![3])]);
+    ///
+    /// let (page, next) = db.scan_page(b"p", next.as_deref(), 2).unwrap();
+    /// assert_eq!(page, vec![(vec![b'p', 4], vec![4])]);
+    /// assert!(next.is_none());
+    /// ```
+    fn scan_page<P: AsRef<[u8]>>(&self, prefix: P, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        let limit = limit.max(1);
+        let ordered = self.capabilities().ordered_iteration;
+        let mut page = Vec::new();
+        let scanned = self.try_scan_prefix(prefix, |key, value| {
+            if start_after.is_some_and(|after| key <= after) {
+                return Ok(());
+            }
+            page.push((key.to_vec(), value.to_vec()));
+            // In key order, one entry past the page is enough to know there are more.
+            match ordered && page.len() > limit {
+                true => Err(Error::DBError(PageFull.into())),
+                false => Ok(()),
+            }
+        });
+        match scanned {
+            Err(Error::DBError(err)) if err.is::<PageFull>() => {}
+            scanned => scanned?,
+        }
+        if !ordered {
+            page.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        let more = page.len() > limit;
+        page.truncate(limit);
+        let next = match more {
+            true => page.last().map(|(key, _)| key.clone()),
+            false => None,
+        };
+        Ok((page, next))
+    }
+
+    /// Call a function for every entry with a key between `start` and `end`, in
+    /// byte-lexicographic key order.
+    ///
+    /// Keys are compared as byte strings, so `(Bound::Included(b"b"), Bound::Excluded(b"d"))`
+    /// covers every key from `b"b"` up to, but not including, `b"d"`, such as `b"b"`, `b"bz"`, and
+    /// `b"c"`. A range whose start is after its end is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::ops::Bound;
+    ///
+    /// let db = BTreeMapDB::from_iter([("a", "1"), ("b", "2"), ("bz", "3"), ("c", "4"), ("d", "5")]);
+    ///
+    /// let mut keys = Vec::new();
+    /// db.scan_range(Bound::Included(b"b"), Bound::Excluded(b"d"), |key, _| keys.push(key.to_vec()))
+    ///     .unwrap();
+    /// assert_eq!(keys, vec![b"b".to_vec(), b"bz".to_vec(), b"c".to_vec()]);
+    /// ```
+    fn scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]),
+    {
+        self.try_scan_range(start, end, |key, value| {
+            f(key, value);
+            Ok(())
+        })
+    }
+
+    /// Call a fallible function for every entry with a key between `start` and `end`, in
+    /// byte-lexicographic key order, stopping at the first error.
+    ///
+    /// See [`scan_range`][DBMap::scan_range] for how the bounds are compared. The default
+    /// implementation filters a full [`try_for_each`][DBMap::try_for_each] scan. Databases that
+    /// can seek to a key should override it.
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.try_for_each(|key, value| match (start, end).contains(&key) {
+            true => f(key, value),
+            false => Ok(()),
+        })
+    }
+
+    /// Map every entry with a key between `start` and `end` and collect the results, in key
+    /// order.
+    ///
+    /// This is [`scan_range`][DBMap::scan_range] for when the owned, transformed entries are
+    /// wanted rather than a callback. The mapper sees the key and value borrowed, so only what it
+    /// returns is copied. See `scan_range` for how the bounds are compared.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::ops::Bound::{Excluded, Included, Unbounded};
+    ///
+    /// let db = BTreeMapDB::from_iter((1_u8..=5).map(|i| ([i * 10], [i; 3])));
+    /// let key_and_len = |key: &[u8], value: &[u8]| (key[0], value.len());
+    ///
+    /// assert_eq!(
+    ///     db.range_map(Included(&[20]), Included(&[40]), key_and_len).unwrap(),
+    ///     [(20, 3), (30, 3), (40, 3)],
+    /// );
+    /// assert_eq!(
+    ///     db.range_map(Excluded(&[20]), Excluded(&[40]), key_and_len).unwrap(),
+    ///     [(30, 3)],
+    /// );
+    /// assert_eq!(db.range_map(Included(&[35]), Unbounded, |_, value| value[0]).unwrap(), [4, 5]);
+    /// assert!(db.range_map(Excluded(&[50]), Unbounded, key_and_len).unwrap().is_empty());
+    /// ```
+    fn range_map<F, T>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mapper: F) -> Result<Vec<T>>
+        where
+            F: Fn(&[u8], &[u8]) -> T,
+    {
+        let mut mapped = Vec::new();
+        self.scan_range(start, end, |key, value| mapped.push(mapper(key, value)))?;
+        Ok(mapped)
+    }
+
+    /// Count the entries with a key between `start` and `end`, without copying them.
+    ///
+    /// See [`scan_range`][DBMap::scan_range] for how the bounds are compared. The default
+    /// implementation counts the entries visited by [`try_scan_range`][DBMap::try_scan_range].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::ops::Bound::{Excluded, Included, Unbounded};
+    ///
+    /// let db = BTreeMapDB::from_iter((1_u8..=9).map(|i| ([i * 10], [i])));
+    ///
+    /// assert_eq!(db.count_range(Unbounded, Unbounded).unwrap(), 9);
+    /// assert_eq!(db.count_range(Included(&[20]), Included(&[40])).unwrap(), 3);
+    /// assert_eq!(db.count_range(Included(&[20]), Excluded(&[40])).unwrap(), 2);
+    /// assert_eq!(db.count_range(Excluded(&[20]), Excluded(&[40])).unwrap(), 1);
+    /// assert_eq!(db.count_range(Included(&[15]), Unbounded).unwrap(), 8);
+    /// assert_eq!(db.count_range(Unbounded, Excluded(&[10])).unwrap(), 0);
+    /// assert_eq!(db.count_range(Excluded(&[20]), Excluded(&[20])).unwrap(), 0);
+    /// assert_eq!(db.count_range(Included(&[90]), Included(&[10])).unwrap(), 0);
+    /// ```
+    fn count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        let mut count = 0;
+        self.try_scan_range(start, end, |_, _| {
+            count += 1;
+            Ok(())
+        })?;
+        Ok(count)
+    }
+
+    /// Get the value of the entry with the smallest key, or `None` if the database is empty.
+    ///
+    /// The default implementation reads a one-entry [`scan_page`][DBMap::scan_page]. Databases
+    /// that can seek to the first key should override it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// assert!(db.first_value().unwrap().is_none());
+    ///
+    /// db.insert(b"m", b"middle").unwrap();
+    /// db.insert(b"a", b"first").unwrap();
+    /// db.insert(b"z", b"last").unwrap();
+    /// assert_eq!(db.first_value().unwrap(), Some(b"first".to_vec()));
+    /// assert_eq!(db.last_value().unwrap(), Some(b"last".to_vec()));
+    /// ```
+    fn first_value(&self) -> Result<Option<Vec<u8>>> {
+        let (page, _) = self.scan_page([], None, 1)?;
+        Ok(page.into_iter().next().map(|(_, value)| value))
+    }
+
+    /// Get the value of the entry with the largest key, or `None` if the database is empty.
+    ///
+    /// The default implementation scans every entry with [`try_for_each`][DBMap::try_for_each].
+    /// Databases that can seek to the last key should override it.
+    fn last_value(&self) -> Result<Option<Vec<u8>>> {
+        let mut last: Option<Vec<u8>> = None;
+        self.try_for_each(|_, value| {
+            // Reuse the buffer rather than allocating for every entry.
+            let last = last.get_or_insert_with(Vec::new);
+            last.clear();
+            last.extend_from_slice(value);
+            Ok(())
+        })?;
+        Ok(last)
+    }
+
+    /// Get the number of entries in the database.
+    ///
+    /// The default implementation counts the entries with [`for_each`][DBMap::for_each].
+    /// Databases that keep track of their size should override it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Create an in-memory testing "database" based on a BTreeMap.
+    /// let db = BTreeMapDB::open();
+    ///
+    /// assert_eq!(db.len().unwrap(), 0);
+    /// db.insert(b"key1", b"value1").unwrap();
+    /// db.insert(b"key2", b"value2").unwrap();
+    /// assert_eq!(db.len().unwrap(), 2);
+    ///
+    /// // Replacing a value doesn't change the length.
+    /// db.insert(b"key1", b"another value").unwrap();
+    /// assert_eq!(db.len().unwrap(), 2);
+    /// ```
+    fn len(&self) -> Result<usize> {
+        let mut count = 0;
+        self.for_each(|_, _| count += 1)?;
+        Ok(count)
+    }
+
+    /// Returns `true` if the database contains no entries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    ///
+    /// assert!(db.is_empty().unwrap());
+    /// db.insert(b"key", b"value").unwrap();
+    /// assert!(!db.is_empty().unwrap());
+    /// ```
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Remove every entry from the database.
+    ///
+    /// The default implementation collects the keys and removes them one at a time, so it isn't
+    /// atomic. Backends override it to empty the database in one step.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([("key1", "value1"), ("key2", "value2")]);
+    /// db.clear().unwrap();
+    /// assert!(db.is_empty().unwrap());
+    /// assert!(db.get(b"key1").unwrap().is_none());
+    ///
+    /// // The database can still be used.
+    /// db.insert(b"key3", b"value3").unwrap();
+    /// assert_eq!(db.len().unwrap(), 1);
+    /// ```
+    fn clear(&self) -> Result<()> {
+        let mut keys = Vec::new();
+        self.for_each(|key, _| keys.push(key.to_vec()))?;
+        for key in keys {
+            // Removing through `update` doesn't fail if the key has gone in the meantime.
+            self.update(key, |_| None)?;
+        }
+        Ok(())
+    }
+
+    /// Compute a SHA-256 digest of the entire contents of the database.
+    ///
+    /// The digest covers every key and value in byte-lexicographic key order, so two databases
+    /// with the same contents have the same digest regardless of how they were built. Each key
+    /// and value is prefixed with its length (as a big-endian `u64`) so that the boundaries
+    /// between entries are part of the digest. If the backend's scans aren't in key order (see
+    /// [`Capabilities::ordered_iteration`]), the entries are read into memory and sorted first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db1 = BTreeMapDB::open();
+    /// db1.insert(b"key1", b"value1").unwrap();
+    /// db1.insert(b"key2", b"value2").unwrap();
+    ///
+    /// // Insert the same data in a different order.
+    /// let db2 = BTreeMapDB::open();
+    /// db2.insert(b"key2", b"value2").unwrap();
+    /// db2.insert(b"key1", b"value1").unwrap();
+    /// assert_eq!(db1.digest().unwrap(), db2.digest().unwrap());
+    ///
+    /// // Any change to the data changes the digest.
+    /// db2.insert(b"key2", b"value3").unwrap();
+    /// assert_ne!(db1.digest().unwrap(), db2.digest().unwrap());
+    /// ```
+    fn digest(&self) -> Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        match self.capabilities().ordered_iteration {
+            true => self.for_each(|key, value| hash_entry(&mut hasher, key, value))?,
+            false => self.iter()?.iter().for_each(|(key, value)| hash_entry(&mut hasher, key, value)),
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    /// Compute a SHA-256 digest of the entries whose keys start with `prefix`.
+    ///
+    /// The entries are hashed the same way as in [`digest`][DBMap::digest], with their whole
+    /// keys, so an empty prefix gives the same digest as `digest`. Comparing the digests of a
+    /// prefix in two databases tells whether that part of the data differs without hashing the
+    /// rest.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// for tenant in ["acme/", "globex/"] {
+    ///     for user in ["alice", "bob"] {
+    ///         db.insert(format!("{tenant}{user}"), user).unwrap();
+    ///     }
+    /// }
+    /// let acme = db.digest_prefix("acme/").unwrap();
+    /// let globex = db.digest_prefix("globex/").unwrap();
+    /// assert_eq!(db.digest_prefix("").unwrap(), db.digest().unwrap());
+    ///
+    /// // Changing a value under one prefix only changes that prefix's digest.
+    /// db.insert("acme/bob", "robert").unwrap();
+    /// assert_ne!(db.digest_prefix("acme/").unwrap(), acme);
+    /// assert_eq!(db.digest_prefix("globex/").unwrap(), globex);
+    /// ```
+    fn digest_prefix<P: AsRef<[u8]>>(&self, prefix: P) -> Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        match self.capabilities().ordered_iteration {
+            true => self.scan_prefix(prefix, |key, value| hash_entry(&mut hasher, key, value))?,
+            false => {
+                let mut entries = Vec::new();
+                self.scan_prefix(prefix, |key, value| entries.push((key.to_vec(), value.to_vec())))?;
+                entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+                entries.iter().for_each(|(key, value)| hash_entry(&mut hasher, key, value));
+            }
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    /// Move every entry under `from_prefix` to the same key under `to_prefix`, returning the
+    /// number of entries moved.
+    ///
+    /// Each key keeps the part after `from_prefix`, so `from/a` moves to `to/a`. Entries already
+    /// under `to_prefix` with the same suffix are replaced, and other entries are left alone. The
+    /// entries to move are read before anything is written, so `to_prefix` may be under
+    /// `from_prefix` (or the other way around) and every entry is still moved exactly once.
+    ///
+    /// The default copies the entries with [`batch_insert`][DBMap::batch_insert] and then
+    /// removes the originals, so it is not atomic: an error part way through can leave entries
+    /// under both prefixes, but never loses one. Backends with transactions override it to move
+    /// everything at once.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([
+    ///     ("acme/alice", "1"),
+    ///     ("acme/bob", "2"),
+    ///     ("acme2/carol", "3"),
+    ///     ("globex/alice", "old"),
+    /// ]);
+    /// assert_eq!(db.move_prefix("acme/", "globex/").unwrap(), 2);
+    ///
+    /// let mut left = 0;
+    /// db.scan_prefix("acme/", |_, _| left += 1).unwrap();
+    /// assert_eq!(left, 0);
+    /// assert_eq!(db.get("globex/alice").unwrap(), Some(b"1".to_vec()));
+    /// assert_eq!(db.get("globex/bob").unwrap(), Some(b"2".to_vec()));
+    /// assert_eq!(db.get("acme2/carol").unwrap(), Some(b"3".to_vec()));
+    ///
+    /// // Moving a subtree under itself moves each entry once.
+    /// assert_eq!(db.move_prefix("globex/", "globex/archive/").unwrap(), 2);
+    /// assert_eq!(db.get("globex/archive/bob").unwrap(), Some(b"2".to_vec()));
+    /// assert_eq!(db.len().unwrap(), 3);
+    /// ```
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        let (from_prefix, to_prefix) = (from_prefix.as_ref(), to_prefix.as_ref());
+        let mut entries = Vec::new();
+        self.scan_prefix(from_prefix, |key, value| {
+            if let Some(new_key) = move_key(key, from_prefix, to_prefix) {
+                entries.push((key.to_vec(), new_key, value.to_vec()));
+            }
+        })?;
+        let moved: BTreeSet<&[u8]> = entries.iter().map(|(_, new_key, _)| &new_key[..]).collect();
+        self.batch_insert(entries.iter().map(|(_, new_key, value)| (new_key, value)))?;
+        for (key, _, _) in &entries {
+            // A key that another entry was just moved to has to stay.
+            if !moved.contains(&key[..]) {
+                self.remove(key)?;
+            }
+        }
+        Ok(entries.len())
+    }
+
+    /// Hint that the keys from `start` up to (but not including) `end` are about to be used
+    /// heavily, so the backend can get ready for them.
+    ///
+    /// This is only a hint: it never changes the contents, and a backend is free to do nothing,
+    /// which is what the default implementation does. Backends that keep their data on disk can
+    /// use it to read the range into memory ahead of time.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::from_iter([("user/1", "Ada"), ("user/2", "Grace")]);
+    /// db.prepare_range(b"user/", b"user0").unwrap();
+    /// assert_eq!(db.len().unwrap(), 2);
+    /// ```
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        let _ = (start, end);
+        Ok(())
+    }
+
+    /// Report the features this database supports.
+    ///
+    /// The default reports none of them. Backends override this to report what they actually
+    /// do, and wrappers pass on the capabilities of the database they wrap, less any they take
+    /// away.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{Capabilities, DBMap};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// /// Read the smallest key, from the start of a scan if the order can be relied on.
+    /// fn first_key<M: DBMap>(db: &M) -> Option<Vec<u8>> {
+    ///     let mut first: Option<Vec<u8>> = None;
+    ///     if db.capabilities().ordered_iteration {
+    ///         let (page, _) = db.scan_page([], None, 1).unwrap();
+    ///         return page.into_iter().next().map(|(key, _)| key);
+    ///     }
+    ///     db.for_each(|key, _| if first.as_deref().is_none_or(|first| key < first) {
+    ///         first = Some(key.to_vec());
+    ///     }).unwrap();
+    ///     first
+    /// }
+    ///
+    /// let db = BTreeMapDB::from_iter([("b", "2"), ("a", "1")]);
+    /// assert_eq!(db.capabilities(), Capabilities {
+    ///     ordered_iteration: true,
+    ///     ..Capabilities::default()
+    /// });
+    /// assert_eq!(first_key(&db), Some(b"a".to_vec()));
+    /// ```
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Get another handle to the same database, where getting one can fail.
+    ///
+    /// `DBMap` requires `Clone`, and every backend in this workspace clones by sharing an `Arc`,
+    /// which can't fail. A backend that needs a resource for each handle, like a connection from
+    /// a pool, can override this to report a failure to get one instead of panicking in `clone`.
+    /// Generic code that can handle the error should prefer this over `clone`. The default
+    /// implementation returns `Ok(self.clone())`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::DBMap;
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// let db = BTreeMapDB::open();
+    /// let handle = db.try_clone().unwrap();
+    ///
+    /// // Both handles see the same data.
+    /// handle.insert(b"key", b"value").unwrap();
+    /// assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+    /// ```
+    fn try_clone(&self) -> Result<Self> {
+        Ok(self.clone())
+    }
 }
@@ -0,0 +1,236 @@
+//! # Key namespaces for the `DBMap` trait.
+//!
+//! The [`Namespaced`] wrapper puts a fixed prefix in front of every key, so that several
+//! independent maps can share one database without their keys colliding.
+use crate::{Capabilities, DBMap, Page, Result};
+use std::ops::Bound;
+
+/// A `DBMap` wrapper that stores every key under a namespace prefix.
+///
+/// Keys passed in are stored in the inner database with the namespace in front of them, and
+/// keys passed back out of scans have it stripped again, so the wrapper behaves like a database
+/// of its own. Scans only visit the namespace: a prefix or range scan is translated into a scan
+/// of the prefixed keys, and an empty scan prefix covers the whole namespace.
+///
+/// Namespaces should not be prefixes of each other. With namespaces `a` and `ab`, the key `b`
+/// in `a` and the empty key in `ab` are both stored as `ab`, and scans of `a` see the keys of `ab`.
+/// Ending every namespace with a separator, as [`KeyBuilder::build_prefix`][crate::KeyBuilder::build_prefix]
+/// does, avoids this.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, Namespaced};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// let inner = BTreeMapDB::open();
+/// let users = Namespaced::new(inner.clone(), "users/");
+/// let groups = Namespaced::new(inner.clone(), "groups/");
+///
+/// users.insert("alice", "Alice").unwrap();
+/// users.insert("bob", "Bob").unwrap();
+/// groups.insert("admins", "alice").unwrap();
+///
+/// // Each namespace only sees its own keys, without the prefix.
+/// let mut keys = Vec::new();
+/// users.for_each(|key, _| keys.push(key.to_vec())).unwrap();
+/// assert_eq!(keys, [b"alice".to_vec(), b"bob".to_vec()]);
+/// assert!(groups.get("alice").unwrap().is_none());
+/// assert_eq!(users.len().unwrap(), 2);
+///
+/// // Scans with a prefix, including an empty one, strip the namespace too.
+/// let mut keys = Vec::new();
+/// users.scan_prefix("b", |key, _| keys.push(key.to_vec())).unwrap();
+/// assert_eq!(keys, [b"bob".to_vec()]);
+/// let mut keys = Vec::new();
+/// groups.scan_prefix("", |key, _| keys.push(key.to_vec())).unwrap();
+/// assert_eq!(keys, [b"admins".to_vec()]);
+///
+/// // The inner database holds the prefixed keys.
+/// let mut raw = Vec::new();
+/// inner.for_each(|key, _| raw.push(String::from_utf8(key.to_vec()).unwrap())).unwrap();
+/// assert_eq!(raw, ["groups/admins", "users/alice", "users/bob"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Namespaced<M> {
+    inner: M,
+    namespace: Vec<u8>,
+}
+
+impl<M: DBMap> Namespaced<M> {
+    /// Wrap `inner` so that every key is stored under `namespace`.
+    pub fn new<N: AsRef<[u8]>>(inner: M, namespace: N) -> Self {
+        Self {
+            inner,
+            namespace: namespace.as_ref().to_vec(),
+        }
+    }
+
+    /// Get a reference to the wrapped database.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Get the namespace prefix.
+    pub fn namespace(&self) -> &[u8] {
+        &self.namespace
+    }
+
+    /// Get the key stored in the inner database for a key.
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        [&self.namespace[..], key].concat()
+    }
+
+    /// Get a key from the inner database without the namespace.
+    fn strip<'a>(&self, key: &'a [u8]) -> &'a [u8] {
+        &key[self.namespace.len()..]
+    }
+
+    /// Translate a bound on keys into a bound on the keys stored in the inner database.
+    fn bound(&self, bound: Bound<&[u8]>) -> Bound<Vec<u8>> {
+        bound.map(|key| self.key(key))
+    }
+
+    /// Get the range of the inner database that holds the namespace.
+    fn namespace_range(&self) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+        let start = match self.namespace.is_empty() {
+            true => Bound::Unbounded,
+            false => Bound::Included(self.namespace.clone()),
+        };
+        (start, prefix_end(&self.namespace))
+    }
+
+    /// Translate a range of keys into the range of the inner database that holds them.
+    fn range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+        let (namespace_start, namespace_end) = self.namespace_range();
+        let start = match start {
+            Bound::Unbounded => namespace_start,
+            start => self.bound(start),
+        };
+        let end = match end {
+            Bound::Unbounded => namespace_end,
+            end => self.bound(end),
+        };
+        (start, end)
+    }
+}
+
+/// Get the bound just past every key that starts with `prefix`: the next key that doesn't, or
+/// `Unbounded` if every key after the prefix starts with it.
+fn prefix_end(prefix: &[u8]) -> Bound<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Bound::Excluded(end);
+        }
+    }
+    Bound::Unbounded
+}
+
+impl<M: DBMap> DBMap for Namespaced<M> {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        self.inner.get_map(self.key(key.as_ref()), mapper)
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        self.inner.insert(self.key(key.as_ref()), value)
+    }
+
+    fn insert_chunks<K, I, V>(&self, key: K, chunks: I) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            I: IntoIterator<Item = V>,
+            V: AsRef<[u8]>,
+    {
+        self.inner.insert_chunks(self.key(key.as_ref()), chunks)
+    }
+
+    fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        self.inner.fetch_and_replace_map(self.key(key.as_ref()), value, mapper)
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        self.inner.remove(self.key(key.as_ref()))
+    }
+
+    fn update<K, F>(&self, key: K, f: F) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        self.inner.update(self.key(key.as_ref()), f)
+    }
+
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        self.inner.batch_insert(items.into_iter().map(|(key, value)| (self.key(key.as_ref()), value)))
+    }
+
+    /// Scan the namespace in the inner database.
+    fn try_for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_scan_prefix(&self.namespace, |key, value| f(self.strip(key), value))
+    }
+
+    /// Scan the prefix under the namespace in the inner database.
+    fn try_scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_scan_prefix(self.key(prefix.as_ref()), |key, value| f(self.strip(key), value))
+    }
+
+    /// Scan the range under the namespace in the inner database. An unbounded end stops at the
+    /// end of the namespace.
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let (start, end) = self.range(start, end);
+        self.inner.try_scan_range(start.as_ref().map(Vec::as_slice), end.as_ref().map(Vec::as_slice), |key, value| f(self.strip(key), value))
+    }
+
+    fn count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        let (start, end) = self.range(start, end);
+        self.inner.count_range(start.as_ref().map(Vec::as_slice), end.as_ref().map(Vec::as_slice))
+    }
+
+    fn scan_page<P: AsRef<[u8]>>(&self, prefix: P, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        let start_after = start_after.map(|key| self.key(key));
+        let (entries, next) = self.inner.scan_page(self.key(prefix.as_ref()), start_after.as_deref(), limit)?;
+        let entries = entries.into_iter()
+            .map(|(key, value)| (self.strip(&key).to_vec(), value))
+            .collect();
+        Ok((entries, next.map(|key| self.strip(&key).to_vec())))
+    }
+
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        self.inner.move_prefix(self.key(from_prefix.as_ref()), self.key(to_prefix.as_ref()))
+    }
+
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        self.inner.prepare_range(&self.key(start), &self.key(end))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
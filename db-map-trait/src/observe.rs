@@ -0,0 +1,65 @@
+//! # Tracing reads through stacked wrappers.
+//!
+//! When wrappers are stacked, like a [`Cached`][crate::Cached] over a
+//! [`Buffered`][crate::Buffered], it can be hard to tell which layer served a read. An
+//! [`Observer`] installed on a wrapper with `with_observer` is called with an [`OpEvent`] for
+//! every point read ([`get_map`][crate::DBMap::get_map] and everything built on it) that the
+//! wrapper handles. Wrappers without an observer skip this entirely.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use db_map_trait::{observe::{OpEvent, Observer}, Buffered, Cached, DBMap};
+//! use db_map_btreemap::BTreeMapDB;
+//! use std::sync::{Arc, Mutex};
+//!
+//! let events = Arc::new(Mutex::new(Vec::new()));
+//! let observer: Observer = {
+//!     let events = events.clone();
+//!     Arc::new(move |event| events.lock().unwrap().push(event))
+//! };
+//! let db = Cached::new(Buffered::new(BTreeMapDB::open(), 100).with_observer(observer.clone()), 10)
+//!     .with_observer(observer);
+//! let mut take_events = || std::mem::take(&mut *events.lock().unwrap());
+//! let event = |layer, hit| OpEvent { layer, op: "get", key_len: 3, hit };
+//!
+//! db.insert(b"key", b"value").unwrap();
+//! db.clear_cache();
+//!
+//! // A cache miss falls through to the buffer, which holds the unflushed write.
+//! assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+//! assert_eq!(take_events(), [event("cached", false), event("buffered", true)]);
+//!
+//! // The value is now cached, so the buffer isn't asked again.
+//! assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+//! assert_eq!(take_events(), [event("cached", true)]);
+//!
+//! // A missing key misses in both layers.
+//! assert_eq!(db.get(b"no!").unwrap(), None);
+//! assert_eq!(take_events(), [event("cached", false), event("buffered", false)]);
+//! ```
+use std::sync::Arc;
+
+/// A callback that is told about the operations a wrapper handles.
+pub type Observer = Arc<dyn Fn(OpEvent) + Send + Sync>;
+
+/// One operation handled by a wrapper, as reported to an [`Observer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpEvent {
+    /// The wrapper that handled the operation, like `"cached"` or `"buffered"`.
+    pub layer: &'static str,
+    /// The operation, like `"get"`.
+    pub op: &'static str,
+    /// The length of the key.
+    pub key_len: usize,
+    /// Whether the wrapper answered from its own state instead of asking the inner database.
+    pub hit: bool,
+}
+
+/// Report an event to the observer, if there is one.
+#[inline]
+pub(crate) fn notify(observer: &Option<Observer>, layer: &'static str, op: &'static str, key: &[u8], hit: bool) {
+    if let Some(observer) = observer {
+        observer(OpEvent { layer, op, key_len: key.len(), hit });
+    }
+}
@@ -0,0 +1,37 @@
+//! # A common constructor for `DBMap` backends.
+//!
+//! Each backend opens differently: `BTreeMapDB::open` takes nothing, while `LMDB::open` takes
+//! a path, a database name, and arguments. The [`OpenDb`] trait puts everything a backend needs
+//! into one `Config` value, so generic setup code can open any backend with `M::open(config)`.
+use crate::{DBMap, Result};
+
+/// A `DBMap` that can be opened from a single configuration value.
+///
+/// Backends keep their own `open` functions, which take precedence when calling
+/// `BTreeMapDB::open()` directly. Generic code calls the trait's `open` through a type parameter.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, OpenDb};
+/// use db_map_btreemap::{BTreeMapDB, ShardedMemDb};
+///
+/// // Setup code that works with any backend.
+/// fn open_and_seed<M: OpenDb>(config: M::Config) -> M {
+///     let db = M::open(config).unwrap();
+///     db.insert(b"version", [1]).unwrap();
+///     db
+/// }
+///
+/// let db: BTreeMapDB = open_and_seed(());
+/// assert_eq!(db.get(b"version").unwrap(), Some(vec![1]));
+/// let db: ShardedMemDb = open_and_seed(());
+/// assert_eq!(db.get(b"version").unwrap(), Some(vec![1]));
+/// ```
+pub trait OpenDb: DBMap + Sized {
+    /// Everything needed to open the database.
+    type Config;
+
+    /// Open the database described by `config`.
+    fn open(config: Self::Config) -> Result<Self>;
+}
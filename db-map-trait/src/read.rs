@@ -0,0 +1,173 @@
+//! # Read-only access to a `DBMap`.
+//!
+//! The [`DBMapRead`] trait has the reading half of [`DBMap`]. Every `DBMap` implements it, and
+//! read-only views (like `BTreeMapDB::as_read_only`) implement only it, so code that takes a
+//! `DBMapRead` can be handed a view that rules out writes at compile time.
+//!
+//! The methods have the same names as their `DBMap` counterparts, so with both traits in scope a
+//! call on a `DBMap` is ambiguous. Import whichever trait the code needs, or call through the
+//! trait (`DBMapRead::get(&db, key)`).
+use crate::{DBMap, Result};
+use std::ops::{Bound, RangeBounds};
+
+/// The read operations of a [`DBMap`].
+///
+/// Only [`get_map`][DBMapRead::get_map] and [`try_for_each`][DBMapRead::try_for_each] have to be
+/// implemented. For a `DBMap`, every method calls the `DBMap` method of the same name, so a
+/// backend's faster versions are used.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::DBMapRead;
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// // A function that can only read from the database it is given.
+/// fn total_len<M: DBMapRead>(db: &M) -> usize {
+///     let mut total = 0;
+///     db.for_each(|_, value| total += value.len()).unwrap();
+///     total
+/// }
+///
+/// let db = BTreeMapDB::from_iter([("a", "apple"), ("b", "banana")]);
+/// assert_eq!(total_len(&db), 11);
+/// assert_eq!(DBMapRead::get(&db, b"a").unwrap(), Some(b"apple".to_vec()));
+/// ```
+pub trait DBMapRead {
+    /// Get the data for a key, mapped by a function. See [`DBMap::get_map`].
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T;
+
+    /// Get the data for a key. See [`DBMap::get`].
+    fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>> {
+        self.get_map(key, |v| v.to_vec())
+    }
+
+    /// Call a fallible function for every entry, stopping at the first error. See
+    /// [`DBMap::try_for_each`].
+    fn try_for_each<F>(&self, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>;
+
+    /// Call a function for every entry. See [`DBMap::for_each`].
+    fn for_each<F>(&self, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]),
+    {
+        self.try_for_each(|key, value| {
+            f(key, value);
+            Ok(())
+        })
+    }
+
+    /// Call a fallible function for every entry under a prefix. See
+    /// [`DBMap::try_scan_prefix`].
+    fn try_scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let prefix = prefix.as_ref();
+        self.try_for_each(|key, value| match key.starts_with(prefix) {
+            true => f(key, value),
+            false => Ok(()),
+        })
+    }
+
+    /// Call a function for every entry under a prefix. See [`DBMap::scan_prefix`].
+    fn scan_prefix<P, F>(&self, prefix: P, mut f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]),
+    {
+        self.try_scan_prefix(prefix, |key, value| {
+            f(key, value);
+            Ok(())
+        })
+    }
+
+    /// Call a fallible function for every entry in a key range. See
+    /// [`DBMap::try_scan_range`].
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, mut f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.try_for_each(|key, value| match (start, end).contains(&key) {
+            true => f(key, value),
+            false => Ok(()),
+        })
+    }
+
+    /// Get the number of entries. See [`DBMap::len`].
+    fn len(&self) -> Result<usize> {
+        let mut count = 0;
+        self.for_each(|_, _| count += 1)?;
+        Ok(count)
+    }
+
+    /// Return `true` if there are no entries. See [`DBMap::is_empty`].
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+impl<M: DBMap> DBMapRead for M {
+    fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            F: FnOnce(&[u8]) -> T,
+    {
+        DBMap::get_map(self, key, mapper)
+    }
+
+    fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>> {
+        DBMap::get(self, key)
+    }
+
+    fn try_for_each<F>(&self, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        DBMap::try_for_each(self, f)
+    }
+
+    fn for_each<F>(&self, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]),
+    {
+        DBMap::for_each(self, f)
+    }
+
+    fn try_scan_prefix<P, F>(&self, prefix: P, f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        DBMap::try_scan_prefix(self, prefix, f)
+    }
+
+    fn scan_prefix<P, F>(&self, prefix: P, f: F) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            F: FnMut(&[u8], &[u8]),
+    {
+        DBMap::scan_prefix(self, prefix, f)
+    }
+
+    fn try_scan_range<F>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, f: F) -> Result<()>
+        where
+            F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        DBMap::try_scan_range(self, start, end, f)
+    }
+
+    fn len(&self) -> Result<usize> {
+        DBMap::len(self)
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        DBMap::is_empty(self)
+    }
+}
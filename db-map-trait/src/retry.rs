@@ -0,0 +1,143 @@
+//! # Retrying operations that fail with transient errors.
+//!
+//! Backends report errors that may go away on their own, like a full memory map or a busy lock,
+//! as retryable (see [`Error::is_retryable`][crate::Error::is_retryable]). [`with_retry`] runs an
+//! operation again after those errors, waiting between attempts as a [`RetryPolicy`] says, so
+//! the same retry configuration works with every backend.
+use crate::Result;
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    thread,
+    time::Duration,
+};
+
+/// How [`with_retry`] retries an operation.
+///
+/// The delay after the first failed attempt is `base_delay`, and it doubles after every attempt
+/// after that, up to `max_delay`. With `jitter` set, each delay is instead a random duration
+/// between zero and that amount, so that clients that failed together don't all retry together.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy {
+///     max_attempts: 10,
+///     base_delay: Duration::from_millis(10),
+///     max_delay: Duration::from_millis(50),
+///     jitter: false,
+/// };
+/// assert_eq!(policy.delay(1), Duration::from_millis(10));
+/// assert_eq!(policy.delay(2), Duration::from_millis(20));
+/// assert_eq!(policy.delay(3), Duration::from_millis(40));
+/// assert_eq!(policy.delay(4), Duration::from_millis(50));
+/// assert_eq!(policy.delay(100), Duration::from_millis(50));
+///
+/// let policy = RetryPolicy { jitter: true, ..policy };
+/// assert!(policy.delay(3) <= Duration::from_millis(40));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The most times the operation is run, including the first. 0 is treated as 1.
+    pub max_attempts: u32,
+    /// The delay after the first failed attempt.
+    pub base_delay: Duration,
+    /// The longest delay between attempts.
+    pub max_delay: Duration,
+    /// Randomize each delay between zero and its full length.
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Get the delay to wait after `attempt` (counting from 1) has failed.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(u32::BITS - 1);
+        let delay = self.base_delay.saturating_mul(1 << doublings).min(self.max_delay);
+        match self.jitter {
+            true => delay.mul_f64(random_fraction()),
+            false => delay,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, waiting about 10 ms and then 20 ms, with jitter.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+            jitter: true,
+        }
+    }
+}
+
+/// Get a random number in `0.0..1.0` without depending on a random number crate.
+fn random_fraction() -> f64 {
+    // Each `RandomState` has new random keys, so hashing anything with it gives a random value.
+    let bits = RandomState::new().hash_one(0_u8);
+    (bits >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+/// Run `op`, running it again after a retryable error until it succeeds or `policy` runs out of
+/// attempts.
+///
+/// The thread sleeps for [`RetryPolicy::delay`] between attempts. An error that isn't
+/// [retryable][crate::Error::is_retryable] is returned straight away, and when every attempt
+/// fails, the error from the last one is returned.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{with_retry, Error, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy {
+///     max_attempts: 3,
+///     base_delay: Duration::from_millis(1),
+///     ..RetryPolicy::default()
+/// };
+///
+/// // Fail twice with a transient error, then succeed.
+/// let mut attempts = 0;
+/// let value = with_retry(&policy, || {
+///     attempts += 1;
+///     match attempts {
+///         1 | 2 => Err(Error::Transient(anyhow::anyhow!("map is full"))),
+///         _ => Ok("stored"),
+///     }
+/// }).unwrap();
+/// assert_eq!((value, attempts), ("stored", 3));
+///
+/// // Running out of attempts returns the last error.
+/// let mut attempts = 0;
+/// let err = with_retry(&policy, || -> Result<(), _> {
+///     attempts += 1;
+///     Err(Error::Timeout(Duration::from_millis(attempts)))
+/// }).unwrap_err();
+/// assert!(matches!(err, Error::Timeout(timeout) if timeout == Duration::from_millis(3)));
+///
+/// // Errors that can't be fixed by retrying are returned after one attempt.
+/// let mut attempts = 0;
+/// let err = with_retry(&policy, || -> Result<(), _> {
+///     attempts += 1;
+///     Err(Error::ReadOnly)
+/// }).unwrap_err();
+/// assert!(matches!(err, Error::ReadOnly));
+/// assert_eq!(attempts, 1);
+/// ```
+pub fn with_retry<T, F: FnMut() -> Result<T>>(policy: &RetryPolicy, mut op: F) -> Result<T> {
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(err) if err.is_retryable() && attempt < policy.max_attempts => {
+                thread::sleep(policy.delay(attempt));
+                attempt += 1;
+            }
+            result => { return result }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! # Schema versions stored in a `DBMap`.
+//!
+//! [`SchemaVersioned`] keeps the version of the data's layout under the reserved
+//! [`SCHEMA_VERSION_KEY`], migrates older data when a database is opened, and refuses to open data
+//! written by newer code.
+use crate::{decode_u64, DBMap, Error, HexKey, Result};
+
+/// The reserved key that holds the schema version, as a big-endian `u64`.
+pub const SCHEMA_VERSION_KEY: &[u8] = b"__schema_version__";
+
+/// A database whose schema version has been checked, and migrated if it was old.
+///
+/// [`open`][SchemaVersioned::open] reads the version stored at [`SCHEMA_VERSION_KEY`] and
+/// compares it with the version the code supports:
+///
+/// - An empty database is new, so it is stamped with the current version.
+/// - An older version is passed to the migration function, which has to bring the data up to
+///   date. The current version is only stored once the migration succeeds, so a failed migration
+///   runs again the next time the database is opened. A database with data but no version is
+///   treated as version 0.
+/// - A newer version fails with [`Error::SchemaTooNew`], without changing anything.
+///
+/// The version key is stored with the data in the inner database, so scans of the inner
+/// database see it, and it must not be written except through `SchemaVersioned`.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{schema::SCHEMA_VERSION_KEY, DBMap, Error, SchemaVersioned};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// // A new database is stamped with the current version.
+/// let db = SchemaVersioned::open(BTreeMapDB::open(), 1, |_, _| unreachable!()).unwrap();
+/// assert_eq!(db.version(), 1);
+/// db.inner().insert(b"user/alice", b"Alice").unwrap();
+///
+/// // Version 2 stores names in upper case, so opening with it migrates the data.
+/// let db = SchemaVersioned::open(db.into_inner(), 2, |db: &BTreeMapDB, stored| {
+///     assert_eq!(stored, 1);
+///     let mut users = Vec::new();
+///     db.scan_prefix(b"user/", |key, name| users.push((key.to_vec(), name.to_ascii_uppercase())))?;
+///     db.batch_insert(users)
+/// }).unwrap();
+/// assert_eq!(db.inner().get(b"user/alice").unwrap(), Some(b"ALICE".to_vec()));
+/// assert_eq!(db.inner().get(SCHEMA_VERSION_KEY).unwrap(), Some(2_u64.to_be_bytes().to_vec()));
+///
+/// // Opening it again with the same version doesn't migrate.
+/// let db = SchemaVersioned::open(db.into_inner(), 2, |_, _| unreachable!()).unwrap();
+///
+/// // Code that only knows version 1 can't open it.
+/// let inner = db.into_inner();
+/// assert!(matches!(
+///     SchemaVersioned::open(inner.clone(), 1, |_, _| unreachable!()),
+///     Err(Error::SchemaTooNew { stored: 2, supported: 1 }),
+/// ));
+/// assert_eq!(inner.get(SCHEMA_VERSION_KEY).unwrap(), Some(2_u64.to_be_bytes().to_vec()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SchemaVersioned<M> {
+    inner: M,
+    version: u64,
+}
+
+impl<M: DBMap> SchemaVersioned<M> {
+    /// Check the schema version of `inner` against `current_version`, calling `migrate` with the
+    /// stored version if it is older.
+    ///
+    /// See [`SchemaVersioned`] for what happens in each case.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{schema::SCHEMA_VERSION_KEY, DBMap, Error, SchemaVersioned};
+    /// use db_map_btreemap::BTreeMapDB;
+    ///
+    /// // Data from before versioning is version 0.
+    /// let inner = BTreeMapDB::from_iter([("key", "value")]);
+    ///
+    /// // A failed migration leaves the version alone, so it runs again next time.
+    /// let failed = SchemaVersioned::open(inner.clone(), 1, |_, stored| {
+    ///     assert_eq!(stored, 0);
+    ///     Err(Error::Validation("not today".to_string()))
+    /// });
+    /// assert!(matches!(failed, Err(Error::Validation(_))));
+    /// assert!(inner.get(SCHEMA_VERSION_KEY).unwrap().is_none());
+    ///
+    /// let mut migrated_from = None;
+    /// let db = SchemaVersioned::open(inner, 1, |_, stored| {
+    ///     migrated_from = Some(stored);
+    ///     Ok(())
+    /// }).unwrap();
+    /// assert_eq!(migrated_from, Some(0));
+    /// assert_eq!(db.version(), 1);
+    /// ```
+    pub fn open<F>(inner: M, current_version: u64, migrate: F) -> Result<Self>
+    where
+        F: FnOnce(&M, u64) -> Result<()>,
+    {
+        let stored = inner.get_map(SCHEMA_VERSION_KEY, |value| {
+            let what = format_args!("the schema version at key {}", HexKey(SCHEMA_VERSION_KEY));
+            decode_u64(value, what)
+        })?;
+        let stored = match stored.transpose()? {
+            Some(stored) => Some(stored),
+            None if inner.is_empty()? => None,
+            None => Some(0),
+        };
+        match stored {
+            Some(stored) if stored > current_version => {
+                return Err(Error::SchemaTooNew { stored, supported: current_version })
+            }
+            Some(stored) if stored < current_version => migrate(&inner, stored)?,
+            Some(_) => { return Ok(Self { inner, version: current_version }) }
+            None => {}
+        }
+        inner.insert(SCHEMA_VERSION_KEY, current_version.to_be_bytes())?;
+        Ok(Self { inner, version: current_version })
+    }
+
+    /// Get the schema version of the data, which is always the version it was opened with.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Get a reference to the database.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Get the database back.
+    pub fn into_inner(self) -> M {
+        self.inner
+    }
+}
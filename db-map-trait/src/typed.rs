@@ -0,0 +1,400 @@
+//! # Typed access to a `DBMap`.
+//!
+//! A [`Codec`] describes how a key type and a value type are turned into bytes and back, and
+//! [`Typed`] wraps a `DBMap` so that it can be used with those types directly. Ready-made codecs
+//! are in the [`codec`][crate::codec] module.
+//!
+//! Alternatively, types can say how they are stored themselves by implementing [`Encode`] and
+//! [`Decode`], and then every `DBMap` can read and write them through the [`TypedDBMap`] trait,
+//! without a wrapper.
+use crate::{DBMap, Error, Result};
+use std::{fmt, io, marker::PhantomData};
+
+/// Convert keys and values to and from the bytes stored in a `DBMap`.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{Codec, Result, Typed};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// /// Store `u32` keys as big-endian bytes and `String` values as UTF-8.
+/// struct IdToName;
+///
+/// impl Codec for IdToName {
+///     type Key = u32;
+///     type Value = String;
+///
+///     fn encode_key(key: &u32) -> Result<Vec<u8>> {
+///         Ok(key.to_be_bytes().to_vec())
+///     }
+///
+///     fn encode_value(value: &String) -> Result<Vec<u8>> {
+///         Ok(value.as_bytes().to_vec())
+///     }
+///
+///     fn decode_value(bytes: &[u8]) -> Result<String> {
+///         Ok(String::from_utf8_lossy(bytes).into_owned())
+///     }
+/// }
+///
+/// let db: Typed<_, IdToName> = Typed::new(BTreeMapDB::open());
+/// db.insert(&7, &"seven".to_string()).unwrap();
+/// assert_eq!(db.get(&7).unwrap(), Some("seven".to_string()));
+/// ```
+pub trait Codec {
+    /// The key type.
+    type Key;
+    /// The value type.
+    type Value;
+
+    /// Encode a key into the bytes stored in the database.
+    fn encode_key(key: &Self::Key) -> Result<Vec<u8>>;
+
+    /// Encode a value into the bytes stored in the database.
+    fn encode_value(value: &Self::Value) -> Result<Vec<u8>>;
+
+    /// Decode a value from the bytes stored in the database.
+    fn decode_value(bytes: &[u8]) -> Result<Self::Value>;
+}
+
+/// A `DBMap` wrapper that stores typed keys and values using a [`Codec`].
+///
+/// The wrapped database still holds plain bytes, so it can be read and written with the normal
+/// `DBMap` methods through [`inner`][Typed::inner].
+pub struct Typed<M, C> {
+    inner: M,
+    codec: PhantomData<fn() -> C>,
+}
+
+impl<M, C> Typed<M, C>
+where
+    M: DBMap,
+    C: Codec,
+{
+    /// Wrap `inner` so that it stores keys and values encoded with `C`.
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            codec: PhantomData,
+        }
+    }
+
+    /// Get the wrapped database.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Get the value for a key.
+    pub fn get(&self, key: &C::Key) -> Result<Option<C::Value>> {
+        self.inner.get_map(C::encode_key(key)?, C::decode_value)?.transpose()
+    }
+
+    /// Insert the value for a key.
+    pub fn insert(&self, key: &C::Key, value: &C::Value) -> Result<()> {
+        self.inner.insert(C::encode_key(key)?, C::encode_value(value)?)
+    }
+
+    /// Insert many keys and values at once with [`batch_insert`][DBMap::batch_insert].
+    ///
+    /// Every pair is encoded before anything is written, so an encoding error leaves the
+    /// database unchanged. Databases with transactions then write all of the pairs in one
+    /// transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use db_map_trait::{Codec, DBMap, Result, Typed};
+    /// use db_map_btreemap::BTreeMapDB;
+    /// use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// struct Point { x: i32, y: i32 }
+    ///
+    /// /// Store `u32` keys and `Point` values as big-endian bytes.
+    /// struct IdToPoint;
+    ///
+    /// impl Codec for IdToPoint {
+    ///     type Key = u32;
+    ///     type Value = Point;
+    ///
+    ///     fn encode_key(key: &u32) -> Result<Vec<u8>> {
+    ///         Ok(key.to_be_bytes().to_vec())
+    ///     }
+    ///
+    ///     fn encode_value(point: &Point) -> Result<Vec<u8>> {
+    ///         Ok([point.x.to_be_bytes(), point.y.to_be_bytes()].concat())
+    ///     }
+    ///
+    ///     fn decode_value(bytes: &[u8]) -> Result<Point> {
+    ///         let (x, y) = bytes.split_at(4);
+    ///         Ok(Point {
+    ///             x: i32::from_be_bytes(x.try_into().unwrap()),
+    ///             y: i32::from_be_bytes(y.try_into().unwrap()),
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// /// A database that counts its `batch_insert` calls and refuses single inserts.
+    /// #[derive(Clone, Debug, Default)]
+    /// struct Batches { db: BTreeMapDB, batches: Arc<AtomicUsize> }
+    ///
+    /// impl DBMap for Batches {
+    ///     fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+    ///         where
+    ///             I: IntoIterator<Item = (K, V)>,
+    ///             K: AsRef<[u8]>,
+    ///             V: AsRef<[u8]>,
+    ///     {
+    ///         self.batches.fetch_add(1, Ordering::SeqCst);
+    ///         self.db.batch_insert(items)
+    ///     }
+    ///
+    ///     fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, _: K, _: V) -> Result<()> {
+    ///         unreachable!("insert_all should only use batch_insert");
+    ///     }
+    /// #   fn get_map<K, F, T>(&self, key: K, mapper: F) -> Result<Option<T>>
+    /// #       where K: AsRef<[u8]>, F: FnOnce(&[u8]) -> T,
+    /// #   { self.db.get_map(key, mapper) }
+    /// #   fn fetch_and_replace_map<K, V, F, T>(&self, key: K, value: V, mapper: F) -> Result<Option<T>>
+    /// #       where K: AsRef<[u8]>, V: AsRef<[u8]>, F: FnOnce(&[u8]) -> T,
+    /// #   { self.db.fetch_and_replace_map(key, value, mapper) }
+    /// #   fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> { self.db.remove(key) }
+    /// #   fn try_for_each<F>(&self, f: F) -> Result<()>
+    /// #       where F: FnMut(&[u8], &[u8]) -> Result<()>,
+    /// #   { self.db.try_for_each(f) }
+    /// }
+    ///
+    /// let db: Typed<_, IdToPoint> = Typed::new(Batches::default());
+    /// db.insert_all((0..100).map(|i| (i, Point { x: i as i32, y: -(i as i32) }))).unwrap();
+    ///
+    /// assert_eq!(db.inner().batches.load(Ordering::SeqCst), 1);
+    /// assert_eq!(db.inner().len().unwrap(), 100);
+    /// assert_eq!(db.get(&0).unwrap(), Some(Point { x: 0, y: 0 }));
+    /// assert_eq!(db.get(&42).unwrap(), Some(Point { x: 42, y: -42 }));
+    /// assert_eq!(db.get(&99).unwrap(), Some(Point { x: 99, y: -99 }));
+    /// assert_eq!(db.get(&100).unwrap(), None);
+    /// ```
+    pub fn insert_all<I>(&self, items: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (C::Key, C::Value)>,
+    {
+        let encoded = items.into_iter()
+            .map(|(key, value)| Ok((C::encode_key(&key)?, C::encode_value(&value)?)))
+            .collect::<Result<Vec<_>>>()?;
+        self.inner.batch_insert(encoded)
+    }
+
+    /// Insert the value for a key, returning the old value.
+    pub fn fetch_and_replace(&self, key: &C::Key, value: &C::Value) -> Result<Option<C::Value>> {
+        self.inner.fetch_and_replace_map(C::encode_key(key)?, C::encode_value(value)?, C::decode_value)?
+            .transpose()
+    }
+
+    /// Remove a key.
+    pub fn remove(&self, key: &C::Key) -> Result<()> {
+        self.inner.remove(C::encode_key(key)?)
+    }
+}
+
+impl<M: Clone, C> Clone for Typed<M, C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            codec: PhantomData,
+        }
+    }
+}
+
+impl<M: fmt::Debug, C> fmt::Debug for Typed<M, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Typed")
+            .field("inner", &self.inner)
+            .field("codec", &std::any::type_name::<C>())
+            .finish()
+    }
+}
+
+/// Turn a value into the bytes stored in a database, for [`TypedDBMap`].
+///
+/// Integers are encoded as fixed-size big-endian bytes, with the sign bit flipped for signed
+/// integers, so encoded integer keys sort in numeric order. Strings are encoded as their UTF-8
+/// bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{Decode, Encode};
+///
+/// for n in [i16::MIN, -1, 0, 1, i16::MAX] {
+///     assert_eq!(n.encode().len(), 2);
+///     assert_eq!(i16::decode(&n.encode()).unwrap(), n);
+/// }
+/// assert!(i16::MIN.encode() < (-1_i16).encode() && 0_i16.encode() < i16::MAX.encode());
+/// assert_eq!(258_u32.encode(), [0, 0, 1, 2]);
+/// assert!(u32::decode(&[1, 2]).is_err());
+/// assert_eq!(String::decode(&"snow ❄".to_string().encode()).unwrap(), "snow ❄");
+/// ```
+pub trait Encode {
+    /// Encode `self` into bytes.
+    fn encode(&self) -> Vec<u8>;
+}
+
+/// Read a value back from the bytes written by its [`Encode`] implementation, for
+/// [`TypedDBMap`].
+pub trait Decode: Sized {
+    /// Decode a value from bytes.
+    ///
+    /// Bytes that don't hold a valid value should give an [`io::ErrorKind::InvalidData`] I/O
+    /// error, as the provided implementations do.
+    fn decode(bytes: &[u8]) -> Result<Self>;
+}
+
+/// Make the error for bytes that don't decode to a `type_name`.
+fn invalid_data(type_name: &str, bytes: &[u8]) -> Error {
+    Error::IoError(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} bytes can't be decoded as a {type_name}", bytes.len()),
+    ))
+}
+
+macro_rules! impl_unsigned {
+    ($($int:ty),*) => {$(
+        impl Encode for $int {
+            fn encode(&self) -> Vec<u8> {
+                self.to_be_bytes().to_vec()
+            }
+        }
+
+        impl Decode for $int {
+            fn decode(bytes: &[u8]) -> Result<Self> {
+                bytes.try_into()
+                    .map(<$int>::from_be_bytes)
+                    .map_err(|_| invalid_data(stringify!($int), bytes))
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed {
+    ($($int:ty => $unsigned:ty),*) => {$(
+        impl Encode for $int {
+            fn encode(&self) -> Vec<u8> {
+                (*self as $unsigned ^ <$int>::MIN as $unsigned).to_be_bytes().to_vec()
+            }
+        }
+
+        impl Decode for $int {
+            fn decode(bytes: &[u8]) -> Result<Self> {
+                bytes.try_into()
+                    .map(|bytes| (<$unsigned>::from_be_bytes(bytes) ^ <$int>::MIN as $unsigned) as $int)
+                    .map_err(|_| invalid_data(stringify!($int), bytes))
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128);
+impl_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
+impl Encode for String {
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl Decode for String {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid_data("String", bytes))
+    }
+}
+
+/// Typed access to any `DBMap`, for keys that implement [`Encode`] and values that implement
+/// [`Encode`] and [`Decode`].
+///
+/// This is implemented for every `DBMap`, so it only needs to be imported. Unlike [`Typed`], the
+/// key and value types aren't fixed by a wrapper, so one database can be used with several.
+/// They are usually inferred from the arguments and the result, but
+/// [`typed_remove`][TypedDBMap::typed_remove] has no value to infer the value type from, so it
+/// has to be named: `TypedDBMap::<K, V>::typed_remove(&db, &key)`.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, Decode, Encode, Error, Result, TypedDBMap};
+/// use db_map_btreemap::BTreeMapDB;
+/// use std::io;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Point { x: i32, y: i32 }
+///
+/// impl Encode for Point {
+///     fn encode(&self) -> Vec<u8> {
+///         [self.x.to_be_bytes(), self.y.to_be_bytes()].concat()
+///     }
+/// }
+///
+/// impl Decode for Point {
+///     fn decode(bytes: &[u8]) -> Result<Point> {
+///         match bytes.len() {
+///             8 => Ok(Point {
+///                 x: i32::from_be_bytes(bytes[..4].try_into().unwrap()),
+///                 y: i32::from_be_bytes(bytes[4..].try_into().unwrap()),
+///             }),
+///             _ => Err(io::Error::new(io::ErrorKind::InvalidData, "not a point").into()),
+///         }
+///     }
+/// }
+///
+/// let db = BTreeMapDB::open();
+/// db.typed_insert(&"origin".to_string(), &Point { x: 0, y: 0 }).unwrap();
+/// db.typed_insert(&"corner".to_string(), &Point { x: -3, y: 4 }).unwrap();
+///
+/// assert_eq!(db.typed_get(&"corner".to_string()).unwrap(), Some(Point { x: -3, y: 4 }));
+/// let missing: Option<Point> = db.typed_get(&"nowhere".to_string()).unwrap();
+/// assert!(missing.is_none());
+///
+/// TypedDBMap::<String, Point>::typed_remove(&db, &"origin".to_string()).unwrap();
+/// assert!(db.get(b"origin").unwrap().is_none());
+///
+/// // The same database can hold other types, and signed integer keys sort numerically.
+/// db.typed_insert(&-1_i64, &"minus one".to_string()).unwrap();
+/// db.typed_insert(&1_i64, &"one".to_string()).unwrap();
+/// assert_eq!(db.typed_get(&-1_i64).unwrap(), Some("minus one".to_string()));
+/// assert!((-1_i64).encode() < 1_i64.encode());
+///
+/// // Bytes that aren't a valid value fail to decode.
+/// assert!(matches!(
+///     TypedDBMap::<String, String>::typed_get(&db, &"corner".to_string()),
+///     Err(Error::IoError(err)) if err.kind() == io::ErrorKind::InvalidData,
+/// ));
+/// ```
+pub trait TypedDBMap<K, V> {
+    /// Get the value for a key.
+    fn typed_get(&self, key: &K) -> Result<Option<V>>;
+
+    /// Insert the value for a key.
+    fn typed_insert(&self, key: &K, value: &V) -> Result<()>;
+
+    /// Remove a key.
+    fn typed_remove(&self, key: &K) -> Result<()>;
+}
+
+impl<M, K, V> TypedDBMap<K, V> for M
+where
+    M: DBMap,
+    K: Encode,
+    V: Encode + Decode,
+{
+    fn typed_get(&self, key: &K) -> Result<Option<V>> {
+        self.get_map(key.encode(), V::decode)?.transpose()
+    }
+
+    fn typed_insert(&self, key: &K, value: &V) -> Result<()> {
+        self.insert(key.encode(), value.encode())
+    }
+
+    fn typed_remove(&self, key: &K) -> Result<()> {
+        self.remove(key.encode())
+    }
+}
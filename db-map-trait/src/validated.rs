@@ -0,0 +1,214 @@
+//! # Value validation for the `DBMap` trait.
+//!
+//! The [`Validated`] wrapper checks every value against a schema function before it is written to
+//! the wrapped database.
+use crate::{Capabilities, DBMap, Error, Page, Result};
+use std::{fmt, ops::Bound, sync::Arc};
+
+/// A `DBMap` wrapper that validates every value before it is written to the inner database.
+///
+/// The validator is called on the value passed to [`insert`][DBMap::insert] and
+/// [`fetch_and_replace_map`][DBMap::fetch_and_replace_map] (and everything built on them). If it
+/// returns `Err(msg)`, nothing is written and the operation fails with
+/// [`Error::Validation(msg)`][Error::Validation]. Keys are passed through unchecked.
+///
+/// Since `Validated` implements `DBMap` itself, it can wrap (or be wrapped by) any other `DBMap`.
+///
+/// # Examples
+///
+/// ```rust
+/// use db_map_trait::{DBMap, Error, Validated};
+/// use db_map_btreemap::BTreeMapDB;
+///
+/// // Only accept values that are exactly 8 bytes long.
+/// let db = Validated::new(BTreeMapDB::open(), |value: &[u8]| {
+///     match value.len() {
+///         8 => Ok(()),
+///         len => Err(format!("expected 8 bytes, got {len}")),
+///     }
+/// });
+///
+/// const KEY: [u8; 4] = [0x12, 0x34, 0x56, 0x78];
+///
+/// // A 3-byte value is rejected and nothing is written.
+/// match db.insert(&KEY, [1_u8, 2, 3]) {
+///     Err(Error::Validation(msg)) => assert_eq!(msg, "expected 8 bytes, got 3"),
+///     other => panic!("unexpected result: {other:?}"),
+/// }
+/// assert!(db.get(&KEY).unwrap().is_none());
+///
+/// // An 8-byte value is accepted.
+/// db.insert(&KEY, 0x123456789ABCDEF0_u64.to_be_bytes()).unwrap();
+/// assert_eq!(db.get(&KEY).unwrap(), Some(0x123456789ABCDEF0_u64.to_be_bytes().to_vec()));
+///
+/// // Replacing with an invalid value leaves the old value in place.
+/// assert!(matches!(db.fetch_and_replace(&KEY, b"bad"), Err(Error::Validation(_))));
+/// assert_eq!(db.get(&KEY).unwrap(), Some(0x123456789ABCDEF0_u64.to_be_bytes().to_vec()));
+/// ```
+pub struct Validated<M, F> {
+    inner: M,
+    validator: Arc<F>,
+}
+
+impl<M, F> Validated<M, F>
+where
+    M: DBMap,
+    F: Fn(&[u8]) -> std::result::Result<(), String>,
+{
+    /// Wrap `inner` so that every value written through it is checked by `validator`.
+    pub fn new(inner: M, validator: F) -> Self {
+        Self {
+            inner,
+            validator: Arc::new(validator),
+        }
+    }
+
+    /// Get a reference to the wrapped database.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Run the validator on a value.
+    fn validate(&self, value: &[u8]) -> Result<()> {
+        (self.validator)(value).map_err(Error::Validation)
+    }
+}
+
+impl<M: Clone, F> Clone for Validated<M, F> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            validator: self.validator.clone(),
+        }
+    }
+}
+
+impl<M: fmt::Debug, F> fmt::Debug for Validated<M, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Validated")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M, F> DBMap for Validated<M, F>
+where
+    M: DBMap,
+    F: Fn(&[u8]) -> std::result::Result<(), String>,
+{
+    fn get_map<K, G, T>(&self, key: K, mapper: G) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            G: FnOnce(&[u8]) -> T,
+    {
+        self.inner.get_map(key, mapper)
+    }
+
+    fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        self.validate(value.as_ref())?;
+        self.inner.insert(key, value)
+    }
+
+    fn fetch_and_replace_map<K, V, G, T>(&self, key: K, value: V, mapper: G) -> Result<Option<T>>
+        where
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+            G: FnOnce(&[u8]) -> T,
+    {
+        self.validate(value.as_ref())?;
+        self.inner.fetch_and_replace_map(key, value, mapper)
+    }
+
+    fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
+        self.inner.remove(key)
+    }
+
+    /// Validate the new value before it is written. If it is invalid, the current value is kept.
+    fn update<K, G>(&self, key: K, f: G) -> Result<()>
+        where
+            K: AsRef<[u8]>,
+            G: FnOnce(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let mut invalid = None;
+        self.inner.update(key, |existing| {
+            let new_value = f(existing);
+            if let Some(value) = &new_value {
+                if let Err(msg) = (self.validator)(value) {
+                    invalid = Some(msg);
+                    return existing.map(|v| v.to_vec());
+                }
+            }
+            new_value
+        })?;
+        match invalid {
+            Some(msg) => Err(Error::Validation(msg)),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate every value first, so that nothing is written if any value is invalid.
+    fn batch_insert<I, K, V>(&self, items: I) -> Result<()>
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: AsRef<[u8]>,
+            V: AsRef<[u8]>,
+    {
+        let items: Vec<(K, V)> = items.into_iter().collect();
+        for (_, value) in &items {
+            self.validate(value.as_ref())?;
+        }
+        self.inner.batch_insert(items)
+    }
+
+    fn try_for_each<G>(&self, f: G) -> Result<()>
+        where
+            G: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_for_each(f)
+    }
+
+    fn try_scan_prefix<P, G>(&self, prefix: P, f: G) -> Result<()>
+        where
+            P: AsRef<[u8]>,
+            G: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_scan_prefix(prefix, f)
+    }
+
+    fn try_scan_range<G>(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, f: G) -> Result<()>
+        where
+            G: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        self.inner.try_scan_range(start, end, f)
+    }
+
+    fn count_range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<usize> {
+        self.inner.count_range(start, end)
+    }
+
+    fn scan_page<P: AsRef<[u8]>>(&self, prefix: P, start_after: Option<&[u8]>, limit: usize) -> Result<Page>
+    {
+        self.inner.scan_page(prefix, start_after, limit)
+    }
+
+    fn len(&self) -> Result<usize> {
+        self.inner.len()
+    }
+
+    fn prepare_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        self.inner.prepare_range(start, end)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.inner.clear()
+    }
+
+    /// The values were validated when they were written, so they are moved without checking.
+    fn move_prefix<A: AsRef<[u8]>, B: AsRef<[u8]>>(&self, from_prefix: A, to_prefix: B) -> Result<usize> {
+        self.inner.move_prefix(from_prefix, to_prefix)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
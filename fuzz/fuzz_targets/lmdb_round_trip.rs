@@ -0,0 +1,103 @@
+//! Run a fuzzed sequence of operations against a temporary LMDB database and a `BTreeMap`
+//! oracle, and check that they never disagree.
+//!
+//! The input is read as a sequence of operations, each made of:
+//!
+//! * an opcode byte: `opcode % 3` is 0 for insert, 1 for get, and 2 for remove,
+//! * a big-endian `u16` key length followed by the key bytes,
+//! * for inserts, a big-endian `u16` value length followed by the value bytes.
+//!
+//! Lengths that run past the end of the input are cut short, and a trailing partial header is
+//! ignored. After the last operation, a full scan of the database has to match the oracle.
+#![no_main]
+
+use db_map_test::temp_lmdb;
+use db_map_trait::DBMap;
+use libfuzzer_sys::fuzz_target;
+use std::collections::BTreeMap;
+
+/// The largest key LMDB accepts with its default build options.
+const MAX_KEY_LEN: usize = 511;
+
+#[derive(Debug)]
+enum Op<'a> {
+    Insert(&'a [u8], &'a [u8]),
+    Get(&'a [u8]),
+    Remove(&'a [u8]),
+}
+
+/// Split the fuzzer input into operations.
+struct Ops<'a>(&'a [u8]);
+
+impl<'a> Ops<'a> {
+    fn take_u16(&mut self) -> Option<usize> {
+        let (len, rest) = self.0.split_first_chunk::<2>()?;
+        self.0 = rest;
+        Some(u16::from_be_bytes(*len) as usize)
+    }
+
+    fn take_bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.take_u16()?.min(self.0.len());
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+}
+
+impl<'a> Iterator for Ops<'a> {
+    type Item = Op<'a>;
+
+    fn next(&mut self) -> Option<Op<'a>> {
+        let (&opcode, rest) = self.0.split_first()?;
+        self.0 = rest;
+        let key = self.take_bytes()?;
+        Some(match opcode % 3 {
+            0 => Op::Insert(key, self.take_bytes()?),
+            1 => Op::Get(key),
+            _ => Op::Remove(key),
+        })
+    }
+}
+
+fn valid_key(key: &[u8]) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_LEN
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (_temp_dir, db) = temp_lmdb(None);
+    let mut oracle = BTreeMap::new();
+
+    for op in Ops(data) {
+        match op {
+            Op::Insert(key, value) => {
+                let result = db.insert(key, value);
+                if valid_key(key) {
+                    result.unwrap();
+                    oracle.insert(key.to_vec(), value.to_vec());
+                } else {
+                    assert!(result.is_err(), "LMDB accepted an invalid key: {op:?}");
+                }
+            }
+            Op::Get(key) => {
+                // LMDB may reject an invalid key instead of reporting it missing.
+                match db.get(key) {
+                    Ok(value) => assert_eq!(value.as_ref(), oracle.get(key), "{op:?}"),
+                    Err(err) => assert!(!valid_key(key), "{op:?} failed: {err}"),
+                }
+            }
+            Op::Remove(key) => {
+                // LMDB reports removing a missing key as an error, so only the result for
+                // present keys is checked. The scan at the end still checks the state.
+                let result = db.remove(key);
+                if oracle.remove(key).is_some() {
+                    result.unwrap();
+                }
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    db.for_each(|key, value| entries.push((key.to_vec(), value.to_vec()))).unwrap();
+    assert_eq!(db.len().unwrap(), oracle.len());
+    assert_eq!(entries, oracle.into_iter().collect::<Vec<_>>());
+});